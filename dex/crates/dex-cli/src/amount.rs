@@ -0,0 +1,161 @@
+use crate::error::CliError;
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Parses a human-readable amount such as `1.25` into base units of a mint with `decimals`
+pub fn parse_ui_amount(amount: &str, decimals: u8) -> Result<u64, CliError> {
+    let invalid = || CliError::InvalidAmount {
+        amount: amount.to_string(),
+        decimals,
+    };
+
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > decimals as usize
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let scale = 10u64.checked_pow(decimals as u32).ok_or_else(invalid)?;
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u64>().map_err(|_| invalid())?
+    };
+    let fraction = format!("{fraction:0<width$}", width = decimals as usize);
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u64>().map_err(|_| invalid())?
+    };
+
+    whole
+        .checked_mul(scale)
+        .and_then(|whole| whole.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// Formats base units of a mint with `decimals` as a human-readable amount, without trailing zeros
+pub fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+
+    let digits = format!("{amount:0>width$}", width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{whole}.{fraction}")
+    }
+}
+
+/// Lowers `amount` by a slippage tolerance in basis points, for minimum outputs
+pub fn apply_slippage_down(amount: u64, slippage_bps: u64) -> u64 {
+    let kept_bps = BPS_DENOMINATOR.saturating_sub(slippage_bps);
+    (amount as u128 * kept_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Raises `amount` by a slippage tolerance in basis points, for maximum inputs
+pub fn apply_slippage_up(amount: u64, slippage_bps: u64) -> u64 {
+    let allowed_bps = BPS_DENOMINATOR.saturating_add(slippage_bps);
+    let raised = (amount as u128 * allowed_bps as u128).div_ceil(BPS_DENOMINATOR as u128);
+    u64::try_from(raised).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts_into_base_units() {
+        assert_eq!(parse_ui_amount("1.25", 6).unwrap(), 1_250_000);
+        assert_eq!(parse_ui_amount("1", 6).unwrap(), 1_000_000);
+        assert_eq!(parse_ui_amount("0.000001", 6).unwrap(), 1);
+        assert_eq!(parse_ui_amount("42", 0).unwrap(), 42);
+    }
+
+    #[test]
+    fn parses_leading_and_trailing_dots() {
+        assert_eq!(parse_ui_amount(".5", 6).unwrap(), 500_000);
+        assert_eq!(parse_ui_amount("5.", 6).unwrap(), 5_000_000);
+        assert_eq!(parse_ui_amount("5.", 0).unwrap(), 5);
+        assert!(parse_ui_amount(".", 6).is_err());
+        assert!(parse_ui_amount("", 6).is_err());
+        assert!(parse_ui_amount("1.2.3", 6).is_err());
+    }
+
+    #[test]
+    fn parses_zero() {
+        assert_eq!(parse_ui_amount("0", 6).unwrap(), 0);
+        assert_eq!(parse_ui_amount("0.000000", 6).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_more_decimals_than_the_mint() {
+        assert!(parse_ui_amount("1.0000001", 6).is_err());
+        assert!(parse_ui_amount("1.0", 0).is_err());
+        assert!(parse_ui_amount(".0", 0).is_err());
+    }
+
+    #[test]
+    fn rejects_signs_and_other_characters() {
+        for amount in ["-1", "+1", "1e3", " 1", "1,5", "0x10"] {
+            assert!(parse_ui_amount(amount, 6).is_err(), "{amount}");
+        }
+    }
+
+    #[test]
+    fn rejects_amounts_overflowing_u64() {
+        assert_eq!(
+            parse_ui_amount("18446744073709.551615", 6).unwrap(),
+            u64::MAX
+        );
+        assert!(parse_ui_amount("18446744073709.551616", 6).is_err());
+        assert!(parse_ui_amount("18446744073710", 6).is_err());
+        assert!(parse_ui_amount("18446744073709551616", 0).is_err());
+        // The scale itself overflows past 19 decimals
+        assert!(parse_ui_amount("0", 20).is_err());
+    }
+
+    #[test]
+    fn formats_base_units_without_trailing_zeros() {
+        assert_eq!(format_ui_amount(1_250_000, 6), "1.25");
+        assert_eq!(format_ui_amount(1_000_000, 6), "1");
+        assert_eq!(format_ui_amount(1, 6), "0.000001");
+        assert_eq!(format_ui_amount(0, 6), "0");
+        assert_eq!(format_ui_amount(42, 0), "42");
+        assert_eq!(format_ui_amount(u64::MAX, 6), "18446744073709.551615");
+    }
+
+    #[test]
+    fn formatted_amounts_parse_back() {
+        for (amount, decimals) in [(0, 9), (1, 9), (1_234_567_890, 9), (u64::MAX, 19), (7, 0)] {
+            let formatted = format_ui_amount(amount, decimals);
+            assert_eq!(parse_ui_amount(&formatted, decimals).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn slippage_rounds_against_the_user() {
+        // Minimum outputs round down, maximum inputs round up
+        assert_eq!(apply_slippage_down(999, 50), 994);
+        assert_eq!(apply_slippage_up(999, 50), 1_004);
+        assert_eq!(apply_slippage_down(1_000, 0), 1_000);
+        assert_eq!(apply_slippage_up(1_000, 0), 1_000);
+    }
+
+    #[test]
+    fn slippage_saturates() {
+        assert_eq!(apply_slippage_down(1_000, BPS_DENOMINATOR + 1), 0);
+        assert_eq!(apply_slippage_up(u64::MAX, 1), u64::MAX);
+        assert_eq!(apply_slippage_down(u64::MAX, 0), u64::MAX);
+    }
+}
@@ -0,0 +1,272 @@
+use std::mem::offset_of;
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+    state::{Account as TokenAccountState, Mint as MintState},
+};
+use dex::{DexState, LiquidityPool, PoolRegistryEntry, POOLS_PER_REGISTRY_PAGE};
+use dex_client::{
+    accounts::{deserialize_dex_state, deserialize_liquidity_pool, deserialize_pool_registry_page},
+    pda::find_pool_registry_page_address,
+};
+use serde_json::{json, Value};
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::{from_account, Account},
+    clock::Clock,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    signature::Keypair,
+    signer::Signer,
+    sysvar,
+    transaction::Transaction,
+};
+
+use crate::{config::load_keypair, error::CliError};
+
+/// Most accounts a single `getMultipleAccounts` request may ask for
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Connection to a cluster plus the settings shared by every command
+pub struct Chain {
+    pub rpc: RpcClient,
+    // Path of the signer keypair, only read by commands that send transactions
+    pub keypair_path: String,
+    // Simulate transactions instead of sending them
+    pub simulate: bool,
+    // Compute unit limit requested for transactions, the runtime default when None
+    pub compute_unit_limit: Option<u32>,
+}
+
+/// What the CLI needs to know about a pool mint
+pub struct MintInfo {
+    pub address: Pubkey,
+    // Token program owning the mint
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    // Token-2022 transfer fee, if the mint has one
+    pub transfer_fee: Option<TransferFeeConfig>,
+}
+
+impl MintInfo {
+    /// Amount the destination receives when `amount` is transferred in `epoch`
+    pub fn amount_received(&self, epoch: u64, amount: u64) -> Result<u64, CliError> {
+        let Some(config) = &self.transfer_fee else {
+            return Ok(amount);
+        };
+
+        config
+            .calculate_epoch_fee(epoch, amount)
+            .and_then(|fee| amount.checked_sub(fee))
+            .ok_or(CliError::TransferFee(self.address))
+    }
+
+    /// Amount to transfer in `epoch` so the destination receives exactly `amount_received`
+    pub fn amount_to_send(&self, epoch: u64, amount_received: u64) -> Result<u64, CliError> {
+        let Some(config) = &self.transfer_fee else {
+            return Ok(amount_received);
+        };
+
+        config
+            .calculate_inverse_epoch_fee(epoch, amount_received)
+            .and_then(|fee| amount_received.checked_add(fee))
+            .ok_or(CliError::TransferFee(self.address))
+    }
+}
+
+impl Chain {
+    /// Reads the signer keypair
+    pub fn payer(&self) -> Result<Keypair, CliError> {
+        load_keypair(&self.keypair_path)
+    }
+
+    /// Fetches an account, None if it doesn't exist
+    pub fn get_account(&self, address: &Pubkey) -> Result<Option<Account>, CliError> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value)
+    }
+
+    /// Fetches an account that has to exist
+    fn get_existing_account(&self, address: &Pubkey) -> Result<Account, CliError> {
+        self.get_account(address)?
+            .ok_or(CliError::AccountNotFound(*address))
+    }
+
+    /// Fetches and decodes the DEX state
+    pub fn fetch_dex_state(&self, address: &Pubkey) -> Result<DexState, CliError> {
+        let account = self.get_existing_account(address)?;
+        Ok(deserialize_dex_state(&account.data)?)
+    }
+
+    /// Fetches and decodes a liquidity pool
+    pub fn fetch_pool(&self, address: &Pubkey) -> Result<LiquidityPool, CliError> {
+        let account = self.get_existing_account(address)?;
+        Ok(deserialize_liquidity_pool(&account.data)?)
+    }
+
+    /// Fetches a mint of either token program
+    pub fn fetch_mint(&self, address: &Pubkey) -> Result<MintInfo, CliError> {
+        let account = self.get_existing_account(address)?;
+        let is_token_2022 = account.owner == spl_token_2022::ID;
+        if !is_token_2022 && account.owner != anchor_spl::token::ID {
+            return Err(CliError::InvalidAccount(*address, "mint"));
+        }
+
+        let mint = StateWithExtensions::<MintState>::unpack(&account.data)
+            .map_err(|_| CliError::InvalidAccount(*address, "mint"))?;
+        let transfer_fee = if is_token_2022 {
+            mint.get_extension::<TransferFeeConfig>().ok().copied()
+        } else {
+            None
+        };
+
+        Ok(MintInfo {
+            address: *address,
+            token_program: account.owner,
+            decimals: mint.base.decimals,
+            transfer_fee,
+        })
+    }
+
+    /// Fetches the balance of a token account
+    pub fn fetch_token_amount(&self, address: &Pubkey) -> Result<u64, CliError> {
+        let account = self.get_existing_account(address)?;
+        let token_account = StateWithExtensions::<TokenAccountState>::unpack(&account.data)
+            .map_err(|_| CliError::InvalidAccount(*address, "token account"))?;
+
+        Ok(token_account.base.amount)
+    }
+
+    /// Fetches the pool reserves, as held by its vaults
+    pub fn fetch_reserves(&self, pool: &LiquidityPool) -> Result<(u64, u64), CliError> {
+        Ok((
+            self.fetch_token_amount(&pool.token_a_account)?,
+            self.fetch_token_amount(&pool.token_b_account)?,
+        ))
+    }
+
+    /// Fetches the cluster clock, for dynamic fees and transfer fee epochs
+    pub fn fetch_clock(&self) -> Result<Clock, CliError> {
+        let account = self.get_existing_account(&sysvar::clock::ID)?;
+        from_account(&account).ok_or(CliError::InvalidAccount(sysvar::clock::ID, "clock"))
+    }
+
+    /// Fetches the registry entries of every listed pool of a DEX, in creation order
+    pub fn fetch_registry(
+        &self,
+        dex_state_address: &Pubkey,
+        dex_state: &DexState,
+    ) -> Result<Vec<PoolRegistryEntry>, CliError> {
+        let pages_count = dex_state
+            .pools_count
+            .div_ceil(POOLS_PER_REGISTRY_PAGE as u64);
+        let page_addresses: Vec<Pubkey> = (0..pages_count)
+            .map(|page_index| {
+                find_pool_registry_page_address(
+                    dex_state_address,
+                    page_index * POOLS_PER_REGISTRY_PAGE as u64,
+                )
+                .0
+            })
+            .collect();
+
+        let mut entries = Vec::new();
+        for addresses in page_addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.rpc.get_multiple_accounts(addresses)?;
+            // Pools created before the registry aren't listed, pages of those alone don't exist
+            for account in accounts.into_iter().flatten() {
+                let page = deserialize_pool_registry_page(&account.data)?;
+                entries.extend(
+                    page.entries[..page.entries_count as usize]
+                        .iter()
+                        .filter(|entry| entry.pool != Pubkey::default()),
+                );
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Fetches the migrated pools of the first release, which the registry doesn't list
+    /// Scans the program accounts for pools whose registry index is UNLISTED_REGISTRY_INDEX
+    pub fn fetch_unlisted_pools(&self) -> Result<Vec<(Pubkey, LiquidityPool)>, CliError> {
+        let registry_index_offset = 8 + offset_of!(LiquidityPool, registry_index);
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize((8 + LiquidityPool::LEN) as u64),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    LiquidityPool::DISCRIMINATOR.to_vec(),
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    registry_index_offset,
+                    LiquidityPool::UNLISTED_REGISTRY_INDEX
+                        .to_le_bytes()
+                        .to_vec(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+
+        self.rpc
+            .get_program_accounts_with_config(&dex::ID, config)?
+            .into_iter()
+            .map(|(address, account)| Ok((address, deserialize_liquidity_pool(&account.data)?)))
+            .collect()
+    }
+
+    /// Signs `instructions` with the payer and `extra_signers`, then sends or simulates them
+    /// Returns the signature, or the simulation logs and compute units
+    pub fn send(
+        &self,
+        payer: &Keypair,
+        mut instructions: Vec<Instruction>,
+        extra_signers: &[&Keypair],
+    ) -> Result<Value, CliError> {
+        if let Some(units) = self.compute_unit_limit {
+            instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+
+        let mut signers: Vec<&dyn Signer> = vec![payer];
+        signers.extend(extra_signers.iter().map(|signer| *signer as &dyn Signer));
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &signers,
+            self.rpc.get_latest_blockhash()?,
+        );
+
+        if !self.simulate {
+            let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+            return Ok(json!({ "signature": signature.to_string() }));
+        }
+
+        let simulation = self.rpc.simulate_transaction(&transaction)?.value;
+        let logs = simulation.logs.unwrap_or_default();
+        if let Some(error) = simulation.err {
+            return Err(CliError::SimulationFailed(format!(
+                "{error}\n{}",
+                logs.join("\n")
+            )));
+        }
+
+        Ok(json!({
+            "simulated": true,
+            "units_consumed": simulation.units_consumed,
+            "logs": logs,
+        }))
+    }
+}
@@ -0,0 +1,770 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use dex::{
+    LiquidityPool, MintListing, PoolCreationMode, ProtocolFeeMode,
+    PERMISSION_CONFIGURE_DYNAMIC_FEE, PERMISSION_CREATE_POOL,
+};
+use dex_client::{
+    instructions,
+    pda::{
+        find_creator_permissions_address, find_dex_state_address, find_extension_allowlist_address,
+        find_liquidity_pool_address,
+    },
+    quote::{quote_deposit, quote_swap, quote_swap_exact_output, quote_withdrawal, SwapQuote},
+};
+use serde_json::{json, Value};
+use solana_sdk::{instruction::Instruction, signature::Keypair, signer::Signer};
+
+use crate::{
+    amount::{apply_slippage_down, apply_slippage_up, format_ui_amount, parse_ui_amount},
+    chain::{Chain, MintInfo},
+    error::CliError,
+};
+
+/// Address of the DEX state, which is derived from the program's admin key
+fn dex_state_address() -> Pubkey {
+    find_dex_state_address(&dex::ADMIN_PUBKEY).0
+}
+
+/// Fee rate as a percentage, for display only
+fn format_fee_rate(fee_numerator: u64, fee_denominator: u64) -> String {
+    if fee_denominator == 0 {
+        return "0%".to_string();
+    }
+    format!("{}%", fee_numerator as f64 * 100.0 / fee_denominator as f64)
+}
+
+/// Appends the fields of the transaction result to a command report
+fn with_transaction(mut report: Value, transaction: Value) -> Value {
+    if let (Some(report), Value::Object(transaction)) = (report.as_object_mut(), transaction) {
+        report.extend(transaction);
+    }
+    report
+}
+
+/// Fetches a pool together with its mints
+fn fetch_pool_with_mints(
+    chain: &Chain,
+    pool_address: &Pubkey,
+) -> Result<(LiquidityPool, MintInfo, MintInfo), CliError> {
+    let pool = chain.fetch_pool(pool_address)?;
+    let token_a = chain.fetch_mint(&pool.token_a_mint)?;
+    let token_b = chain.fetch_mint(&pool.token_b_mint)?;
+
+    Ok((pool, token_a, token_b))
+}
+
+/// Initializes the DEX state, signed by the admin keypair
+pub fn init(
+    chain: &Chain,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    fee_collector: Option<Pubkey>,
+) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let fee_collector = fee_collector.unwrap_or(admin.pubkey());
+
+    let instruction = instructions::initialize(
+        &admin.pubkey(),
+        fee_numerator,
+        fee_denominator,
+        protocol_fee_percentage,
+        &fee_collector,
+    );
+    let transaction = chain.send(&admin, vec![instruction], &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "dex_state": find_dex_state_address(&admin.pubkey()).0.to_string(),
+            "fee": format_fee_rate(fee_numerator, fee_denominator),
+            "protocol_fee_percentage": protocol_fee_percentage,
+            "fee_collector": fee_collector.to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Changes the DEX fees and fee collector, signed by the admin keypair
+pub fn set_fees(
+    chain: &Chain,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    fee_collector: Option<Pubkey>,
+) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let fee_collector = fee_collector.unwrap_or(admin.pubkey());
+
+    let instruction = instructions::set_fees(
+        &admin.pubkey(),
+        fee_numerator,
+        fee_denominator,
+        protocol_fee_percentage,
+        &fee_collector,
+    );
+    let transaction = chain.send(&admin, vec![instruction], &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "fee": format_fee_rate(fee_numerator, fee_denominator),
+            "protocol_fee_percentage": protocol_fee_percentage,
+            "fee_collector": fee_collector.to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Creates the vaults and the pool of a pair on a fee tier
+pub fn create_pool(
+    chain: &Chain,
+    token_a_mint: &Pubkey,
+    token_b_mint: &Pubkey,
+    fee_tier_index: u16,
+) -> Result<Value, CliError> {
+    let owner = chain.payer()?;
+    let token_a = chain.fetch_mint(token_a_mint)?;
+    let dex_state = dex_state_address();
+    let pools_count = chain.fetch_dex_state(&dex_state)?.pools_count;
+    let lp_token_mint = Keypair::new();
+
+    // The allowlist and the creator's permissions are only passed when governance created them
+    let extension_allowlist = find_extension_allowlist_address(&dex_state).0;
+    let extension_allowlist = chain
+        .get_account(&extension_allowlist)?
+        .map(|_| extension_allowlist);
+    let creator_permissions = find_creator_permissions_address(&dex_state, &owner.pubkey()).0;
+    let creator_permissions = chain
+        .get_account(&creator_permissions)?
+        .map(|_| creator_permissions);
+
+    let mut pool_instructions = instructions::create_pool_vaults(
+        &owner.pubkey(),
+        token_a_mint,
+        token_b_mint,
+        fee_tier_index,
+        &token_a.token_program,
+    )
+    .to_vec();
+    pool_instructions.push(instructions::create_pool(
+        &owner.pubkey(),
+        &dex_state,
+        pools_count,
+        token_a_mint,
+        token_b_mint,
+        fee_tier_index,
+        &lp_token_mint.pubkey(),
+        &token_a.token_program,
+        extension_allowlist,
+        creator_permissions,
+    ));
+    let transaction = chain.send(&owner, pool_instructions, &[&lp_token_mint])?;
+
+    let pool = find_liquidity_pool_address(token_a_mint, token_b_mint, fee_tier_index).0;
+    Ok(with_transaction(
+        json!({
+            "pool": pool.to_string(),
+            "token_a_mint": token_a_mint.to_string(),
+            "token_b_mint": token_b_mint.to_string(),
+            "fee_tier_index": fee_tier_index,
+            "lp_token_mint": lp_token_mint.pubkey().to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Deposits both tokens of a pool, amounts given in each mint's decimals
+pub fn deposit(
+    chain: &Chain,
+    pool_address: &Pubkey,
+    token_a_amount: &str,
+    token_b_amount: &str,
+) -> Result<Value, CliError> {
+    let owner = chain.payer()?;
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+    let lp_token = chain.fetch_mint(&pool.lp_token_mint)?;
+    let fee_collector = chain.fetch_dex_state(&dex_state_address())?.fee_collector;
+    let token_a_amount = parse_ui_amount(token_a_amount, token_a.decimals)?;
+    let token_b_amount = parse_ui_amount(token_b_amount, token_b.decimals)?;
+
+    // The pool values the deposit on what reaches the vaults
+    let epoch = chain.fetch_clock()?.epoch;
+    let (reserve_a, reserve_b) = chain.fetch_reserves(&pool)?;
+    let lp_tokens = quote_deposit(
+        &pool,
+        reserve_a,
+        reserve_b,
+        token_a.amount_received(epoch, token_a_amount)?,
+        token_b.amount_received(epoch, token_b_amount)?,
+    )?;
+
+    let instructions = with_fee_collector_lp_token(
+        &owner.pubkey(),
+        &pool,
+        &fee_collector,
+        instructions::deposit_liquidity(
+            &owner.pubkey(),
+            pool_address,
+            &pool,
+            &fee_collector,
+            &token_a.token_program,
+            &lp_token.token_program,
+            token_a_amount,
+            token_b_amount,
+        ),
+    );
+    let transaction = chain.send(&owner, instructions, &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "pool": pool_address.to_string(),
+            "token_a_amount": format_ui_amount(token_a_amount, token_a.decimals),
+            "token_b_amount": format_ui_amount(token_b_amount, token_b.decimals),
+            "expected_lp_tokens": format_ui_amount(lp_tokens, lp_token.decimals),
+        }),
+        transaction,
+    ))
+}
+
+/// Burns LP tokens of a pool, amount given in the LP mint's decimals
+pub fn withdraw(chain: &Chain, pool_address: &Pubkey, lp_amount: &str) -> Result<Value, CliError> {
+    let owner = chain.payer()?;
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+    let lp_token = chain.fetch_mint(&pool.lp_token_mint)?;
+    let fee_collector = chain.fetch_dex_state(&dex_state_address())?.fee_collector;
+    let lp_amount = parse_ui_amount(lp_amount, lp_token.decimals)?;
+
+    let epoch = chain.fetch_clock()?.epoch;
+    let (reserve_a, reserve_b) = chain.fetch_reserves(&pool)?;
+    let (token_a_amount, token_b_amount) =
+        quote_withdrawal(&pool, reserve_a, reserve_b, lp_amount)?;
+
+    let instructions = with_fee_collector_lp_token(
+        &owner.pubkey(),
+        &pool,
+        &fee_collector,
+        instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            pool_address,
+            &pool,
+            &fee_collector,
+            &token_a.token_program,
+            &lp_token.token_program,
+            lp_amount,
+        ),
+    );
+    let transaction = chain.send(&owner, instructions, &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "pool": pool_address.to_string(),
+            "lp_amount": format_ui_amount(lp_amount, lp_token.decimals),
+            "expected_token_a_amount": format_ui_amount(
+                token_a.amount_received(epoch, token_a_amount)?,
+                token_a.decimals,
+            ),
+            "expected_token_b_amount": format_ui_amount(
+                token_b.amount_received(epoch, token_b_amount)?,
+                token_b.decimals,
+            ),
+        }),
+        transaction,
+    ))
+}
+
+/// Prepends the creation of the fee collector's LP token account to a deposit or withdrawal of
+/// a pool minting its protocol fee, which fails without it when it mints protocol fee LP tokens
+fn with_fee_collector_lp_token(
+    payer: &Pubkey,
+    pool: &LiquidityPool,
+    fee_collector: &Pubkey,
+    instruction: Instruction,
+) -> Vec<Instruction> {
+    instructions::create_fee_collector_lp_token(payer, pool, fee_collector)
+        .into_iter()
+        .chain([instruction])
+        .collect()
+}
+
+/// A priced swap, with amounts as the user sends and receives them
+struct SwapPlan {
+    pool: LiquidityPool,
+    source: MintInfo,
+    destination: MintInfo,
+    // Amount of the source token leaving the user's account
+    input_amount: u64,
+    // Amount of the destination token reaching the user's account
+    output_amount: u64,
+    quote: SwapQuote,
+}
+
+/// Prices a swap from `source_mint`, netting Token-2022 transfer fees on both legs like the
+/// program does
+/// `amount` is the input for exact input swaps and the output for exact output swaps
+fn plan_swap(
+    chain: &Chain,
+    pool_address: &Pubkey,
+    source_mint: &Pubkey,
+    amount: &str,
+    exact_output: bool,
+) -> Result<SwapPlan, CliError> {
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+    let (source, destination) = if *source_mint == pool.token_a_mint {
+        (token_a, token_b)
+    } else if *source_mint == pool.token_b_mint {
+        (token_b, token_a)
+    } else {
+        return Err(dex_client::ClientError::MintNotInPool(*source_mint).into());
+    };
+
+    let clock = chain.fetch_clock()?;
+    let (reserve_a, reserve_b) = chain.fetch_reserves(&pool)?;
+
+    let (input_amount, output_amount, quote) = if exact_output {
+        let output_amount = parse_ui_amount(amount, destination.decimals)?;
+        let quote = quote_swap_exact_output(
+            &pool,
+            reserve_a,
+            reserve_b,
+            source_mint,
+            destination.amount_to_send(clock.epoch, output_amount)?,
+            clock.unix_timestamp,
+        )?;
+        let input_amount = source.amount_to_send(clock.epoch, quote.input_amount)?;
+        (input_amount, output_amount, quote)
+    } else {
+        let input_amount = parse_ui_amount(amount, source.decimals)?;
+        let quote = quote_swap(
+            &pool,
+            reserve_a,
+            reserve_b,
+            source_mint,
+            source.amount_received(clock.epoch, input_amount)?,
+            clock.unix_timestamp,
+        )?;
+        let output_amount = destination.amount_received(clock.epoch, quote.output_amount)?;
+        (input_amount, output_amount, quote)
+    };
+
+    Ok(SwapPlan {
+        pool,
+        source,
+        destination,
+        input_amount,
+        output_amount,
+        quote,
+    })
+}
+
+/// Report fields shared by `quote` and `swap`
+fn swap_report(pool_address: &Pubkey, plan: &SwapPlan) -> Value {
+    json!({
+        "pool": pool_address.to_string(),
+        "source_mint": plan.source.address.to_string(),
+        "destination_mint": plan.destination.address.to_string(),
+        "input_amount": format_ui_amount(plan.input_amount, plan.source.decimals),
+        "output_amount": format_ui_amount(plan.output_amount, plan.destination.decimals),
+        "fee": format_fee_rate(plan.quote.fee_numerator, plan.quote.fee_denominator),
+        "protocol_fee": format_ui_amount(plan.quote.protocol_fee, plan.source.decimals),
+    })
+}
+
+/// Prices a swap without sending anything
+pub fn quote(
+    chain: &Chain,
+    pool_address: &Pubkey,
+    source_mint: &Pubkey,
+    amount: &str,
+    exact_output: bool,
+) -> Result<Value, CliError> {
+    let plan = plan_swap(chain, pool_address, source_mint, amount, exact_output)?;
+    Ok(swap_report(pool_address, &plan))
+}
+
+/// Swaps from `source_mint`, bounding the other side of the trade by `slippage_bps`
+pub fn swap(
+    chain: &Chain,
+    pool_address: &Pubkey,
+    source_mint: &Pubkey,
+    amount: &str,
+    exact_output: bool,
+    slippage_bps: u64,
+) -> Result<Value, CliError> {
+    let owner = chain.payer()?;
+    let plan = plan_swap(chain, pool_address, source_mint, amount, exact_output)?;
+
+    let (instruction, limit) = if exact_output {
+        let maximum_input_amount = apply_slippage_up(plan.input_amount, slippage_bps);
+        let instruction = instructions::swap_exact_output(
+            &owner.pubkey(),
+            pool_address,
+            &plan.pool,
+            source_mint,
+            &plan.source.token_program,
+            plan.output_amount,
+            maximum_input_amount,
+        )?;
+        let limit = json!({
+            "maximum_input_amount": format_ui_amount(maximum_input_amount, plan.source.decimals),
+        });
+        (instruction, limit)
+    } else {
+        let minimum_output_amount = apply_slippage_down(plan.output_amount, slippage_bps);
+        let instruction = instructions::swap(
+            &owner.pubkey(),
+            pool_address,
+            &plan.pool,
+            source_mint,
+            &plan.source.token_program,
+            plan.input_amount,
+            minimum_output_amount,
+        )?;
+        let limit = json!({
+            "minimum_output_amount": format_ui_amount(
+                minimum_output_amount,
+                plan.destination.decimals,
+            ),
+        });
+        (instruction, limit)
+    };
+    let transaction = chain.send(&owner, vec![instruction], &[])?;
+
+    Ok(with_transaction(
+        with_transaction(swap_report(pool_address, &plan), limit),
+        transaction,
+    ))
+}
+
+/// Sends the protocol fees of a pool to the fee collector, signed by the admin keypair
+/// Creates the fee collector's token accounts when they don't exist yet
+pub fn collect_fees(chain: &Chain, pool_address: &Pubkey) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let dex_state_address = dex_state_address();
+    let dex_state = chain.fetch_dex_state(&dex_state_address)?;
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+
+    let fee_instructions = vec![
+        create_associated_token_account_idempotent(
+            &admin.pubkey(),
+            &dex_state.fee_collector,
+            &pool.token_a_mint,
+            &token_a.token_program,
+        ),
+        create_associated_token_account_idempotent(
+            &admin.pubkey(),
+            &dex_state.fee_collector,
+            &pool.token_b_mint,
+            &token_b.token_program,
+        ),
+        instructions::collect_fees(
+            &admin.pubkey(),
+            &dex_state_address,
+            &dex_state.fee_collector,
+            pool_address,
+            &pool,
+            &token_a.token_program,
+        ),
+    ];
+    let transaction = chain.send(&admin, fee_instructions, &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "pool": pool_address.to_string(),
+            "fee_collector": dex_state.fee_collector.to_string(),
+            "token_a_fees": format_ui_amount(pool.protocol_fees_token_a, token_a.decimals),
+            "token_b_fees": format_ui_amount(pool.protocol_fees_token_b, token_b.decimals),
+        }),
+        transaction,
+    ))
+}
+
+/// Closes an empty pool, signed by the pool creator or the admin
+/// Creates the fee collector's token accounts that receive leftover vault dust when needed
+pub fn close_pool(chain: &Chain, pool_address: &Pubkey) -> Result<Value, CliError> {
+    let authority = chain.payer()?;
+    let dex_state_address = dex_state_address();
+    let dex_state = chain.fetch_dex_state(&dex_state_address)?;
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+    let lp_token = chain.fetch_mint(&pool.lp_token_mint)?;
+
+    let close_instructions = vec![
+        create_associated_token_account_idempotent(
+            &authority.pubkey(),
+            &dex_state.fee_collector,
+            &pool.token_a_mint,
+            &token_a.token_program,
+        ),
+        create_associated_token_account_idempotent(
+            &authority.pubkey(),
+            &dex_state.fee_collector,
+            &pool.token_b_mint,
+            &token_b.token_program,
+        ),
+        instructions::close_pool(
+            &authority.pubkey(),
+            &dex_state_address,
+            &dex_state.fee_collector,
+            pool_address,
+            &pool,
+            &token_a.token_program,
+            &lp_token.token_program,
+        ),
+    ];
+    let transaction = chain.send(&authority, close_instructions, &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "pool": pool_address.to_string(),
+            "creator": pool.creator.to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Switches who may create pools, signed by the admin
+pub fn set_pool_creation_mode(chain: &Chain, mode: PoolCreationMode) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::set_pool_creation_mode(&admin.pubkey(), mode)],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "pool_creation_mode": format!("{:?}", mode),
+        }),
+        transaction,
+    ))
+}
+
+/// Replaces the permissions of a pool creator, signed by the admin
+pub fn set_creator_permissions(
+    chain: &Chain,
+    creator: &Pubkey,
+    permissions: u8,
+) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let dex_state = dex_state_address();
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::set_creator_permissions(
+            &admin.pubkey(),
+            &dex_state,
+            creator,
+            permissions,
+        )],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "creator": creator.to_string(),
+            "create_pool": permissions & PERMISSION_CREATE_POOL != 0,
+            "configure_dynamic_fee": permissions & PERMISSION_CONFIGURE_DYNAMIC_FEE != 0,
+        }),
+        transaction,
+    ))
+}
+
+/// Lists a mint as allowed, denied or unknown, signed by the admin
+pub fn set_mint_status(
+    chain: &Chain,
+    mint: &Pubkey,
+    status: MintListing,
+) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::set_mint_status(&admin.pubkey(), mint, status)],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "mint": mint.to_string(),
+            "status": format!("{:?}", status),
+        }),
+        transaction,
+    ))
+}
+
+/// Makes new pools accept only allowed mints, or any mint that isn't denied, signed by the admin
+pub fn set_require_allowed_mints(chain: &Chain, required: bool) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::set_require_allowed_mints(
+            &admin.pubkey(),
+            required,
+        )],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "require_allowed_mints": required,
+        }),
+        transaction,
+    ))
+}
+
+/// Switches how pools created from now on take the protocol fee, signed by the admin
+pub fn set_protocol_fee_mode(chain: &Chain, mode: ProtocolFeeMode) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::set_protocol_fee_mode(&admin.pubkey(), mode)],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "protocol_fee_mode": format!("{:?}", mode),
+        }),
+        transaction,
+    ))
+}
+
+/// Upgrades a pool to the current account layout, the payer funding the extra rent
+pub fn migrate_pool(chain: &Chain, pool_address: &Pubkey) -> Result<Value, CliError> {
+    let payer = chain.payer()?;
+    let transaction = chain.send(
+        &payer,
+        vec![instructions::migrate_pool(&payer.pubkey(), pool_address)],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "pool": pool_address.to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Upgrades the DEX state to the current account layout, signed by the admin
+pub fn migrate_state(chain: &Chain) -> Result<Value, CliError> {
+    let admin = chain.payer()?;
+    let transaction = chain.send(
+        &admin,
+        vec![instructions::migrate_state(&admin.pubkey())],
+        &[],
+    )?;
+
+    Ok(with_transaction(
+        json!({
+            "dex_state": dex_state_address().to_string(),
+        }),
+        transaction,
+    ))
+}
+
+/// Side of a pool in `show-pool`
+fn pool_token_report(mint: &MintInfo, vault: &Pubkey, reserve: u64, protocol_fees: u64) -> Value {
+    json!({
+        "mint": mint.address.to_string(),
+        "token_program": mint.token_program.to_string(),
+        "decimals": mint.decimals,
+        "vault": vault.to_string(),
+        "reserve": format_ui_amount(reserve, mint.decimals),
+        "protocol_fees": format_ui_amount(protocol_fees, mint.decimals),
+        "transfer_fee": mint.transfer_fee.is_some(),
+    })
+}
+
+/// Prints the state of a pool with amounts in each mint's decimals
+pub fn show_pool(chain: &Chain, pool_address: &Pubkey) -> Result<Value, CliError> {
+    let (pool, token_a, token_b) = fetch_pool_with_mints(chain, pool_address)?;
+    let lp_token = chain.fetch_mint(&pool.lp_token_mint)?;
+    let (reserve_a, reserve_b) = chain.fetch_reserves(&pool)?;
+
+    // Price of one whole token A in whole tokens B
+    let price = if reserve_a == 0 {
+        0.0
+    } else {
+        (reserve_b as f64 / 10f64.powi(token_b.decimals as i32))
+            / (reserve_a as f64 / 10f64.powi(token_a.decimals as i32))
+    };
+
+    Ok(json!({
+        "pool": pool_address.to_string(),
+        "creator": pool.creator.to_string(),
+        "created_at": pool.created_at,
+        "created_slot": pool.created_slot,
+        "version": pool.version,
+        "fee_tier_index": pool.fee_tier_index,
+        "fee": format_fee_rate(pool.fee_numerator, pool.fee_denominator),
+        "dynamic_fee": pool.dynamic_fee.is_enabled(),
+        "protocol_fee_percentage": pool.protocol_fee_percentage,
+        "protocol_fee_mode": if pool.mints_protocol_fee() { "MintLp" } else { "Accrued" },
+        "price": price.to_string(),
+        "lp_token_mint": pool.lp_token_mint.to_string(),
+        "lp_supply": format_ui_amount(pool.total_liquidity, lp_token.decimals),
+        "token_a": pool_token_report(
+            &token_a,
+            &pool.token_a_account,
+            reserve_a,
+            pool.protocol_fees_token_a,
+        ),
+        "token_b": pool_token_report(
+            &token_b,
+            &pool.token_b_account,
+            reserve_b,
+            pool.protocol_fees_token_b,
+        ),
+    }))
+}
+
+/// Lists the pools of the DEX from its on-chain registry, optionally only those trading `mint`
+/// Migrated pools of the first release aren't in the registry, they are found by scanning the
+/// program accounts and listed after it, unless `registry_only`
+pub fn list_pools(
+    chain: &Chain,
+    mint: Option<Pubkey>,
+    registry_only: bool,
+) -> Result<Value, CliError> {
+    let trades_mint = |token_a_mint: &Pubkey, token_b_mint: &Pubkey| {
+        mint.is_none_or(|mint| *token_a_mint == mint || *token_b_mint == mint)
+    };
+
+    let dex_state_address = dex_state_address();
+    let dex_state = chain.fetch_dex_state(&dex_state_address)?;
+    let mut pools: Vec<Value> = chain
+        .fetch_registry(&dex_state_address, &dex_state)?
+        .iter()
+        .filter(|entry| trades_mint(&entry.token_a_mint, &entry.token_b_mint))
+        .map(|entry| {
+            json!({
+                "pool": entry.pool.to_string(),
+                "token_a_mint": entry.token_a_mint.to_string(),
+                "token_b_mint": entry.token_b_mint.to_string(),
+                "created_slot": entry.created_slot,
+                "closed": entry.closed,
+                "listed": true,
+            })
+        })
+        .collect();
+
+    if !registry_only {
+        // Their accounts are closed with the pool, so they are all open
+        pools.extend(
+            chain
+                .fetch_unlisted_pools()?
+                .iter()
+                .filter(|(_, pool)| trades_mint(&pool.token_a_mint, &pool.token_b_mint))
+                .map(|(address, pool)| {
+                    json!({
+                        "pool": address.to_string(),
+                        "token_a_mint": pool.token_a_mint.to_string(),
+                        "token_b_mint": pool.token_b_mint.to_string(),
+                        "created_slot": pool.created_slot,
+                        "closed": false,
+                        "listed": false,
+                    })
+                }),
+        );
+    }
+
+    Ok(Value::Array(pools))
+}
@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use solana_sdk::signature::{read_keypair_file, Keypair};
+
+use crate::error::CliError;
+
+/// Keypair used when `--keypair` is not given, the Solana CLI default
+pub const DEFAULT_KEYPAIR_PATH: &str = "~/.config/solana/id.json";
+
+/// RPC URL of a cluster moniker, or the argument itself when it already is a URL
+/// Accepts the same monikers as the Solana CLI: localnet, devnet, testnet, mainnet-beta and
+/// their first letters
+pub fn resolve_cluster_url(cluster: &str) -> String {
+    match cluster {
+        "localnet" | "l" => "http://127.0.0.1:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "t" => "https://api.testnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
+
+/// Reads the signer keypair, expanding a leading `~` to the home directory
+pub fn load_keypair(path: &str) -> Result<Keypair, CliError> {
+    let expanded = match path.strip_prefix("~/") {
+        Some(relative) => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(relative))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    };
+
+    read_keypair_file(&expanded).map_err(|error| CliError::Keypair {
+        path: expanded.display().to_string(),
+        reason: error.to_string(),
+    })
+}
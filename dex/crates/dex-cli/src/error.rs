@@ -0,0 +1,37 @@
+use anchor_lang::prelude::Pubkey;
+use dex_client::ClientError;
+
+/// Errors reported by the CLI
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    // Instruction building, decoding or quoting failed
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    // The RPC node rejected a request or the transaction failed
+    #[error(transparent)]
+    Rpc(Box<solana_rpc_client_api::client_error::Error>),
+    // The signer keypair could not be read
+    #[error("failed to read keypair {path}: {reason}")]
+    Keypair { path: String, reason: String },
+    // An amount that doesn't parse with the decimals of its mint
+    #[error("invalid amount {amount:?} for a mint with {decimals} decimals")]
+    InvalidAmount { amount: String, decimals: u8 },
+    // A required account does not exist on the cluster
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    // An account that is not the expected kind of account
+    #[error("account {0} is not a valid {1}")]
+    InvalidAccount(Pubkey, &'static str),
+    // The transfer fee of a Token-2022 mint can't be applied to an amount
+    #[error("transfer fee calculation failed for mint {0}")]
+    TransferFee(Pubkey),
+    // The simulated transaction failed
+    #[error("simulation failed: {0}")]
+    SimulationFailed(String),
+}
+
+impl From<solana_rpc_client_api::client_error::Error> for CliError {
+    fn from(error: solana_rpc_client_api::client_error::Error) -> Self {
+        CliError::Rpc(Box::new(error))
+    }
+}
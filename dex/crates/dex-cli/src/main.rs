@@ -0,0 +1,349 @@
+//! Command-line tool for operating the dex program against a local validator or any RPC node
+//!
+//! Amounts are read and printed in each mint's decimals. Every command that sends a transaction
+//! accepts `--simulate`, and `--output json` prints machine-readable results.
+
+mod amount;
+mod chain;
+mod commands;
+mod config;
+mod error;
+mod output;
+
+use anchor_lang::prelude::Pubkey;
+use clap::{Parser, Subcommand, ValueEnum};
+use dex::{
+    MintListing, PoolCreationMode, ProtocolFeeMode, PERMISSION_CONFIGURE_DYNAMIC_FEE,
+    PERMISSION_CREATE_POOL,
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::{
+    chain::Chain,
+    config::{resolve_cluster_url, DEFAULT_KEYPAIR_PATH},
+    error::CliError,
+    output::OutputFormat,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "dex-cli",
+    version,
+    about = "Operate the dex program from the command line"
+)]
+struct Cli {
+    /// Cluster moniker (localnet, devnet, testnet, mainnet-beta) or RPC URL
+    #[arg(short = 'u', long = "url", global = true, default_value = "localnet")]
+    cluster: String,
+    /// Keypair that signs and pays for transactions
+    #[arg(short, long, global = true, default_value = DEFAULT_KEYPAIR_PATH)]
+    keypair: String,
+    /// Simulate transactions instead of sending them
+    #[arg(long, global = true)]
+    simulate: bool,
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Display)]
+    output: OutputFormat,
+    /// Compute unit limit requested for transactions
+    #[arg(long, global = true)]
+    compute_unit_limit: Option<u32>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Pool creation modes, as typed on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum CreationMode {
+    /// Anyone can create pools
+    Open,
+    /// Only the admin creates pools
+    AdminOnly,
+    /// The admin and allowlisted creators create pools
+    Allowlisted,
+}
+
+impl From<CreationMode> for PoolCreationMode {
+    fn from(mode: CreationMode) -> Self {
+        match mode {
+            CreationMode::Open => PoolCreationMode::Open,
+            CreationMode::AdminOnly => PoolCreationMode::AdminOnly,
+            CreationMode::Allowlisted => PoolCreationMode::Allowlisted,
+        }
+    }
+}
+
+/// Mint listings, as typed on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum Listing {
+    /// Never reviewed
+    Unknown,
+    /// Accepted in new pools
+    Allowed,
+    /// Blocked from new pools and from trading
+    Denied,
+}
+
+impl From<Listing> for MintListing {
+    fn from(listing: Listing) -> Self {
+        match listing {
+            Listing::Unknown => MintListing::Unknown,
+            Listing::Allowed => MintListing::Allowed,
+            Listing::Denied => MintListing::Denied,
+        }
+    }
+}
+
+/// Protocol fee modes, as typed on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum FeeMode {
+    /// Set aside a share of every swap fee, sent out by collect-fees
+    Accrued,
+    /// Mint LP tokens to the fee collector for its share of the fee growth
+    MintLp,
+}
+
+impl From<FeeMode> for ProtocolFeeMode {
+    fn from(mode: FeeMode) -> Self {
+        match mode {
+            FeeMode::Accrued => ProtocolFeeMode::Accrued,
+            FeeMode::MintLp => ProtocolFeeMode::MintLp,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Initialize the DEX state, signed by the admin keypair
+    Init {
+        #[arg(long)]
+        fee_numerator: u64,
+        #[arg(long)]
+        fee_denominator: u64,
+        /// Share of swap fees set aside for the protocol, in percent
+        #[arg(long)]
+        protocol_fee_percentage: u8,
+        /// Owner of the accounts receiving protocol fees, the admin by default
+        #[arg(long)]
+        fee_collector: Option<Pubkey>,
+    },
+    /// Change the DEX fees and fee collector, signed by the admin keypair
+    SetFees {
+        #[arg(long)]
+        fee_numerator: u64,
+        #[arg(long)]
+        fee_denominator: u64,
+        /// Share of swap fees set aside for the protocol, in percent
+        #[arg(long)]
+        protocol_fee_percentage: u8,
+        /// Owner of the accounts receiving protocol fees, the admin by default
+        #[arg(long)]
+        fee_collector: Option<Pubkey>,
+    },
+    /// Create the pool of a pair on a fee tier
+    CreatePool {
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        fee_tier: u16,
+    },
+    /// Deposit both tokens of a pool
+    Deposit {
+        pool: Pubkey,
+        token_a_amount: String,
+        token_b_amount: String,
+    },
+    /// Burn LP tokens for the underlying tokens
+    Withdraw { pool: Pubkey, lp_amount: String },
+    /// Swap one token of a pool for the other
+    Swap {
+        pool: Pubkey,
+        /// Mint of the token sold
+        #[arg(long)]
+        from: Pubkey,
+        /// Amount sold, or amount bought with --exact-output
+        amount: String,
+        /// Buy exactly AMOUNT of the other token instead of selling exactly AMOUNT
+        #[arg(long)]
+        exact_output: bool,
+        /// Tolerated price move, in basis points
+        #[arg(long, default_value_t = 50)]
+        slippage_bps: u64,
+    },
+    /// Price a swap without sending it
+    Quote {
+        pool: Pubkey,
+        /// Mint of the token sold
+        #[arg(long)]
+        from: Pubkey,
+        /// Amount sold, or amount bought with --exact-output
+        amount: String,
+        /// Buy exactly AMOUNT of the other token instead of selling exactly AMOUNT
+        #[arg(long)]
+        exact_output: bool,
+    },
+    /// Send the protocol fees of a pool to the fee collector, signed by the admin keypair
+    CollectFees { pool: Pubkey },
+    /// Close an empty pool, signed by the pool creator or the admin
+    ClosePool { pool: Pubkey },
+    /// Choose who may create pools, signed by the admin keypair
+    SetPoolCreationMode {
+        #[arg(value_enum)]
+        mode: CreationMode,
+    },
+    /// Replace the permissions of a pool creator, signed by the admin keypair
+    /// Passing no flag revokes every permission
+    SetCreatorPermissions {
+        creator: Pubkey,
+        /// Allow creating pools while creation is restricted to allowlisted creators
+        #[arg(long)]
+        create_pool: bool,
+        /// Allow configuring the dynamic fee of the creator's own pools
+        #[arg(long)]
+        configure_dynamic_fee: bool,
+    },
+    /// List a mint as allowed, denied or unknown, signed by the admin keypair
+    SetMintStatus {
+        mint: Pubkey,
+        #[arg(value_enum)]
+        status: Listing,
+    },
+    /// Make new pools accept only allowed mints, signed by the admin keypair
+    SetRequireAllowedMints {
+        #[arg(action = clap::ArgAction::Set)]
+        required: bool,
+    },
+    /// Choose how new pools take the protocol fee, signed by the admin keypair
+    SetProtocolFeeMode {
+        #[arg(value_enum)]
+        mode: FeeMode,
+    },
+    /// Upgrade a pool created by an older program version to the current account layout
+    MigratePool { pool: Pubkey },
+    /// Upgrade the DEX state to the current account layout, signed by the admin keypair
+    MigrateState,
+    /// Show the state of a pool
+    ShowPool { pool: Pubkey },
+    /// List the pools of the program: those of the registry, then the migrated pools of the first
+    /// release, which it doesn't list. Pools of the first release not migrated yet aren't listed
+    ListPools {
+        /// Only list pools trading this mint
+        #[arg(long)]
+        mint: Option<Pubkey>,
+        /// Skip the scan of the program accounts for pools missing from the registry, for RPC
+        /// nodes that don't serve getProgramAccounts
+        #[arg(long)]
+        registry_only: bool,
+    },
+}
+
+fn run(cli: Cli) -> Result<serde_json::Value, CliError> {
+    let chain = Chain {
+        rpc: RpcClient::new_with_commitment(
+            resolve_cluster_url(&cli.cluster),
+            CommitmentConfig::confirmed(),
+        ),
+        keypair_path: cli.keypair,
+        simulate: cli.simulate,
+        compute_unit_limit: cli.compute_unit_limit,
+    };
+
+    match cli.command {
+        Command::Init {
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector,
+        } => commands::init(
+            &chain,
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector,
+        ),
+        Command::SetFees {
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector,
+        } => commands::set_fees(
+            &chain,
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector,
+        ),
+        Command::CreatePool {
+            token_a_mint,
+            token_b_mint,
+            fee_tier,
+        } => commands::create_pool(&chain, &token_a_mint, &token_b_mint, fee_tier),
+        Command::Deposit {
+            pool,
+            token_a_amount,
+            token_b_amount,
+        } => commands::deposit(&chain, &pool, &token_a_amount, &token_b_amount),
+        Command::Withdraw { pool, lp_amount } => commands::withdraw(&chain, &pool, &lp_amount),
+        Command::Swap {
+            pool,
+            from,
+            amount,
+            exact_output,
+            slippage_bps,
+        } => commands::swap(&chain, &pool, &from, &amount, exact_output, slippage_bps),
+        Command::Quote {
+            pool,
+            from,
+            amount,
+            exact_output,
+        } => commands::quote(&chain, &pool, &from, &amount, exact_output),
+        Command::CollectFees { pool } => commands::collect_fees(&chain, &pool),
+        Command::ClosePool { pool } => commands::close_pool(&chain, &pool),
+        Command::SetPoolCreationMode { mode } => {
+            commands::set_pool_creation_mode(&chain, mode.into())
+        }
+        Command::SetCreatorPermissions {
+            creator,
+            create_pool,
+            configure_dynamic_fee,
+        } => {
+            let mut permissions = 0;
+            if create_pool {
+                permissions |= PERMISSION_CREATE_POOL;
+            }
+            if configure_dynamic_fee {
+                permissions |= PERMISSION_CONFIGURE_DYNAMIC_FEE;
+            }
+            commands::set_creator_permissions(&chain, &creator, permissions)
+        }
+        Command::SetMintStatus { mint, status } => {
+            commands::set_mint_status(&chain, &mint, status.into())
+        }
+        Command::SetRequireAllowedMints { required } => {
+            commands::set_require_allowed_mints(&chain, required)
+        }
+        Command::SetProtocolFeeMode { mode } => {
+            commands::set_protocol_fee_mode(&chain, mode.into())
+        }
+        Command::MigratePool { pool } => commands::migrate_pool(&chain, &pool),
+        Command::MigrateState => commands::migrate_state(&chain),
+        Command::ShowPool { pool } => commands::show_pool(&chain, &pool),
+        Command::ListPools {
+            mint,
+            registry_only,
+        } => commands::list_pools(&chain, mint, registry_only),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let format = cli.output;
+
+    match run(cli) {
+        Ok(report) => output::print(&report, format),
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// How command results are printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    // Indented `key: value` lines
+    Display,
+    // Pretty-printed JSON
+    Json,
+}
+
+/// Prints a command result in the requested format
+pub fn print(value: &Value, format: OutputFormat) {
+    match format {
+        OutputFormat::Display => print_display(value, 0),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+        ),
+    }
+}
+
+fn print_display(value: &Value, indent: usize) {
+    let padding = " ".repeat(indent);
+
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let label = key.replace('_', " ");
+                match field {
+                    Value::Null => {}
+                    Value::Object(_) | Value::Array(_) => {
+                        println!("{padding}{label}:");
+                        print_display(field, indent + 2);
+                    }
+                    _ => println!("{padding}{label}: {}", scalar(field)),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 && item.is_object() {
+                    println!();
+                }
+                match item {
+                    Value::Object(_) | Value::Array(_) => print_display(item, indent),
+                    _ => println!("{padding}{}", scalar(item)),
+                }
+            }
+        }
+        _ => println!("{padding}{}", scalar(value)),
+    }
+}
+
+/// Scalars print without JSON quoting
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,40 @@
+use anchor_lang::{error::ErrorCode, AccountDeserialize, Discriminator};
+use dex::{CreatorPermissions, DexState, LiquidityPool, MintStatus, PoolPair, PoolRegistryPage};
+
+use crate::ClientError;
+
+/// Decodes the raw data of a DEX state account, discriminator included
+pub fn deserialize_dex_state(data: &[u8]) -> Result<DexState, ClientError> {
+    Ok(DexState::try_deserialize(&mut &data[..])?)
+}
+
+/// Decodes the raw data of a liquidity pool account, discriminator included
+/// Pools are zero-copy: the data is copied out, RPC buffers aren't aligned for the pool type
+pub fn deserialize_liquidity_pool(data: &[u8]) -> Result<LiquidityPool, ClientError> {
+    if data.len() < 8 || data[..8] != *LiquidityPool::DISCRIMINATOR {
+        return Err(anchor_lang::error!(ErrorCode::AccountDiscriminatorMismatch).into());
+    }
+
+    bytemuck::try_pod_read_unaligned(&data[8..])
+        .map_err(|_| anchor_lang::error!(ErrorCode::AccountDidNotDeserialize).into())
+}
+
+/// Decodes the raw data of a pool registry page, discriminator included
+pub fn deserialize_pool_registry_page(data: &[u8]) -> Result<PoolRegistryPage, ClientError> {
+    Ok(PoolRegistryPage::try_deserialize(&mut &data[..])?)
+}
+
+/// Decodes the raw data of a mint pair lookup, discriminator included
+pub fn deserialize_pool_pair(data: &[u8]) -> Result<PoolPair, ClientError> {
+    Ok(PoolPair::try_deserialize(&mut &data[..])?)
+}
+
+/// Decodes the raw data of a creator permissions account, discriminator included
+pub fn deserialize_creator_permissions(data: &[u8]) -> Result<CreatorPermissions, ClientError> {
+    Ok(CreatorPermissions::try_deserialize(&mut &data[..])?)
+}
+
+/// Decodes the raw data of a mint status account, discriminator included
+pub fn deserialize_mint_status(data: &[u8]) -> Result<MintStatus, ClientError> {
+    Ok(MintStatus::try_deserialize(&mut &data[..])?)
+}
@@ -0,0 +1,15 @@
+use anchor_lang::prelude::Pubkey;
+
+/// Errors returned by the client helpers
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    // Account data that doesn't decode, or a fee rate the program would reject
+    #[error(transparent)]
+    Program(#[from] anchor_lang::error::Error),
+    // A quote the pool math rejects, such as an empty pool or an amount rounding to zero
+    #[error(transparent)]
+    Math(#[from] dex_math::MathError),
+    // The mint passed to a swap helper is neither token A nor token B of the pool
+    #[error("mint {0} is not part of the pool")]
+    MintNotInPool(Pubkey),
+}
@@ -0,0 +1,582 @@
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, system_program, sysvar},
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+        ID as ASSOCIATED_TOKEN_PROGRAM_ID,
+    },
+    token_2022::ID as TOKEN_2022_PROGRAM_ID,
+};
+use dex::{DynamicFeeParams, LiquidityPool, MintListing, PoolCreationMode, ProtocolFeeMode};
+
+use crate::{
+    pda::{
+        find_creator_permissions_address, find_dex_state_address, find_fee_tier_address,
+        find_liquidity_pool_address, find_metaplex_metadata_address, find_mint_status_address,
+        find_pool_pair_address, find_pool_registry_page_address, get_associated_token_address,
+    },
+    ClientError,
+};
+
+// Builders return plain instructions: transfer hook extra accounts, when a pool mint has a
+// hook, are appended by the caller to `Instruction::accounts`
+
+/// Builds an instruction of the dex program from its accounts and arguments
+fn build_instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: dex::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Returns the mint on the other side of the pool, or an error if `mint` isn't in the pool
+fn counterpart_mint(pool: &LiquidityPool, mint: &Pubkey) -> Result<Pubkey, ClientError> {
+    if *mint == pool.token_a_mint {
+        Ok(pool.token_b_mint)
+    } else if *mint == pool.token_b_mint {
+        Ok(pool.token_a_mint)
+    } else {
+        Err(ClientError::MintNotInPool(*mint))
+    }
+}
+
+/// Initializes the DEX state of `admin`
+pub fn initialize(
+    admin: &Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    fee_collector: &Pubkey,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::Initialize {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+            system_program: system_program::ID,
+        },
+        dex::instruction::Initialize {
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector: *fee_collector,
+        },
+    )
+}
+
+/// Changes the fees and fee collector of the DEX of `admin`
+pub fn set_fees(
+    admin: &Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    fee_collector: &Pubkey,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::SetFees {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+        },
+        dex::instruction::SetFees {
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector: *fee_collector,
+        },
+    )
+}
+
+/// Registers the next fee tier of a DEX, `fee_tiers_count` being the current
+/// `DexState::fee_tiers_count`
+pub fn add_fee_tier(
+    admin: &Pubkey,
+    dex_state: &Pubkey,
+    fee_tiers_count: u16,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::AddFeeTier {
+            admin: *admin,
+            dex_state: *dex_state,
+            fee_tier: find_fee_tier_address(dex_state, fee_tiers_count).0,
+            system_program: system_program::ID,
+        },
+        dex::instruction::AddFeeTier {
+            fee_numerator,
+            fee_denominator,
+        },
+    )
+}
+
+/// Creates the pool-owned reserve accounts `create_pool` expects, as associated token accounts
+/// Idempotent, so it is safe to send again
+pub fn create_pool_vaults(
+    payer: &Pubkey,
+    token_a_mint: &Pubkey,
+    token_b_mint: &Pubkey,
+    fee_tier_index: u16,
+    token_program: &Pubkey,
+) -> [Instruction; 2] {
+    let pool = find_liquidity_pool_address(token_a_mint, token_b_mint, fee_tier_index).0;
+
+    [
+        create_associated_token_account_idempotent(payer, &pool, token_a_mint, token_program),
+        create_associated_token_account_idempotent(payer, &pool, token_b_mint, token_program),
+    ]
+}
+
+/// Creates the pool of a pair on a fee tier
+/// `lp_token_mint` is a fresh keypair that must sign the transaction, `extension_allowlist`
+/// is only needed for mints relying on governance-approved Token-2022 extensions,
+/// `creator_permissions` when pool creation is restricted to allowlisted creators
+/// `pools_count` is the current `DexState::pools_count`, which picks the registry page
+/// The Metaplex metadata of both mints is always passed, the LP token takes its symbols when
+/// it exists
+#[allow(clippy::too_many_arguments)]
+pub fn create_pool(
+    owner: &Pubkey,
+    dex_state: &Pubkey,
+    pools_count: u64,
+    token_a_mint: &Pubkey,
+    token_b_mint: &Pubkey,
+    fee_tier_index: u16,
+    lp_token_mint: &Pubkey,
+    token_program: &Pubkey,
+    extension_allowlist: Option<Pubkey>,
+    creator_permissions: Option<Pubkey>,
+) -> Instruction {
+    let pool = find_liquidity_pool_address(token_a_mint, token_b_mint, fee_tier_index).0;
+
+    build_instruction(
+        dex::accounts::CreatePool {
+            owner: *owner,
+            dex_state: *dex_state,
+            fee_tier: find_fee_tier_address(dex_state, fee_tier_index).0,
+            extension_allowlist,
+            creator_permissions,
+            token_a_mint: *token_a_mint,
+            token_b_mint: *token_b_mint,
+            token_a_mint_status: find_mint_status_address(dex_state, token_a_mint).0,
+            token_b_mint_status: find_mint_status_address(dex_state, token_b_mint).0,
+            token_a_metadata: Some(find_metaplex_metadata_address(token_a_mint).0),
+            token_b_metadata: Some(find_metaplex_metadata_address(token_b_mint).0),
+            pool,
+            registry_page: find_pool_registry_page_address(dex_state, pools_count).0,
+            pool_pair: find_pool_pair_address(dex_state, token_a_mint, token_b_mint).0,
+            pool_token_a: get_associated_token_address(&pool, token_a_mint, token_program),
+            pool_token_b: get_associated_token_address(&pool, token_b_mint, token_program),
+            lp_token_mint: *lp_token_mint,
+            owner_lp_token: get_associated_token_address(
+                owner,
+                lp_token_mint,
+                &TOKEN_2022_PROGRAM_ID,
+            ),
+            token_program: *token_program,
+            lp_token_program: TOKEN_2022_PROGRAM_ID,
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        dex::instruction::CreatePool { fee_tier_index },
+    )
+}
+
+/// Deposits both tokens of a pool from the owner's associated token accounts
+/// `lp_token_program` owns the LP mint: Token-2022, or SPL Token for pools of the first release
+/// `fee_collector` is the current `DexState::fee_collector`: pools minting their protocol fee as
+/// LP tokens mint it to its associated LP token account, which only has to exist when the deposit
+/// mints some (see create_fee_collector_lp_token)
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_liquidity(
+    owner: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    fee_collector: &Pubkey,
+    token_program: &Pubkey,
+    lp_token_program: &Pubkey,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::DepositLiquidity {
+            owner: *owner,
+            pool: *pool_address,
+            token_a_mint: pool.token_a_mint,
+            token_b_mint: pool.token_b_mint,
+            pool_token_a: pool.token_a_account,
+            pool_token_b: pool.token_b_account,
+            lp_token_mint: pool.lp_token_mint,
+            user_token_a: get_associated_token_address(owner, &pool.token_a_mint, token_program),
+            user_token_b: get_associated_token_address(owner, &pool.token_b_mint, token_program),
+            user_lp_token: get_associated_token_address(
+                owner,
+                &pool.lp_token_mint,
+                lp_token_program,
+            ),
+            dex_state: find_dex_state_address(&dex::ADMIN_PUBKEY).0,
+            fee_collector_lp_token: fee_collector_lp_token(pool, fee_collector, lp_token_program),
+            token_program: *token_program,
+            lp_token_program: *lp_token_program,
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        dex::instruction::DepositLiquidity {
+            token_a_amount,
+            token_b_amount,
+        },
+    )
+}
+
+/// Associated LP token account of the fee collector for a pool minting its protocol fee as LP
+/// tokens, None for other pools
+/// Always passed for such pools, it is only read when the pool has protocol fee LP tokens to mint
+fn fee_collector_lp_token(
+    pool: &LiquidityPool,
+    fee_collector: &Pubkey,
+    lp_token_program: &Pubkey,
+) -> Option<Pubkey> {
+    pool.mints_protocol_fee()
+        .then(|| get_associated_token_address(fee_collector, &pool.lp_token_mint, lp_token_program))
+}
+
+/// Creates the LP token account of `fee_collector` for a pool minting its protocol fee as LP
+/// tokens, which deposits and withdrawals minting protocol fee LP tokens need; None for other
+/// pools
+/// Idempotent, so it can be prepended to every deposit and withdrawal
+pub fn create_fee_collector_lp_token(
+    payer: &Pubkey,
+    pool: &LiquidityPool,
+    fee_collector: &Pubkey,
+) -> Option<Instruction> {
+    pool.mints_protocol_fee().then(|| {
+        create_associated_token_account_idempotent(
+            payer,
+            fee_collector,
+            &pool.lp_token_mint,
+            &TOKEN_2022_PROGRAM_ID,
+        )
+    })
+}
+
+/// Burns LP tokens of a pool for the underlying tokens, paid to the owner's associated token
+/// accounts
+/// `lp_token_program` owns the LP mint: Token-2022, or SPL Token for pools of the first release
+/// `fee_collector` is the current `DexState::fee_collector`, as for deposit_liquidity
+pub fn withdraw_liquidity(
+    owner: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    fee_collector: &Pubkey,
+    token_program: &Pubkey,
+    lp_token_program: &Pubkey,
+    lp_amount: u64,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::WithdrawLiquidity {
+            owner: *owner,
+            pool: *pool_address,
+            token_a_mint: pool.token_a_mint,
+            token_b_mint: pool.token_b_mint,
+            pool_token_a: pool.token_a_account,
+            pool_token_b: pool.token_b_account,
+            lp_token_mint: pool.lp_token_mint,
+            user_token_a: get_associated_token_address(owner, &pool.token_a_mint, token_program),
+            user_token_b: get_associated_token_address(owner, &pool.token_b_mint, token_program),
+            user_lp_token: get_associated_token_address(
+                owner,
+                &pool.lp_token_mint,
+                lp_token_program,
+            ),
+            dex_state: find_dex_state_address(&dex::ADMIN_PUBKEY).0,
+            fee_collector_lp_token: fee_collector_lp_token(pool, fee_collector, lp_token_program),
+            token_program: *token_program,
+            lp_token_program: *lp_token_program,
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        dex::instruction::WithdrawLiquidity { lp_amount },
+    )
+}
+
+/// Accounts shared by both swap instructions, from `source_mint` to the other pool token
+fn swap_accounts(
+    owner: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    source_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<dex::accounts::Swap, ClientError> {
+    let destination_mint = counterpart_mint(pool, source_mint)?;
+    // Pools have no link to their DEX state, there is only the one of `ADMIN_PUBKEY`
+    let dex_state = find_dex_state_address(&dex::ADMIN_PUBKEY).0;
+
+    Ok(dex::accounts::Swap {
+        owner: *owner,
+        pool: *pool_address,
+        source_mint: *source_mint,
+        destination_mint,
+        dex_state,
+        source_mint_status: find_mint_status_address(&dex_state, source_mint).0,
+        destination_mint_status: find_mint_status_address(&dex_state, &destination_mint).0,
+        pool_token_a: pool.token_a_account,
+        pool_token_b: pool.token_b_account,
+        user_source_token: get_associated_token_address(owner, source_mint, token_program),
+        user_destination_token: get_associated_token_address(
+            owner,
+            &destination_mint,
+            token_program,
+        ),
+        token_program: *token_program,
+        associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+    })
+}
+
+/// Swaps exactly `input_amount` of `source_mint` for the other token of the pool
+pub fn swap(
+    owner: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    source_mint: &Pubkey,
+    token_program: &Pubkey,
+    input_amount: u64,
+    minimum_output_amount: u64,
+) -> Result<Instruction, ClientError> {
+    Ok(build_instruction(
+        swap_accounts(owner, pool_address, pool, source_mint, token_program)?,
+        dex::instruction::Swap {
+            input_amount,
+            minimum_output_amount,
+        },
+    ))
+}
+
+/// Swaps at most `maximum_input_amount` of `source_mint` for exactly `output_amount` of the
+/// other token of the pool
+pub fn swap_exact_output(
+    owner: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    source_mint: &Pubkey,
+    token_program: &Pubkey,
+    output_amount: u64,
+    maximum_input_amount: u64,
+) -> Result<Instruction, ClientError> {
+    Ok(build_instruction(
+        swap_accounts(owner, pool_address, pool, source_mint, token_program)?,
+        dex::instruction::SwapExactOutput {
+            output_amount,
+            maximum_input_amount,
+        },
+    ))
+}
+
+/// Sends the protocol fees of a pool to the fee collector's associated token accounts
+pub fn collect_fees(
+    admin: &Pubkey,
+    dex_state: &Pubkey,
+    fee_collector: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    token_program: &Pubkey,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::CollectProtocolFees {
+            admin: *admin,
+            dex_state: *dex_state,
+            pool: *pool_address,
+            token_a_mint: pool.token_a_mint,
+            token_b_mint: pool.token_b_mint,
+            pool_token_a: pool.token_a_account,
+            pool_token_b: pool.token_b_account,
+            fee_collector_token_a: get_associated_token_address(
+                fee_collector,
+                &pool.token_a_mint,
+                token_program,
+            ),
+            fee_collector_token_b: get_associated_token_address(
+                fee_collector,
+                &pool.token_b_mint,
+                token_program,
+            ),
+            token_program: *token_program,
+        },
+        dex::instruction::CollectFees {},
+    )
+}
+
+/// Closes an empty pool, its vaults and its LP mint, returning their rent to the pool creator
+/// Leftover vault dust goes to the fee collector's associated token accounts, which must exist
+/// `lp_token_program` owns the LP mint: Token-2022, or SPL Token for pools of the first release
+pub fn close_pool(
+    authority: &Pubkey,
+    dex_state: &Pubkey,
+    fee_collector: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    token_program: &Pubkey,
+    lp_token_program: &Pubkey,
+) -> Instruction {
+    // Pools created before the registry aren't listed in it nor in their pair lookup
+    let listed = pool.registry_index != LiquidityPool::UNLISTED_REGISTRY_INDEX;
+
+    build_instruction(
+        dex::accounts::ClosePool {
+            authority: *authority,
+            creator: pool.creator,
+            dex_state: *dex_state,
+            pool: *pool_address,
+            registry_page: listed
+                .then(|| find_pool_registry_page_address(dex_state, pool.registry_index).0),
+            pool_pair: listed.then(|| {
+                find_pool_pair_address(dex_state, &pool.token_a_mint, &pool.token_b_mint).0
+            }),
+            token_a_mint: pool.token_a_mint,
+            token_b_mint: pool.token_b_mint,
+            pool_token_a: pool.token_a_account,
+            pool_token_b: pool.token_b_account,
+            lp_token_mint: pool.lp_token_mint,
+            fee_collector_token_a: get_associated_token_address(
+                fee_collector,
+                &pool.token_a_mint,
+                token_program,
+            ),
+            fee_collector_token_b: get_associated_token_address(
+                fee_collector,
+                &pool.token_b_mint,
+                token_program,
+            ),
+            token_program: *token_program,
+            lp_token_program: *lp_token_program,
+        },
+        dex::instruction::ClosePool {},
+    )
+}
+
+/// Turns the volatility-based fee of a pool on with `params`, or back to its static fee with None
+/// Signed by the admin, or by the pool creator when governance granted them the permission
+pub fn configure_dynamic_fee(
+    authority: &Pubkey,
+    dex_state: &Pubkey,
+    pool_address: &Pubkey,
+    pool: &LiquidityPool,
+    params: Option<DynamicFeeParams>,
+) -> Instruction {
+    // The creator's permissions are only checked when someone other than the admin signs
+    let signed_by_creator = *authority != dex::ADMIN_PUBKEY;
+
+    build_instruction(
+        dex::accounts::ConfigureDynamicFee {
+            authority: *authority,
+            dex_state: *dex_state,
+            pool: *pool_address,
+            creator_permissions: signed_by_creator
+                .then(|| find_creator_permissions_address(dex_state, &pool.creator).0),
+        },
+        dex::instruction::ConfigureDynamicFee { params },
+    )
+}
+
+/// Upgrades a pool laid out as an earlier version, `payer` funding the extra rent
+pub fn migrate_pool(payer: &Pubkey, pool_address: &Pubkey) -> Instruction {
+    build_instruction(
+        dex::accounts::MigratePool {
+            payer: *payer,
+            pool: *pool_address,
+            system_program: system_program::ID,
+        },
+        dex::instruction::MigratePool {},
+    )
+}
+
+/// Upgrades the DEX state of `admin` laid out as an earlier version, the admin funding the extra
+/// rent
+pub fn migrate_state(admin: &Pubkey) -> Instruction {
+    build_instruction(
+        dex::accounts::MigrateState {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+            system_program: system_program::ID,
+        },
+        dex::instruction::MigrateState {},
+    )
+}
+
+/// Replaces the governance permissions of a pool creator with `permissions`
+pub fn set_creator_permissions(
+    admin: &Pubkey,
+    dex_state: &Pubkey,
+    creator: &Pubkey,
+    permissions: u8,
+) -> Instruction {
+    build_instruction(
+        dex::accounts::SetCreatorPermissions {
+            admin: *admin,
+            dex_state: *dex_state,
+            creator_permissions: find_creator_permissions_address(dex_state, creator).0,
+            system_program: system_program::ID,
+        },
+        dex::instruction::SetCreatorPermissions {
+            creator: *creator,
+            permissions,
+        },
+    )
+}
+
+/// Switches who may create pools on the DEX of `admin`
+pub fn set_pool_creation_mode(admin: &Pubkey, mode: PoolCreationMode) -> Instruction {
+    build_instruction(
+        dex::accounts::SetPoolCreationMode {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+        },
+        dex::instruction::SetPoolCreationMode { mode },
+    )
+}
+
+/// Lists `mint` as allowed, denied or unknown on the DEX of `admin`
+pub fn set_mint_status(admin: &Pubkey, mint: &Pubkey, status: MintListing) -> Instruction {
+    let dex_state = find_dex_state_address(admin).0;
+
+    build_instruction(
+        dex::accounts::SetMintStatus {
+            admin: *admin,
+            dex_state,
+            mint_status: find_mint_status_address(&dex_state, mint).0,
+            system_program: system_program::ID,
+        },
+        dex::instruction::SetMintStatus {
+            mint: *mint,
+            status,
+        },
+    )
+}
+
+/// Makes new pools on the DEX of `admin` accept only mints listed as allowed
+pub fn set_require_allowed_mints(admin: &Pubkey, required: bool) -> Instruction {
+    build_instruction(
+        dex::accounts::SetRequireAllowedMints {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+        },
+        dex::instruction::SetRequireAllowedMints { required },
+    )
+}
+
+/// Switches how pools created from now on take the protocol fee on the DEX of `admin`
+pub fn set_protocol_fee_mode(admin: &Pubkey, mode: ProtocolFeeMode) -> Instruction {
+    build_instruction(
+        dex::accounts::SetProtocolFeeMode {
+            admin: *admin,
+            dex_state: find_dex_state_address(admin).0,
+        },
+        dex::instruction::SetProtocolFeeMode { mode },
+    )
+}
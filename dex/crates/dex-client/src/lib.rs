@@ -0,0 +1,13 @@
+//! Rust client for the dex program
+//!
+//! Derives the program addresses, builds instructions with every account resolved, decodes
+//! program accounts and quotes pool operations off-chain with the program's own math
+
+pub mod accounts;
+mod error;
+pub mod instructions;
+pub mod pda;
+pub mod quote;
+
+pub use dex::ID as PROGRAM_ID;
+pub use error::ClientError;
@@ -0,0 +1,114 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use dex::{registry_page_index, sorted_pair_mints, LiquidityPool};
+
+/// Address of the DEX state created by `admin`
+pub fn find_dex_state_address(admin: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"dex_state", admin.as_ref()], &dex::ID)
+}
+
+/// Address of the fee tier registered at `fee_tier_index`
+pub fn find_fee_tier_address(dex_state: &Pubkey, fee_tier_index: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"fee_tier",
+            dex_state.as_ref(),
+            &fee_tier_index.to_le_bytes(),
+        ],
+        &dex::ID,
+    )
+}
+
+/// Address of the pool of a pair on a fee tier
+/// The mint order matters: it is the order the pool was created with
+/// Pools created before fee tiers, on `LiquidityPool::LEGACY_FEE_TIER_INDEX`, are derived from
+/// the pair alone
+pub fn find_liquidity_pool_address(
+    token_a_mint: &Pubkey,
+    token_b_mint: &Pubkey,
+    fee_tier_index: u16,
+) -> (Pubkey, u8) {
+    let fee_tier_seed = fee_tier_index.to_le_bytes();
+    let mut seeds = vec![
+        b"liquidity_pool".as_ref(),
+        token_a_mint.as_ref(),
+        token_b_mint.as_ref(),
+    ];
+    if fee_tier_index != LiquidityPool::LEGACY_FEE_TIER_INDEX {
+        seeds.push(&fee_tier_seed);
+    }
+
+    Pubkey::find_program_address(&seeds, &dex::ID)
+}
+
+/// Address of the mint extension allowlist of a DEX
+pub fn find_extension_allowlist_address(dex_state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"extension_allowlist", dex_state.as_ref()], &dex::ID)
+}
+
+/// Address of the governance permissions granted to a pool creator
+pub fn find_creator_permissions_address(dex_state: &Pubkey, creator: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"creator_permissions", dex_state.as_ref(), creator.as_ref()],
+        &dex::ID,
+    )
+}
+
+/// Address of governance's listing of a mint, shared by every pool of the DEX trading it
+pub fn find_mint_status_address(dex_state: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"mint_status", dex_state.as_ref(), mint.as_ref()],
+        &dex::ID,
+    )
+}
+
+/// Address of the Metaplex metadata of a mint, which may not exist
+pub fn find_metaplex_metadata_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            dex::METAPLEX_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &dex::METAPLEX_METADATA_PROGRAM_ID,
+    )
+}
+
+/// Address of the registry page listing the pool created as number `registry_index`
+pub fn find_pool_registry_page_address(dex_state: &Pubkey, registry_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"pool_registry",
+            dex_state.as_ref(),
+            &registry_page_index(registry_index).to_le_bytes(),
+        ],
+        &dex::ID,
+    )
+}
+
+/// Address of the lookup of the pools of a DEX trading a mint pair, in either mint order
+pub fn find_pool_pair_address(
+    dex_state: &Pubkey,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> (Pubkey, u8) {
+    let (mint_0, mint_1) = sorted_pair_mints(*mint_a, *mint_b);
+    Pubkey::find_program_address(
+        &[
+            b"pool_pair",
+            dex_state.as_ref(),
+            mint_0.as_ref(),
+            mint_1.as_ref(),
+        ],
+        &dex::ID,
+    )
+}
+
+/// Associated token account of `owner` for `mint`, under the token program owning the mint
+pub fn get_associated_token_address(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
@@ -0,0 +1,234 @@
+use anchor_lang::prelude::Pubkey;
+use dex::{calculate_swap_prices, LiquidityPool, BPS_DENOMINATOR};
+use dex_math::{
+    calculate_fee_breakdown, calculate_initial_liquidity, calculate_input_amount,
+    calculate_input_amount_before_fee, calculate_output_amount, calculate_proportional_liquidity,
+    calculate_withdrawal_amounts, MathError,
+};
+
+use crate::ClientError;
+
+// Quotes replay the program's math on reserves read from the pool vaults, leaving out the
+// protocol fees accrued in them like the program does, and the LP tokens pools minting their
+// protocol fee owe the fee collector are added to the supply like the program does
+// Token-2022 transfer fees are not included: the program prices on the amounts that reach the
+// pool, so callers using transfer-fee mints have to net them out first
+
+/// Result of a swap quote
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapQuote {
+    // Amount of the source token taken from the user
+    pub input_amount: u64,
+    // Amount of the destination token sent to the user
+    pub output_amount: u64,
+    // Fee rate charged by the pool for this swap
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    // Part of the fee set aside for the protocol, in the source token
+    pub protocol_fee: u64,
+}
+
+/// Fee rate the pool charges at unix time `now` for a swap from `source_mint` moving along the
+/// curve by `curve_amounts` (source in, destination out), the fee left out
+/// Replays the dynamic fee update on a copy of the pool state when the pool uses dynamic fees
+pub fn quote_fee_rate(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    source_mint: &Pubkey,
+    (source_amount, destination_amount): (u64, u64),
+    now: i64,
+) -> Result<(u64, u64), ClientError> {
+    if !pool.dynamic_fee.is_enabled() {
+        return Ok((pool.fee_numerator, pool.fee_denominator));
+    }
+
+    let (source_reserve, destination_reserve) =
+        swap_reserves(pool, source_mint, reserve_a, reserve_b)?;
+    let (price_before, price_after) = calculate_swap_prices(
+        *source_mint == pool.token_a_mint,
+        source_reserve,
+        destination_reserve,
+        source_amount,
+        destination_amount,
+    )
+    .ok_or(MathError::InsufficientLiquidity)?;
+    let mut dynamic_fee = pool.dynamic_fee;
+    let fee_bps = dynamic_fee.update(now, price_before, price_after)?;
+
+    Ok((fee_bps, BPS_DENOMINATOR))
+}
+
+/// Reserves owned by the liquidity providers, ordered as (source, destination) for a swap from
+/// `source_mint`
+fn swap_reserves(
+    pool: &LiquidityPool,
+    source_mint: &Pubkey,
+    reserve_a: u64,
+    reserve_b: u64,
+) -> Result<(u64, u64), ClientError> {
+    let (reserve_a, reserve_b) = lp_reserves(pool, reserve_a, reserve_b)?;
+    if *source_mint == pool.token_a_mint {
+        Ok((reserve_a, reserve_b))
+    } else if *source_mint == pool.token_b_mint {
+        Ok((reserve_b, reserve_a))
+    } else {
+        Err(ClientError::MintNotInPool(*source_mint))
+    }
+}
+
+/// Part of the vault balances the program prices on, the uncollected protocol fees left out
+fn lp_reserves(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+) -> Result<(u64, u64), ClientError> {
+    Ok(pool
+        .lp_reserves(reserve_a, reserve_b)
+        .ok_or(MathError::InsufficientLiquidity)?)
+}
+
+/// Quotes a `swap` of exactly `input_amount` of `source_mint`
+pub fn quote_swap(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    source_mint: &Pubkey,
+    input_amount: u64,
+    now: i64,
+) -> Result<SwapQuote, ClientError> {
+    let (source_reserve, destination_reserve) =
+        swap_reserves(pool, source_mint, reserve_a, reserve_b)?;
+    let curve_output_amount =
+        calculate_output_amount(input_amount, source_reserve, destination_reserve, 0, 1)?;
+    let (fee_numerator, fee_denominator) = quote_fee_rate(
+        pool,
+        reserve_a,
+        reserve_b,
+        source_mint,
+        (input_amount, curve_output_amount),
+        now,
+    )?;
+
+    let (total_fee, protocol_fee) = calculate_fee_breakdown(
+        input_amount,
+        fee_numerator,
+        fee_denominator,
+        pool.accrued_protocol_fee_percentage(),
+    )?;
+    let input_amount_with_fee = input_amount
+        .checked_sub(total_fee)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    let output_amount = calculate_output_amount(
+        input_amount_with_fee,
+        source_reserve,
+        destination_reserve,
+        0,
+        1,
+    )?;
+
+    Ok(SwapQuote {
+        input_amount,
+        output_amount,
+        fee_numerator,
+        fee_denominator,
+        protocol_fee,
+    })
+}
+
+/// Quotes a `swap_exact_output` for exactly `output_amount` of the other token of the pool
+pub fn quote_swap_exact_output(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    source_mint: &Pubkey,
+    output_amount: u64,
+    now: i64,
+) -> Result<SwapQuote, ClientError> {
+    let (source_reserve, destination_reserve) =
+        swap_reserves(pool, source_mint, reserve_a, reserve_b)?;
+
+    let input_amount_with_fee =
+        calculate_input_amount(output_amount, source_reserve, destination_reserve)?;
+    let (fee_numerator, fee_denominator) = quote_fee_rate(
+        pool,
+        reserve_a,
+        reserve_b,
+        source_mint,
+        (input_amount_with_fee, output_amount),
+        now,
+    )?;
+    let input_amount =
+        calculate_input_amount_before_fee(input_amount_with_fee, fee_numerator, fee_denominator)?;
+
+    let (_, protocol_fee) = calculate_fee_breakdown(
+        input_amount,
+        fee_numerator,
+        fee_denominator,
+        pool.accrued_protocol_fee_percentage(),
+    )?;
+
+    Ok(SwapQuote {
+        input_amount,
+        output_amount,
+        fee_numerator,
+        fee_denominator,
+        protocol_fee,
+    })
+}
+
+/// LP tokens minted for a deposit of `token_a_amount` and `token_b_amount`
+pub fn quote_deposit(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> Result<u64, ClientError> {
+    let (reserve_a, reserve_b) = lp_reserves(pool, reserve_a, reserve_b)?;
+    let lp_tokens = if pool.total_liquidity == 0 {
+        calculate_initial_liquidity(token_a_amount, token_b_amount)?
+    } else {
+        calculate_proportional_liquidity(
+            token_a_amount,
+            token_b_amount,
+            reserve_a,
+            reserve_b,
+            total_liquidity_with_protocol_fee(pool, reserve_a, reserve_b)?,
+        )?
+    };
+
+    Ok(lp_tokens)
+}
+
+/// Token A and token B amounts returned for burning `lp_amount` LP tokens
+pub fn quote_withdrawal(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    lp_amount: u64,
+) -> Result<(u64, u64), ClientError> {
+    // Liquidity providers are paid from the vaults minus the uncollected protocol fees
+    let (reserve_a, reserve_b) = lp_reserves(pool, reserve_a, reserve_b)?;
+
+    Ok(calculate_withdrawal_amounts(
+        lp_amount,
+        reserve_a,
+        reserve_b,
+        total_liquidity_with_protocol_fee(pool, reserve_a, reserve_b)?,
+    )?)
+}
+
+/// LP supply a deposit or withdrawal is valued against: the pool's total liquidity plus the LP
+/// tokens it mints the fee collector first, for pools minting their protocol fee
+fn total_liquidity_with_protocol_fee(
+    pool: &LiquidityPool,
+    reserve_a: u64,
+    reserve_b: u64,
+) -> Result<u64, ClientError> {
+    Ok(pool
+        .total_liquidity
+        .checked_add(pool.protocol_fee_liquidity(reserve_a, reserve_b)?)
+        .ok_or(MathError::InsufficientLiquidity)?)
+}
@@ -0,0 +1,121 @@
+//! Account decoders checked against account data laid out by hand, field by field in the
+//! declared order, rather than produced by the types they decode
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use dex::{DexState, LiquidityPool, ProtocolFeeMode};
+use dex_client::accounts::{deserialize_dex_state, deserialize_liquidity_pool};
+
+const CREATOR: Pubkey = Pubkey::new_from_array([1; 32]);
+const TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([2; 32]);
+const TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([3; 32]);
+const LP_TOKEN_MINT: Pubkey = Pubkey::new_from_array([6; 32]);
+const K_LAST: u128 = 1_000_000 * 4_000_000;
+
+/// Data of a pool account: a MintLp pool on fee tier 2 with a dynamic fee
+fn pool_account_data() -> Vec<u8> {
+    let mut data = LiquidityPool::DISCRIMINATOR.to_vec();
+    // Pubkeys: creator, mints, vaults, LP mint and transfer hooks
+    for byte in 1..=8 {
+        data.extend([byte; 32]);
+    }
+    // total_liquidity, fees, protocol fees, registry_index, created_at, created_slot
+    for value in [
+        2_000_000u64,
+        3,
+        1_000,
+        100,
+        200,
+        17,
+        1_700_000_000,
+        250_000_000,
+    ] {
+        data.extend(value.to_le_bytes());
+    }
+    // Dynamic fee: last update, reference price, accumulators and parameters, enabled, padding
+    data.extend(1_700_000_100i64.to_le_bytes());
+    data.extend((1u128 << 64).to_le_bytes());
+    for value in [500u32, 40, 10_000, 350_000] {
+        data.extend(value.to_le_bytes());
+    }
+    for value in [5u16, 100, 30, 600, 5_000] {
+        data.extend(value.to_le_bytes());
+    }
+    data.push(1);
+    data.extend([0; 5]);
+    // fee_tier_index, bump, protocol_fee_percentage, version, padding
+    data.extend(2u16.to_le_bytes());
+    data.extend([254, 30, LiquidityPool::VERSION, 0, 0, 0]);
+    // k_last, protocol_fee_mode, then the reserved space
+    data.extend(K_LAST.to_le_bytes());
+    data.push(ProtocolFeeMode::MintLp as u8);
+    data.resize(8 + LiquidityPool::LEN, 0);
+    data
+}
+
+#[test]
+fn decodes_a_pool_account() {
+    let pool = deserialize_liquidity_pool(&pool_account_data()).unwrap();
+
+    assert_eq!(pool.creator, CREATOR);
+    assert_eq!(
+        (pool.token_a_mint, pool.token_b_mint),
+        (TOKEN_A_MINT, TOKEN_B_MINT)
+    );
+    assert_eq!(pool.lp_token_mint, LP_TOKEN_MINT);
+    assert_eq!(pool.token_b_transfer_hook, Pubkey::new_from_array([8; 32]));
+    assert_eq!(pool.total_liquidity, 2_000_000);
+    assert_eq!((pool.fee_numerator, pool.fee_denominator), (3, 1_000));
+    assert_eq!(
+        (pool.protocol_fees_token_a, pool.protocol_fees_token_b),
+        (100, 200)
+    );
+    assert_eq!(pool.registry_index, 17);
+    assert_eq!(
+        (pool.created_at, pool.created_slot),
+        (1_700_000_000, 250_000_000)
+    );
+
+    assert!(pool.dynamic_fee.is_enabled());
+    assert_eq!(pool.dynamic_fee.last_update_timestamp, 1_700_000_100);
+    assert_eq!(pool.dynamic_fee.volatility_accumulator, 500);
+    assert_eq!(pool.dynamic_fee.max_volatility_accumulator, 350_000);
+    assert_eq!(
+        (pool.dynamic_fee.min_fee_bps, pool.dynamic_fee.max_fee_bps),
+        (5, 100)
+    );
+    assert_eq!(pool.dynamic_fee.reduction_factor, 5_000);
+
+    assert_eq!(pool.fee_tier_index, 2);
+    assert_eq!(pool.bump, 254);
+    assert_eq!(pool.protocol_fee_percentage, 30);
+    assert_eq!(pool.version, LiquidityPool::VERSION);
+    assert_eq!(pool.k_last(), K_LAST);
+    assert!(pool.mints_protocol_fee());
+    assert!(pool.reserved.iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn decodes_pool_data_whatever_its_alignment() {
+    // RPC buffers make no alignment promise, shift the data by one byte
+    let mut shifted = vec![0];
+    shifted.extend(pool_account_data());
+
+    let pool = deserialize_liquidity_pool(&shifted[1..]).unwrap();
+    assert_eq!(bytemuck::bytes_of(&pool), &pool_account_data()[8..]);
+}
+
+#[test]
+fn rejects_pool_data_of_another_size() {
+    let data = pool_account_data();
+    assert!(deserialize_liquidity_pool(&data[..data.len() - 1]).is_err());
+    assert!(deserialize_liquidity_pool(&[data.as_slice(), &[0]].concat()).is_err());
+    assert!(deserialize_liquidity_pool(&data[..7]).is_err());
+}
+
+#[test]
+fn rejects_accounts_of_other_types() {
+    let mut data = pool_account_data();
+    data[..8].copy_from_slice(DexState::DISCRIMINATOR);
+    assert!(deserialize_liquidity_pool(&data).is_err());
+    assert!(deserialize_dex_state(&pool_account_data()).is_err());
+}
@@ -0,0 +1,153 @@
+//! Program addresses derived by the client, checked against the seeds the program declares
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{token::ID as SPL_TOKEN_PROGRAM_ID, token_2022::ID as TOKEN_2022_PROGRAM_ID};
+use dex::{LiquidityPool, POOLS_PER_REGISTRY_PAGE};
+use dex_client::{
+    pda::{
+        find_dex_state_address, find_fee_tier_address, find_liquidity_pool_address,
+        find_pool_pair_address, find_pool_registry_page_address, get_associated_token_address,
+    },
+    PROGRAM_ID,
+};
+
+const TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([1; 32]);
+const TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([2; 32]);
+
+#[test]
+fn dex_state_is_derived_from_the_admin() {
+    let admin = Pubkey::new_from_array([9; 32]);
+    assert_eq!(
+        find_dex_state_address(&admin),
+        Pubkey::find_program_address(&[b"dex_state", admin.as_ref()], &PROGRAM_ID)
+    );
+}
+
+#[test]
+fn fee_tiers_are_derived_from_their_index() {
+    let (dex_state, _) = find_dex_state_address(&dex::ADMIN_PUBKEY);
+    assert_eq!(
+        find_fee_tier_address(&dex_state, 3),
+        Pubkey::find_program_address(
+            &[b"fee_tier", dex_state.as_ref(), &3u16.to_le_bytes()],
+            &PROGRAM_ID
+        )
+    );
+    assert_ne!(
+        find_fee_tier_address(&dex_state, 0),
+        find_fee_tier_address(&dex_state, 1)
+    );
+}
+
+#[test]
+fn pools_are_derived_from_their_pair_and_fee_tier() {
+    assert_eq!(
+        find_liquidity_pool_address(&TOKEN_A_MINT, &TOKEN_B_MINT, 2),
+        Pubkey::find_program_address(
+            &[
+                b"liquidity_pool",
+                TOKEN_A_MINT.as_ref(),
+                TOKEN_B_MINT.as_ref(),
+                &2u16.to_le_bytes(),
+            ],
+            &PROGRAM_ID
+        )
+    );
+    // One pool per tier, and the mint order is the creation order
+    assert_ne!(
+        find_liquidity_pool_address(&TOKEN_A_MINT, &TOKEN_B_MINT, 2),
+        find_liquidity_pool_address(&TOKEN_A_MINT, &TOKEN_B_MINT, 3)
+    );
+    assert_ne!(
+        find_liquidity_pool_address(&TOKEN_A_MINT, &TOKEN_B_MINT, 2),
+        find_liquidity_pool_address(&TOKEN_B_MINT, &TOKEN_A_MINT, 2)
+    );
+}
+
+#[test]
+fn pools_created_before_fee_tiers_are_derived_from_their_pair_alone() {
+    let legacy_pool = find_liquidity_pool_address(
+        &TOKEN_A_MINT,
+        &TOKEN_B_MINT,
+        LiquidityPool::LEGACY_FEE_TIER_INDEX,
+    );
+    assert_eq!(
+        legacy_pool,
+        Pubkey::find_program_address(
+            &[
+                b"liquidity_pool",
+                TOKEN_A_MINT.as_ref(),
+                TOKEN_B_MINT.as_ref(),
+            ],
+            &PROGRAM_ID
+        )
+    );
+    // Not the address a tier at that index would have
+    assert_ne!(
+        legacy_pool,
+        Pubkey::find_program_address(
+            &[
+                b"liquidity_pool",
+                TOKEN_A_MINT.as_ref(),
+                TOKEN_B_MINT.as_ref(),
+                &LiquidityPool::LEGACY_FEE_TIER_INDEX.to_le_bytes(),
+            ],
+            &PROGRAM_ID
+        )
+    );
+}
+
+#[test]
+fn registry_pages_hold_consecutive_pools() {
+    let (dex_state, _) = find_dex_state_address(&dex::ADMIN_PUBKEY);
+    let last_of_first_page = POOLS_PER_REGISTRY_PAGE as u64 - 1;
+    assert_eq!(
+        find_pool_registry_page_address(&dex_state, 0),
+        Pubkey::find_program_address(
+            &[b"pool_registry", dex_state.as_ref(), &0u64.to_le_bytes()],
+            &PROGRAM_ID
+        )
+    );
+    assert_eq!(
+        find_pool_registry_page_address(&dex_state, 0),
+        find_pool_registry_page_address(&dex_state, last_of_first_page)
+    );
+    assert_eq!(
+        find_pool_registry_page_address(&dex_state, last_of_first_page + 1),
+        Pubkey::find_program_address(
+            &[b"pool_registry", dex_state.as_ref(), &1u64.to_le_bytes()],
+            &PROGRAM_ID
+        )
+    );
+}
+
+#[test]
+fn pair_lookups_ignore_the_mint_order() {
+    let (dex_state, _) = find_dex_state_address(&dex::ADMIN_PUBKEY);
+    let pair = find_pool_pair_address(&dex_state, &TOKEN_A_MINT, &TOKEN_B_MINT);
+    assert_eq!(
+        pair,
+        find_pool_pair_address(&dex_state, &TOKEN_B_MINT, &TOKEN_A_MINT)
+    );
+    assert_eq!(
+        pair,
+        Pubkey::find_program_address(
+            &[
+                b"pool_pair",
+                dex_state.as_ref(),
+                TOKEN_A_MINT.as_ref(),
+                TOKEN_B_MINT.as_ref(),
+            ],
+            &PROGRAM_ID
+        )
+    );
+}
+
+#[test]
+fn associated_token_accounts_depend_on_the_token_program() {
+    let owner = Pubkey::new_from_array([9; 32]);
+    assert_ne!(
+        get_associated_token_address(&owner, &TOKEN_A_MINT, &TOKEN_2022_PROGRAM_ID),
+        get_associated_token_address(&owner, &TOKEN_A_MINT, &SPL_TOKEN_PROGRAM_ID)
+    );
+}
@@ -0,0 +1,228 @@
+//! Quotes checked against the math the program runs, on reserves net of the protocol fees
+//! accrued in the vaults
+
+use anchor_lang::prelude::Pubkey;
+use dex::{
+    calculate_swap_prices, DynamicFee, DynamicFeeParams, LiquidityPool, ProtocolFeeMode,
+    BPS_DENOMINATOR,
+};
+use dex_client::{
+    quote::{quote_deposit, quote_fee_rate, quote_swap, quote_swap_exact_output, quote_withdrawal},
+    ClientError,
+};
+use dex_math::{
+    calculate_fee_breakdown, calculate_initial_liquidity, calculate_input_amount,
+    calculate_input_amount_before_fee, calculate_output_amount, calculate_proportional_liquidity,
+    calculate_protocol_fee_liquidity, calculate_withdrawal_amounts,
+};
+
+const TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([2; 32]);
+const TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([3; 32]);
+
+// Vault balances, protocol fees included
+const VAULT_A: u64 = 1_000_100;
+const VAULT_B: u64 = 4_000_200;
+// Reserves owned by the liquidity providers
+const RESERVE_A: u64 = 1_000_000;
+const RESERVE_B: u64 = 4_000_000;
+const TOTAL_LIQUIDITY: u64 = 2_000_000;
+const NOW: i64 = 1_700_000_000;
+
+/// A 0.3% pool setting 30% of its fees aside, holding uncollected protocol fees
+fn pool() -> LiquidityPool {
+    let mut pool: LiquidityPool = bytemuck::Zeroable::zeroed();
+    pool.token_a_mint = TOKEN_A_MINT;
+    pool.token_b_mint = TOKEN_B_MINT;
+    pool.total_liquidity = TOTAL_LIQUIDITY;
+    pool.fee_numerator = 3;
+    pool.fee_denominator = 1_000;
+    pool.protocol_fee_percentage = 30;
+    pool.protocol_fees_token_a = VAULT_A - RESERVE_A;
+    pool.protocol_fees_token_b = VAULT_B - RESERVE_B;
+    pool
+}
+
+/// The same pool minting its protocol fee, whose reserves grew since the last deposit
+fn mint_lp_pool() -> LiquidityPool {
+    let mut pool = pool();
+    pool.protocol_fee_mode = ProtocolFeeMode::MintLp as u8;
+    pool.protocol_fees_token_a = 0;
+    pool.protocol_fees_token_b = 0;
+    pool.k_last = (990_000u128 * 3_960_000).to_le_bytes();
+    pool
+}
+
+#[test]
+fn static_fee_pools_charge_their_fee_rate() {
+    assert_eq!(
+        quote_fee_rate(
+            &pool(),
+            VAULT_A,
+            VAULT_B,
+            &TOKEN_A_MINT,
+            (1_000, 3_000),
+            NOW
+        )
+        .unwrap(),
+        (3, 1_000)
+    );
+}
+
+#[test]
+fn dynamic_fee_pools_charge_the_fee_of_the_swap_price_impact() {
+    let mut pool = pool();
+    pool.dynamic_fee = DynamicFee::new(DynamicFeeParams {
+        min_fee_bps: 5,
+        max_fee_bps: 200,
+        filter_period: 30,
+        decay_period: 600,
+        reduction_factor: 5000,
+        variable_fee_control: 40_000,
+        max_volatility_accumulator: 100_000,
+    });
+
+    let fee_rate = |curve_amounts: (u64, u64)| {
+        let (price_before, price_after) =
+            calculate_swap_prices(true, RESERVE_A, RESERVE_B, curve_amounts.0, curve_amounts.1)
+                .unwrap();
+        let fee_bps = pool
+            .dynamic_fee
+            .clone()
+            .update(NOW, price_before, price_after)
+            .unwrap();
+        (fee_bps, BPS_DENOMINATOR)
+    };
+    let small_swap = (1_000, 3_996);
+    let large_swap = (100_000, 363_636);
+    for curve_amounts in [small_swap, large_swap] {
+        assert_eq!(
+            quote_fee_rate(&pool, VAULT_A, VAULT_B, &TOKEN_A_MINT, curve_amounts, NOW).unwrap(),
+            fee_rate(curve_amounts)
+        );
+    }
+    assert!(fee_rate(large_swap).0 > fee_rate(small_swap).0);
+}
+
+#[test]
+fn exact_input_swaps_match_the_program_math() {
+    for (source_mint, source_reserve, destination_reserve) in [
+        (TOKEN_A_MINT, RESERVE_A, RESERVE_B),
+        (TOKEN_B_MINT, RESERVE_B, RESERVE_A),
+    ] {
+        let input_amount = 10_000;
+        let quote = quote_swap(&pool(), VAULT_A, VAULT_B, &source_mint, input_amount, NOW).unwrap();
+
+        let (total_fee, protocol_fee) =
+            calculate_fee_breakdown(input_amount, 3, 1_000, 30).unwrap();
+        let output_amount = calculate_output_amount(
+            input_amount - total_fee,
+            source_reserve,
+            destination_reserve,
+            0,
+            1,
+        )
+        .unwrap();
+        assert_eq!(quote.input_amount, input_amount);
+        assert_eq!(quote.output_amount, output_amount);
+        assert_eq!((quote.fee_numerator, quote.fee_denominator), (3, 1_000));
+        assert_eq!(quote.protocol_fee, protocol_fee);
+    }
+}
+
+#[test]
+fn exact_output_swaps_match_the_program_math() {
+    let output_amount = 30_000;
+    let quote =
+        quote_swap_exact_output(&pool(), VAULT_A, VAULT_B, &TOKEN_A_MINT, output_amount, NOW)
+            .unwrap();
+
+    let input_amount_with_fee =
+        calculate_input_amount(output_amount, RESERVE_A, RESERVE_B).unwrap();
+    let input_amount = calculate_input_amount_before_fee(input_amount_with_fee, 3, 1_000).unwrap();
+    let (_, protocol_fee) = calculate_fee_breakdown(input_amount, 3, 1_000, 30).unwrap();
+    assert_eq!(quote.input_amount, input_amount);
+    assert_eq!(quote.output_amount, output_amount);
+    assert_eq!(quote.protocol_fee, protocol_fee);
+
+    // Paying that input exactly buys at least the output asked for
+    let exact_input =
+        quote_swap(&pool(), VAULT_A, VAULT_B, &TOKEN_A_MINT, input_amount, NOW).unwrap();
+    assert!(exact_input.output_amount >= output_amount);
+}
+
+#[test]
+fn pools_minting_their_protocol_fee_set_nothing_aside_in_swaps() {
+    let quote = quote_swap(
+        &mint_lp_pool(),
+        RESERVE_A,
+        RESERVE_B,
+        &TOKEN_A_MINT,
+        10_000,
+        NOW,
+    )
+    .unwrap();
+    assert_eq!(quote.protocol_fee, 0);
+}
+
+#[test]
+fn swaps_from_another_mint_are_refused() {
+    let other_mint = Pubkey::new_from_array([9; 32]);
+    assert!(matches!(
+        quote_swap(&pool(), VAULT_A, VAULT_B, &other_mint, 10_000, NOW),
+        Err(ClientError::MintNotInPool(mint)) if mint == other_mint
+    ));
+}
+
+#[test]
+fn vaults_holding_less_than_the_protocol_fees_are_refused() {
+    assert!(matches!(
+        quote_swap(&pool(), 99, VAULT_B, &TOKEN_A_MINT, 10_000, NOW),
+        Err(ClientError::Math(_))
+    ));
+}
+
+#[test]
+fn first_deposits_mint_the_geometric_mean() {
+    let mut empty_pool = pool();
+    empty_pool.total_liquidity = 0;
+    empty_pool.protocol_fees_token_a = 0;
+    empty_pool.protocol_fees_token_b = 0;
+
+    assert_eq!(
+        quote_deposit(&empty_pool, 0, 0, 1_000_000, 4_000_000).unwrap(),
+        calculate_initial_liquidity(1_000_000, 4_000_000).unwrap()
+    );
+}
+
+#[test]
+fn deposits_and_withdrawals_match_the_program_math() {
+    assert_eq!(
+        quote_deposit(&pool(), VAULT_A, VAULT_B, 10_000, 40_000).unwrap(),
+        calculate_proportional_liquidity(10_000, 40_000, RESERVE_A, RESERVE_B, TOTAL_LIQUIDITY)
+            .unwrap()
+    );
+    assert_eq!(
+        quote_withdrawal(&pool(), VAULT_A, VAULT_B, 20_000).unwrap(),
+        calculate_withdrawal_amounts(20_000, RESERVE_A, RESERVE_B, TOTAL_LIQUIDITY).unwrap()
+    );
+}
+
+#[test]
+fn deposits_and_withdrawals_count_the_protocol_fee_minted_first() {
+    let pool = mint_lp_pool();
+    let protocol_liquidity =
+        calculate_protocol_fee_liquidity(RESERVE_A, RESERVE_B, pool.k_last(), TOTAL_LIQUIDITY, 30)
+            .unwrap();
+    assert!(protocol_liquidity > 0);
+    let total_liquidity = TOTAL_LIQUIDITY + protocol_liquidity;
+
+    assert_eq!(
+        quote_deposit(&pool, RESERVE_A, RESERVE_B, 10_000, 40_000).unwrap(),
+        calculate_proportional_liquidity(10_000, 40_000, RESERVE_A, RESERVE_B, total_liquidity)
+            .unwrap()
+    );
+    assert_eq!(
+        quote_withdrawal(&pool, RESERVE_A, RESERVE_B, 20_000).unwrap(),
+        calculate_withdrawal_amounts(20_000, RESERVE_A, RESERVE_B, total_liquidity).unwrap()
+    );
+}
@@ -0,0 +1,22 @@
+use core::fmt;
+
+/// Errors returned by the math functions
+/// Variants mirror the program errors they are reported as on-chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathError {
+    // Not enough reserves for the operation, an amount rounding to zero, or an overflow
+    InsufficientLiquidity,
+    // A fee numerator that is not smaller than its denominator
+    InvalidFees,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathError::InsufficientLiquidity => write!(f, "Insufficient liquidity."),
+            MathError::InvalidFees => write!(f, "Invalid fees."),
+        }
+    }
+}
+
+impl core::error::Error for MathError {}
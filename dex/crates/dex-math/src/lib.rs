@@ -0,0 +1,23 @@
+//! AMM math shared by the dex program and its off-chain clients
+//!
+//! Everything here is pure integer math with the program's exact rounding, so quotes computed
+//! off-chain match what the program executes
+
+#![no_std]
+
+mod error;
+mod liquidity;
+mod math;
+mod price;
+mod sqrt;
+mod swap;
+
+pub use error::MathError;
+pub use liquidity::*;
+pub use math::*;
+pub use price::*;
+pub use sqrt::*;
+pub use swap::*;
+
+/// Result type of every math function
+pub type Result<T> = core::result::Result<T, MathError>;
@@ -0,0 +1,108 @@
+use crate::{calculate_sqrt, mul_div, mul_div_u64, MathError, Result, Rounding};
+
+/// Calculates initial liquidity tokens for the first deposit using geometric mean
+pub fn calculate_initial_liquidity(token_a_amount: u64, token_b_amount: u64) -> Result<u64> {
+    // Calculate product of token amounts
+    let product = (token_a_amount as u128)
+        .checked_mul(token_b_amount as u128)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    let sqrt_result = calculate_sqrt(product);
+
+    // Make sure we're minting a non-zero amount
+    if sqrt_result == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    Ok(sqrt_result)
+}
+
+/// Calculates liquidity tokens for subsequent deposits proportionally
+pub fn calculate_proportional_liquidity(
+    token_a_amount: u64,
+    token_b_amount: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_liquidity: u64,
+) -> Result<u64> {
+    // LP tokens by each token's proportion, rounded down in favor of the pool
+    // A share beyond u64 is refused rather than narrowed
+    let lp_tokens_by_a = mul_div_u64(token_a_amount, total_liquidity, reserve_a, Rounding::Down)
+        .ok_or(MathError::InsufficientLiquidity)?;
+    let lp_tokens_by_b = mul_div_u64(token_b_amount, total_liquidity, reserve_b, Rounding::Down)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    // Use the minimum to maintain the price ratio
+    let lp_tokens = core::cmp::min(lp_tokens_by_a, lp_tokens_by_b);
+
+    // Make sure we're minting a non-zero amount
+    if lp_tokens == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    Ok(lp_tokens)
+}
+
+/// Calculates token amounts to withdraw based on LP tokens amount
+pub fn calculate_withdrawal_amounts(
+    lp_amount: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_liquidity: u64,
+) -> Result<(u64, u64)> {
+    // Share of each reserve the LP tokens burned stand for, rounded down in favor of the pool
+    // amount = floor(reserve * lp_amount / total_liquidity)
+    let token_a_amount = mul_div_u64(reserve_a, lp_amount, total_liquidity, Rounding::Down)
+        .ok_or(MathError::InsufficientLiquidity)?;
+    let token_b_amount = mul_div_u64(reserve_b, lp_amount, total_liquidity, Rounding::Down)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    // Make sure we're withdrawing non-zero amounts
+    if token_a_amount == 0 || token_b_amount == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    Ok((token_a_amount, token_b_amount))
+}
+
+/// Calculates the LP tokens minted to the protocol for its share of the swap fees earned since
+/// `k_last`, the product of the reserves after the last deposit or withdrawal (Uniswap v2)
+/// Fees are what grows sqrt(k) between deposits and withdrawals, the protocol takes
+/// `protocol_fee_percentage` of that growth:
+/// minted / (total_liquidity + minted) = percentage * (sqrt(k) - sqrt(k_last)) / sqrt(k)
+pub fn calculate_protocol_fee_liquidity(
+    reserve_a: u64,
+    reserve_b: u64,
+    k_last: u128,
+    total_liquidity: u64,
+    protocol_fee_percentage: u8,
+) -> Result<u64> {
+    if protocol_fee_percentage > 100 {
+        return Err(MathError::InvalidFees);
+    }
+    // Nothing accrues before the first deposit, nor to an empty pool
+    if k_last == 0 || total_liquidity == 0 || protocol_fee_percentage == 0 {
+        return Ok(0);
+    }
+
+    let root_k = calculate_sqrt(reserve_a as u128 * reserve_b as u128) as u128;
+    let root_k_last = calculate_sqrt(k_last) as u128;
+    if root_k <= root_k_last {
+        return Ok(0);
+    }
+
+    // Solved for minted, with the percentage p out of 100:
+    // minted = total_liquidity * p * (sqrt(k) - sqrt(k_last)) / ((100 - p) * sqrt(k) + p *
+    // sqrt(k_last)) rounded down, the fees not minted stay with the liquidity providers
+    let percentage = protocol_fee_percentage as u128;
+    let denominator = (100 - percentage) * root_k + percentage * root_k_last;
+    let protocol_liquidity = mul_div(
+        total_liquidity as u128 * (root_k - root_k_last),
+        percentage,
+        denominator,
+        Rounding::Down,
+    )
+    .ok_or(MathError::InsufficientLiquidity)?;
+
+    u64::try_from(protocol_liquidity).map_err(|_| MathError::InsufficientLiquidity)
+}
@@ -0,0 +1,65 @@
+use super::{mul_div, Rounding};
+
+/// Unsigned Q64.64 fixed point number: 64 integer bits and 64 fraction bits in a u128
+/// The format of the prices the program stores
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Q64x64(u128);
+
+impl Q64x64 {
+    pub const FRACTION_BITS: u32 = 64;
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << Self::FRACTION_BITS);
+    pub const MAX: Self = Self(u128::MAX);
+
+    /// The number whose raw representation is `bits`, i.e. `bits / 2^64`
+    pub const fn from_bits(bits: u128) -> Self {
+        Self(bits)
+    }
+
+    pub const fn to_bits(self) -> u128 {
+        self.0
+    }
+
+    pub const fn from_int(value: u64) -> Self {
+        Self((value as u128) << Self::FRACTION_BITS)
+    }
+
+    /// `numerator / denominator`, None on a zero denominator or a ratio of 2^64 or more
+    pub fn from_ratio(numerator: u128, denominator: u128, rounding: Rounding) -> Option<Self> {
+        mul_div(numerator, Self::ONE.0, denominator, rounding).map(Self)
+    }
+
+    /// The integer part, or the next integer up for a fractional number rounded up
+    /// None only when rounding up a number above u64::MAX
+    pub fn to_int(self, rounding: Rounding) -> Option<u64> {
+        let int = (self.0 >> Self::FRACTION_BITS) as u64;
+        match rounding {
+            Rounding::Up if self.0 & (Self::ONE.0 - 1) > 0 => int.checked_add(1),
+            _ => Some(int),
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Product of two Q64.64 numbers, None when it reaches 2^64
+    pub fn checked_mul(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, other.0, Self::ONE.0, rounding).map(Self)
+    }
+
+    /// Quotient of two Q64.64 numbers, None on a zero divisor or a quotient of 2^64 or more
+    pub fn checked_div(self, other: Self, rounding: Rounding) -> Option<Self> {
+        mul_div(self.0, Self::ONE.0, other.0, rounding).map(Self)
+    }
+
+    /// This number times a token amount, as a token amount, None when it doesn't fit a u64
+    pub fn mul_int(self, value: u64, rounding: Rounding) -> Option<u64> {
+        mul_div(self.0, value as u128, Self::ONE.0, rounding)
+            .and_then(|result| u64::try_from(result).ok())
+    }
+}
@@ -0,0 +1,34 @@
+//! Wide integer and fixed point arithmetic with explicit rounding
+//!
+//! Every division takes the direction it rounds in, so the pool math states at each step which
+//! side keeps the remainder: amounts paid out round down, amounts paid in round up. Products are
+//! computed over 256 bits, so no intermediate overflows whatever the u64 reserves
+
+mod fixed;
+mod u256;
+
+pub use fixed::*;
+pub use u256::*;
+
+/// Direction of a division that doesn't come out exact
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    // Toward zero, for amounts the pool pays out
+    Down,
+    // Away from zero, for amounts the pool is paid
+    Up,
+}
+
+/// Computes `a * b / denominator` with the product kept over 256 bits
+/// Returns None on a zero denominator or a result beyond u128
+pub fn mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Option<u128> {
+    U256::full_mul(a, b)
+        .checked_div(denominator, rounding)?
+        .to_u128()
+}
+
+/// `mul_div` on token amounts, returns None as well when the result doesn't fit a u64
+pub fn mul_div_u64(a: u64, b: u64, denominator: u64, rounding: Rounding) -> Option<u64> {
+    mul_div(a as u128, b as u128, denominator as u128, rounding)
+        .and_then(|result| u64::try_from(result).ok())
+}
@@ -0,0 +1,133 @@
+use super::Rounding;
+
+/// Unsigned 256-bit integer, wide enough for the product of any two u128 values
+/// Only the operations the pool math needs: full products, addition, subtraction, and division
+/// by a u128
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U256 {
+    // Declared first so that the derived ordering compares the high half first
+    high: u128,
+    low: u128,
+}
+
+impl U256 {
+    pub const ZERO: Self = Self::new(0, 0);
+    pub const ONE: Self = Self::new(0, 1);
+    pub const MAX: Self = Self::new(u128::MAX, u128::MAX);
+
+    /// The value `high * 2^128 + low`
+    pub const fn new(high: u128, low: u128) -> Self {
+        Self { high, low }
+    }
+
+    pub const fn high(self) -> u128 {
+        self.high
+    }
+
+    pub const fn low(self) -> u128 {
+        self.low
+    }
+
+    /// The value as a u128, None when it doesn't fit
+    pub const fn to_u128(self) -> Option<u128> {
+        if self.high == 0 {
+            Some(self.low)
+        } else {
+            None
+        }
+    }
+
+    /// Full product of two u128 values, which never overflows
+    pub const fn full_mul(a: u128, b: u128) -> Self {
+        const MASK: u128 = u64::MAX as u128;
+        let (a_high, a_low) = (a >> 64, a & MASK);
+        let (b_high, b_low) = (b >> 64, b & MASK);
+
+        // Four 64x64-bit partial products, none of which overflows a u128
+        let low_low = a_low * b_low;
+        let high_low = a_high * b_low;
+        let low_high = a_low * b_high;
+        let high_high = a_high * b_high;
+
+        // Bits 64..128, with the carries into bit 128 and above kept in its upper half
+        let middle = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+
+        Self {
+            high: high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64),
+            low: (middle << 64) | (low_low & MASK),
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (low, carry) = self.low.overflowing_add(other.low);
+        let high = self
+            .high
+            .checked_add(other.high)?
+            .checked_add(carry as u128)?;
+        Some(Self { high, low })
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let (low, borrow) = self.low.overflowing_sub(other.low);
+        let high = self
+            .high
+            .checked_sub(other.high)?
+            .checked_sub(borrow as u128)?;
+        Some(Self { high, low })
+    }
+
+    /// Quotient and remainder of the division by `divisor`, None when it is zero
+    pub fn checked_div_rem(self, divisor: u128) -> Option<(Self, u128)> {
+        if divisor == 0 {
+            return None;
+        }
+        if self.high == 0 {
+            return Some((Self::new(0, self.low / divisor), self.low % divisor));
+        }
+
+        let (low, remainder) = div_wide(self.high % divisor, self.low, divisor);
+        Some((Self::new(self.high / divisor, low), remainder))
+    }
+
+    /// Quotient of the division by `divisor` in the given rounding, None when it is zero
+    pub fn checked_div(self, divisor: u128, rounding: Rounding) -> Option<Self> {
+        let (quotient, remainder) = self.checked_div_rem(divisor)?;
+        match rounding {
+            Rounding::Up if remainder > 0 => quotient.checked_add(Self::ONE),
+            _ => Some(quotient),
+        }
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        Self::new(0, value)
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        Self::new(0, value as u128)
+    }
+}
+
+/// Divides `high * 2^128 + low` by `divisor`, returns (quotient, remainder)
+/// `high` must be below `divisor`, which keeps the quotient within a u128
+///
+/// Shift and subtract, one bit of `low` at a time. The remainder stays below the divisor, so
+/// after a shift it is below twice the divisor and one subtraction brings it back; a remainder
+/// shifted past bit 127 is above any u128 divisor, and the wrapping subtraction is then exact
+fn div_wide(mut remainder: u128, low: u128, divisor: u128) -> (u128, u128) {
+    let mut quotient = 0u128;
+    for bit in (0..u128::BITS).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= divisor {
+            remainder = remainder.wrapping_sub(divisor);
+            quotient |= 1;
+        }
+    }
+
+    (quotient, remainder)
+}
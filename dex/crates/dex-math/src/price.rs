@@ -0,0 +1,9 @@
+use crate::{Q64x64, Rounding};
+
+/// Spot price of token A in token B as a Q64.64 fixed point number, rounded down
+/// Returns 0 when the pool has no token A reserves yet
+pub fn calculate_spot_price(reserve_a: u64, reserve_b: u64) -> u128 {
+    // reserve_b / reserve_a is below 2^64 for any reserve_a >= 1, only 0 is refused
+    Q64x64::from_ratio(reserve_b as u128, reserve_a as u128, Rounding::Down)
+        .map_or(0, Q64x64::to_bits)
+}
@@ -0,0 +1,24 @@
+/// Floor of the square root of `value`, exact over the whole u128 range
+///
+/// Newton's iteration on integers, started from a power of two at or above the root. From any
+/// x >= floor(sqrt(value)) the next step (x + value / x) / 2 stays >= floor(sqrt(value)), and it
+/// is strictly smaller than x until x reaches the floor root: the first step that doesn't
+/// decrease marks the result. Starting within a factor of two of the root, this takes about 7
+/// steps for the largest values where a binary search over 0..value takes up to 128
+pub fn calculate_sqrt(value: u128) -> u64 {
+    if value < 2 {
+        return value as u64;
+    }
+
+    // value < 2^bits, so 2^ceil(bits / 2) is above its root (2^64 at most, no overflow below)
+    let bits = u128::BITS - value.leading_zeros();
+    let mut root = 1u128 << bits.div_ceil(2);
+    loop {
+        let next = (root + value / root) / 2;
+        if next >= root {
+            // The root of a u128 always fits in a u64
+            return root as u64;
+        }
+        root = next;
+    }
+}
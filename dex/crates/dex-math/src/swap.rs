@@ -0,0 +1,116 @@
+use crate::{mul_div, mul_div_u64, MathError, Result, Rounding};
+
+/// Calculates the output amount for a swap based on constant product formula (x*y=k)
+/// Takes into account the fee charged by the pool
+pub fn calculate_output_amount(
+    input_amount: u64,
+    input_reserve: u64,
+    output_reserve: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<u64> {
+    // Ensure there are enough reserves
+    if input_reserve == 0 || output_reserve == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    // Calculate the input amount after fee, the fee rounded up in favor of the pool
+    // fee_amount = ceil(input_amount * fee_numerator / fee_denominator)
+    // input_amount_with_fee = input_amount - fee_amount
+    let fee_amount = mul_div_u64(input_amount, fee_numerator, fee_denominator, Rounding::Up)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    let input_amount_with_fee = input_amount
+        .checked_sub(fee_amount)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    // Use the constant product formula: (x + dx) * (y - dy) = x * y
+    // Solved for dy: dy = floor(y * dx / (x + dx)), rounded down since the pool pays it out
+    // Where:
+    // x = input_reserve
+    // dx = input_amount_with_fee
+    // y = output_reserve
+    // dy = output_amount
+    let output_amount = mul_div(
+        output_reserve as u128,
+        input_amount_with_fee as u128,
+        input_reserve as u128 + input_amount_with_fee as u128,
+        Rounding::Down,
+    )
+    .ok_or(MathError::InsufficientLiquidity)? as u64;
+
+    // Ensure output amount is not zero and doesn't exceed available reserves
+    if output_amount == 0 || output_amount > output_reserve {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    Ok(output_amount)
+}
+
+/// Calculates the input (after pool fee) needed to take exactly `output_amount` out of the pool
+/// Inverse of the constant product formula, rounded up in favor of the pool
+pub fn calculate_input_amount(
+    output_amount: u64,
+    input_reserve: u64,
+    output_reserve: u64,
+) -> Result<u64> {
+    // The pool can never be fully drained
+    if input_reserve == 0 || output_amount == 0 || output_amount >= output_reserve {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    // Solve (x + dx) * (y - dy) = x * y for dx: dx = ceil(x * dy / (y - dy))
+    mul_div_u64(
+        input_reserve,
+        output_amount,
+        output_reserve - output_amount,
+        Rounding::Up,
+    )
+    .ok_or(MathError::InsufficientLiquidity)
+}
+
+/// Calculates the smallest input that is still `input_amount_after_fee` once the pool fee of
+/// `calculate_fee_breakdown` is taken out
+pub fn calculate_input_amount_before_fee(
+    input_amount_after_fee: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<u64> {
+    let kept_numerator = fee_denominator
+        .checked_sub(fee_numerator)
+        .filter(|kept| *kept > 0)
+        .ok_or(MathError::InvalidFees)?;
+
+    // input = ceil(input_after_fee * denominator / (denominator - numerator))
+    mul_div_u64(
+        input_amount_after_fee,
+        fee_denominator,
+        kept_numerator,
+        Rounding::Up,
+    )
+    .ok_or(MathError::InsufficientLiquidity)
+}
+
+/// Calculates the fee breakdown for a swap
+/// Returns (total_fee_amount, protocol_fee_amount)
+pub fn calculate_fee_breakdown(
+    input_amount: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+) -> Result<(u64, u64)> {
+    // Calculate total fee amount, rounded up in favor of the pool
+    let total_fee_amount = mul_div_u64(input_amount, fee_numerator, fee_denominator, Rounding::Up)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    // Calculate protocol fee portion, rounded down so the remainder stays with the LPs
+    let protocol_fee_amount = mul_div_u64(
+        total_fee_amount,
+        protocol_fee_percentage as u64,
+        100,
+        Rounding::Down,
+    )
+    .ok_or(MathError::InsufficientLiquidity)?;
+
+    Ok((total_fee_amount, protocol_fee_amount))
+}
@@ -0,0 +1,426 @@
+//! Invariants of the AMM math over the full u64 input range
+//!
+//! Functions are allowed to refuse an input (overflow, amount rounding to zero), the invariants
+//! apply to every result they do return
+
+use dex_math::{
+    calculate_fee_breakdown, calculate_initial_liquidity, calculate_input_amount,
+    calculate_input_amount_before_fee, calculate_output_amount, calculate_proportional_liquidity,
+    calculate_protocol_fee_liquidity, calculate_sqrt, calculate_withdrawal_amounts, U256,
+};
+use proptest::prelude::*;
+
+/// Full product of two u128 values as (high, low) halves, products of reserves overflow u128
+fn wide_mul(a: u128, b: u128) -> (u128, u128) {
+    let product = U256::full_mul(a, b);
+    (product.high(), product.low())
+}
+
+/// A fee rate the program accepts: numerator below a non-zero denominator
+fn fee_rate() -> impl Strategy<Value = (u64, u64)> {
+    (1..=u64::MAX).prop_flat_map(|denominator| (0..denominator, Just(denominator)))
+}
+
+/// A non-zero LP supply with an amount of it to burn
+fn lp_share() -> impl Strategy<Value = (u64, u64)> {
+    (1..=u64::MAX).prop_flat_map(|total_liquidity| (0..=total_liquidity, Just(total_liquidity)))
+}
+
+proptest! {
+    #[test]
+    fn wide_mul_matches_u128_when_it_fits(a in any::<u64>(), b in any::<u64>()) {
+        prop_assert_eq!(wide_mul(a as u128, b as u128), (0, a as u128 * b as u128));
+    }
+
+    #[test]
+    fn swaps_never_decrease_k(
+        input_amount in any::<u64>(),
+        input_reserve in 1..=u64::MAX,
+        output_reserve in 1..=u64::MAX,
+        (fee_numerator, fee_denominator) in fee_rate(),
+    ) {
+        if let Ok(output_amount) = calculate_output_amount(
+            input_amount,
+            input_reserve,
+            output_reserve,
+            fee_numerator,
+            fee_denominator,
+        ) {
+            prop_assert!(output_amount < output_reserve);
+
+            // The pool receives the whole input, fee included
+            let k_before = wide_mul(input_reserve as u128, output_reserve as u128);
+            let k_after = wide_mul(
+                input_reserve as u128 + input_amount as u128,
+                (output_reserve - output_amount) as u128,
+            );
+            prop_assert!(k_after >= k_before);
+        }
+    }
+
+    #[test]
+    fn swap_output_rounds_down(
+        input_amount in any::<u64>(),
+        input_reserve in 1..=u64::MAX,
+        output_reserve in 1..=u64::MAX,
+        (fee_numerator, fee_denominator) in fee_rate(),
+    ) {
+        if let Ok(output_amount) = calculate_output_amount(
+            input_amount,
+            input_reserve,
+            output_reserve,
+            fee_numerator,
+            fee_denominator,
+        ) {
+            // Even on the input left after the fee, k holds: the output never exceeds the
+            // exact constant product amount
+            let fee_amount = (input_amount as u128 * fee_numerator as u128)
+                .div_ceil(fee_denominator as u128) as u64;
+            let input_after_fee = (input_amount - fee_amount) as u128;
+            prop_assert!(
+                wide_mul(output_amount as u128, input_reserve as u128 + input_after_fee)
+                    <= wide_mul(output_reserve as u128, input_after_fee)
+            );
+        }
+    }
+
+    #[test]
+    fn swap_output_is_monotonic_in_the_input(
+        input_amount in any::<u64>(),
+        extra_input in any::<u64>(),
+        input_reserve in 1..=u64::MAX,
+        output_reserve in 1..=u64::MAX,
+        (fee_numerator, fee_denominator) in fee_rate(),
+    ) {
+        let larger_input = input_amount.saturating_add(extra_input);
+        let swap = |amount| {
+            calculate_output_amount(
+                amount,
+                input_reserve,
+                output_reserve,
+                fee_numerator,
+                fee_denominator,
+            )
+        };
+        if let (Ok(output), Ok(larger_output)) = (swap(input_amount), swap(larger_input)) {
+            prop_assert!(larger_output >= output);
+        }
+    }
+
+    #[test]
+    fn exact_output_input_rounds_up(
+        output_amount in any::<u64>(),
+        input_reserve in 1..=u64::MAX,
+        output_reserve in 1..=u64::MAX,
+    ) {
+        if let Ok(input_amount) =
+            calculate_input_amount(output_amount, input_reserve, output_reserve)
+        {
+            // The input always covers the exact constant product amount, so k never decreases
+            let k_before = wide_mul(input_reserve as u128, output_reserve as u128);
+            let k_after = wide_mul(
+                input_reserve as u128 + input_amount as u128,
+                (output_reserve - output_amount) as u128,
+            );
+            prop_assert!(k_after >= k_before);
+        }
+    }
+
+    #[test]
+    fn input_before_fee_covers_the_fee(
+        input_amount_after_fee in any::<u64>(),
+        (fee_numerator, fee_denominator) in fee_rate(),
+    ) {
+        if let Ok(input_amount) = calculate_input_amount_before_fee(
+            input_amount_after_fee,
+            fee_numerator,
+            fee_denominator,
+        ) {
+            // The fee is rounded up like the swaps do
+            let fee_amount = (input_amount as u128 * fee_numerator as u128)
+                .div_ceil(fee_denominator as u128) as u64;
+            prop_assert!(input_amount - fee_amount >= input_amount_after_fee);
+        }
+    }
+
+    #[test]
+    fn sqrt_is_the_floor_root(value in any::<u128>()) {
+        let root = calculate_sqrt(value) as u128;
+        prop_assert!(root * root <= value);
+        // (root + 1)^2 above u128::MAX is above every value
+        if let Some(next_square) = (root + 1).checked_mul(root + 1) {
+            prop_assert!(next_square > value);
+        }
+    }
+
+    #[test]
+    fn first_deposit_lp_is_the_floor_geometric_mean(
+        token_a_amount in 1..=u64::MAX,
+        token_b_amount in 1..=u64::MAX,
+    ) {
+        // Defined for every pair of non-zero amounts
+        let lp_amount = calculate_initial_liquidity(token_a_amount, token_b_amount).unwrap();
+        prop_assert_eq!(
+            lp_amount,
+            calculate_sqrt(token_a_amount as u128 * token_b_amount as u128)
+        );
+        prop_assert!(lp_amount >= token_a_amount.min(token_b_amount));
+        prop_assert!(lp_amount <= token_a_amount.max(token_b_amount));
+    }
+
+    #[test]
+    fn first_deposit_round_trip_never_pays_out_more(
+        token_a_amount in any::<u64>(),
+        token_b_amount in any::<u64>(),
+    ) {
+        if let Ok(lp_amount) = calculate_initial_liquidity(token_a_amount, token_b_amount) {
+            if let Ok((withdrawn_a, withdrawn_b)) =
+                calculate_withdrawal_amounts(lp_amount, token_a_amount, token_b_amount, lp_amount)
+            {
+                prop_assert!(withdrawn_a <= token_a_amount);
+                prop_assert!(withdrawn_b <= token_b_amount);
+            }
+        }
+    }
+
+    #[test]
+    fn deposit_round_trip_never_pays_out_more(
+        token_a_amount in any::<u64>(),
+        token_b_amount in any::<u64>(),
+        reserve_a in 1..=u64::MAX,
+        reserve_b in 1..=u64::MAX,
+        total_liquidity in 1..=u64::MAX,
+    ) {
+        if let Ok(lp_amount) = calculate_proportional_liquidity(
+            token_a_amount,
+            token_b_amount,
+            reserve_a,
+            reserve_b,
+            total_liquidity,
+        ) {
+            if let (Some(new_reserve_a), Some(new_reserve_b), Some(new_total_liquidity)) = (
+                reserve_a.checked_add(token_a_amount),
+                reserve_b.checked_add(token_b_amount),
+                total_liquidity.checked_add(lp_amount),
+            ) {
+                if let Ok((withdrawn_a, withdrawn_b)) = calculate_withdrawal_amounts(
+                    lp_amount,
+                    new_reserve_a,
+                    new_reserve_b,
+                    new_total_liquidity,
+                ) {
+                    prop_assert!(withdrawn_a <= token_a_amount);
+                    prop_assert!(withdrawn_b <= token_b_amount);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deposits_round_lp_tokens_down(
+        token_a_amount in any::<u64>(),
+        token_b_amount in any::<u64>(),
+        reserve_a in 1..=u64::MAX,
+        reserve_b in 1..=u64::MAX,
+        total_liquidity in 1..=u64::MAX,
+    ) {
+        if let Ok(lp_amount) = calculate_proportional_liquidity(
+            token_a_amount,
+            token_b_amount,
+            reserve_a,
+            reserve_b,
+            total_liquidity,
+        ) {
+            // Never more than the exact share of either token
+            prop_assert!(
+                lp_amount as u128 * reserve_a as u128
+                    <= token_a_amount as u128 * total_liquidity as u128
+            );
+            prop_assert!(
+                lp_amount as u128 * reserve_b as u128
+                    <= token_b_amount as u128 * total_liquidity as u128
+            );
+        }
+    }
+
+    #[test]
+    fn swap_fee_rounds_up(
+        input_amount in any::<u64>(),
+        (fee_numerator, fee_denominator) in fee_rate(),
+        protocol_fee_percentage in 0..=100u8,
+    ) {
+        let (total_fee, protocol_fee) = calculate_fee_breakdown(
+            input_amount,
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+        )
+        .unwrap();
+        // The smallest fee at or above the exact one, of which the protocol takes no more
+        // than its exact share
+        let exact_fee = input_amount as u128 * fee_numerator as u128;
+        prop_assert!(total_fee as u128 * fee_denominator as u128 >= exact_fee);
+        if total_fee > 0 {
+            prop_assert!((total_fee as u128 - 1) * (fee_denominator as u128) < exact_fee);
+        }
+        prop_assert!(protocol_fee as u128 * 100 <= total_fee as u128 * protocol_fee_percentage as u128);
+    }
+
+    #[test]
+    fn withdrawals_are_the_floor_share_of_full_u64_reserves(
+        (lp_amount, total_liquidity) in lp_share(),
+        reserve_a in any::<u64>(),
+        reserve_b in any::<u64>(),
+    ) {
+        // Exact for any reserves, with no precision lost to an intermediate proportion
+        if let Ok((withdrawn_a, withdrawn_b)) =
+            calculate_withdrawal_amounts(lp_amount, reserve_a, reserve_b, total_liquidity)
+        {
+            let share = |reserve: u64| {
+                (reserve as u128 * lp_amount as u128 / total_liquidity as u128) as u64
+            };
+            prop_assert_eq!(withdrawn_a, share(reserve_a));
+            prop_assert_eq!(withdrawn_b, share(reserve_b));
+        }
+    }
+
+    #[test]
+    fn withdrawals_round_amounts_down(
+        (lp_amount, total_liquidity) in lp_share(),
+        reserve_a in any::<u64>(),
+        reserve_b in any::<u64>(),
+    ) {
+        if let Ok((withdrawn_a, withdrawn_b)) =
+            calculate_withdrawal_amounts(lp_amount, reserve_a, reserve_b, total_liquidity)
+        {
+            // Never more than the exact share of the reserves
+            prop_assert!(
+                withdrawn_a as u128 * total_liquidity as u128
+                    <= lp_amount as u128 * reserve_a as u128
+            );
+            prop_assert!(
+                withdrawn_b as u128 * total_liquidity as u128
+                    <= lp_amount as u128 * reserve_b as u128
+            );
+        }
+    }
+
+    #[test]
+    fn first_deposit_lp_is_monotonic(
+        token_a_amount in any::<u64>(),
+        extra_token_a in any::<u64>(),
+        token_b_amount in any::<u64>(),
+    ) {
+        let larger_a = token_a_amount.saturating_add(extra_token_a);
+        if let (Ok(lp_amount), Ok(larger_lp_amount)) = (
+            calculate_initial_liquidity(token_a_amount, token_b_amount),
+            calculate_initial_liquidity(larger_a, token_b_amount),
+        ) {
+            prop_assert!(larger_lp_amount >= lp_amount);
+        }
+    }
+
+    #[test]
+    fn deposit_lp_is_monotonic(
+        token_a_amount in any::<u64>(),
+        token_b_amount in any::<u64>(),
+        extra_token_a in any::<u64>(),
+        extra_token_b in any::<u64>(),
+        reserve_a in 1..=u64::MAX,
+        reserve_b in 1..=u64::MAX,
+        total_liquidity in 1..=u64::MAX,
+    ) {
+        let deposit = |token_a_amount, token_b_amount| {
+            calculate_proportional_liquidity(
+                token_a_amount,
+                token_b_amount,
+                reserve_a,
+                reserve_b,
+                total_liquidity,
+            )
+        };
+        if let (Ok(lp_amount), Ok(larger_lp_amount)) = (
+            deposit(token_a_amount, token_b_amount),
+            deposit(
+                token_a_amount.saturating_add(extra_token_a),
+                token_b_amount.saturating_add(extra_token_b),
+            ),
+        ) {
+            prop_assert!(larger_lp_amount >= lp_amount);
+        }
+    }
+
+    #[test]
+    fn withdrawal_is_monotonic_in_lp_burned(
+        (lp_amount, total_liquidity) in lp_share(),
+        extra_lp in any::<u64>(),
+        reserve_a in any::<u64>(),
+        reserve_b in any::<u64>(),
+    ) {
+        let larger_lp_amount = lp_amount.saturating_add(extra_lp).min(total_liquidity);
+        let withdraw = |lp_amount| {
+            calculate_withdrawal_amounts(lp_amount, reserve_a, reserve_b, total_liquidity)
+        };
+        if let (Ok((withdrawn_a, withdrawn_b)), Ok((larger_a, larger_b))) =
+            (withdraw(lp_amount), withdraw(larger_lp_amount))
+        {
+            prop_assert!(larger_a >= withdrawn_a);
+            prop_assert!(larger_b >= withdrawn_b);
+        }
+    }
+
+    #[test]
+    fn protocol_fee_liquidity_is_at_most_its_share_of_the_growth(
+        reserve_a in 1..=u64::MAX,
+        reserve_b in 1..=u64::MAX,
+        growth_a in any::<u64>(),
+        growth_b in any::<u64>(),
+        total_liquidity in 1..=u64::MAX,
+        protocol_fee_percentage in 0..=100u8,
+    ) {
+        // Reserves grown by the fees since k_last was recorded
+        let k_last = reserve_a as u128 * reserve_b as u128;
+        let grown_a = reserve_a.saturating_add(growth_a);
+        let grown_b = reserve_b.saturating_add(growth_b);
+        if let Ok(protocol_liquidity) = calculate_protocol_fee_liquidity(
+            grown_a,
+            grown_b,
+            k_last,
+            total_liquidity,
+            protocol_fee_percentage,
+        ) {
+            // minted * 100 * sqrt(k) <= percentage * (sqrt(k) - sqrt(k_last)) * (total + minted)
+            let root_k = calculate_sqrt(grown_a as u128 * grown_b as u128) as u128;
+            let root_k_last = calculate_sqrt(k_last) as u128;
+            let new_total = total_liquidity as u128 + protocol_liquidity as u128;
+            prop_assert!(
+                wide_mul(protocol_liquidity as u128 * 100, root_k)
+                    <= wide_mul(
+                        protocol_fee_percentage as u128 * (root_k - root_k_last),
+                        new_total
+                    )
+            );
+        }
+    }
+
+    #[test]
+    fn protocol_fee_liquidity_needs_growth(
+        reserve_a in any::<u64>(),
+        reserve_b in any::<u64>(),
+        total_liquidity in any::<u64>(),
+        protocol_fee_percentage in 0..=100u8,
+    ) {
+        // No fee earned since k_last, nothing to mint
+        let k_last = (reserve_a as u128 * reserve_b as u128).max(1);
+        prop_assert_eq!(
+            calculate_protocol_fee_liquidity(
+                reserve_a,
+                reserve_b,
+                k_last,
+                total_liquidity,
+                protocol_fee_percentage,
+            ),
+            Ok(0)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Wide and fixed point arithmetic checked against its definitions: exact products, divisions
+//! that land on the floor or ceiling of the exact quotient
+
+use dex_math::{mul_div, mul_div_u64, Q64x64, Rounding, U256};
+use proptest::prelude::*;
+
+/// Panics unless `quotient` is `numerator / divisor` in the given rounding
+fn assert_quotient(numerator: U256, divisor: u128, quotient: u128, rounding: Rounding) {
+    let product = U256::full_mul(quotient, divisor);
+    match rounding {
+        Rounding::Down => {
+            assert!(product <= numerator, "{quotient} is above the quotient");
+            assert!(
+                product.checked_add(U256::from(divisor)).unwrap() > numerator,
+                "{quotient} is below the floor quotient"
+            );
+        }
+        Rounding::Up => {
+            assert!(product >= numerator, "{quotient} is below the quotient");
+            if quotient > 0 {
+                let lower = U256::full_mul(quotient - 1, divisor);
+                assert!(
+                    lower < numerator,
+                    "{quotient} is above the ceiling quotient"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn full_products_at_the_ends_of_the_range() {
+    assert_eq!(U256::full_mul(0, u128::MAX), U256::ZERO);
+    assert_eq!(U256::full_mul(1, u128::MAX), U256::new(0, u128::MAX));
+    assert_eq!(U256::full_mul(1 << 64, 1 << 64), U256::new(1, 0));
+    // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+    assert_eq!(
+        U256::full_mul(u128::MAX, u128::MAX),
+        U256::new(u128::MAX - 1, 1)
+    );
+    assert_eq!(U256::MAX.checked_add(U256::ONE), None);
+    assert_eq!(U256::ZERO.checked_sub(U256::ONE), None);
+    assert_eq!(
+        U256::new(1, 0).checked_sub(U256::ONE),
+        Some(U256::new(0, u128::MAX))
+    );
+}
+
+#[test]
+fn division_of_the_largest_products() {
+    let product = U256::full_mul(u128::MAX, u128::MAX);
+    assert_eq!(
+        mul_div(u128::MAX, u128::MAX, u128::MAX, Rounding::Down),
+        Some(u128::MAX)
+    );
+    assert_eq!(
+        mul_div(u128::MAX, u128::MAX, u128::MAX, Rounding::Up),
+        Some(u128::MAX)
+    );
+    assert_eq!(
+        mul_div(u128::MAX, u128::MAX, u128::MAX - 1, Rounding::Down),
+        None
+    );
+    assert_eq!(mul_div(u128::MAX, u128::MAX, 0, Rounding::Down), None);
+
+    // A divisor with its top bit set exercises the carry out of the shifted remainder
+    let divisor = (1 << 127) + 12345;
+    let (quotient, remainder) = product.checked_div_rem(divisor).unwrap();
+    assert_eq!(quotient.high(), 1);
+    assert!(remainder < divisor);
+    let low_part = U256::full_mul(quotient.low(), divisor);
+    let high_part = U256::new(divisor, 0);
+    assert_eq!(
+        low_part
+            .checked_add(high_part)
+            .and_then(|sum| sum.checked_add(U256::from(remainder))),
+        Some(product)
+    );
+}
+
+#[test]
+fn token_amounts_round_in_the_requested_direction() {
+    assert_eq!(mul_div_u64(10, 1, 3, Rounding::Down), Some(3));
+    assert_eq!(mul_div_u64(10, 1, 3, Rounding::Up), Some(4));
+    assert_eq!(mul_div_u64(9, 1, 3, Rounding::Up), Some(3));
+    assert_eq!(
+        mul_div_u64(u64::MAX, u64::MAX, u64::MAX, Rounding::Up),
+        Some(u64::MAX)
+    );
+    assert_eq!(mul_div_u64(u64::MAX, 2, 1, Rounding::Down), None);
+}
+
+#[test]
+fn fixed_point_conversions() {
+    assert_eq!(Q64x64::from_int(1), Q64x64::ONE);
+    assert_eq!(Q64x64::from_ratio(1, 0, Rounding::Down), None);
+    assert_eq!(Q64x64::from_ratio(1 << 64, 1, Rounding::Down), None);
+
+    let third = Q64x64::from_ratio(1, 3, Rounding::Down).unwrap();
+    let third_up = Q64x64::from_ratio(1, 3, Rounding::Up).unwrap();
+    assert_eq!(third_up.to_bits(), third.to_bits() + 1);
+    assert_eq!(third.to_int(Rounding::Down), Some(0));
+    assert_eq!(third.to_int(Rounding::Up), Some(1));
+    assert_eq!(Q64x64::from_int(7).to_int(Rounding::Up), Some(7));
+    assert_eq!(Q64x64::MAX.to_int(Rounding::Down), Some(u64::MAX));
+    assert_eq!(Q64x64::MAX.to_int(Rounding::Up), None);
+
+    // 1/3 rounded down, times 3, falls just short of one; rounded up it is just above one
+    assert_eq!(third.mul_int(3, Rounding::Down), Some(0));
+    assert_eq!(third.mul_int(3, Rounding::Up), Some(1));
+    assert_eq!(third_up.mul_int(3, Rounding::Down), Some(1));
+    assert_eq!(third_up.mul_int(3, Rounding::Up), Some(2));
+    assert_eq!(
+        third.checked_mul(Q64x64::from_int(3), Rounding::Down),
+        Q64x64::ONE.checked_sub(Q64x64::from_bits(1))
+    );
+    assert_eq!(
+        Q64x64::ONE.checked_div(Q64x64::from_int(3), Rounding::Up),
+        Some(third_up)
+    );
+    assert_eq!(
+        Q64x64::MAX.checked_mul(Q64x64::from_int(2), Rounding::Down),
+        None
+    );
+    assert_eq!(Q64x64::ONE.checked_div(Q64x64::ZERO, Rounding::Down), None);
+}
+
+proptest! {
+    #[test]
+    fn full_product_matches_u128_when_it_fits(a in any::<u64>(), b in any::<u64>()) {
+        prop_assert_eq!(U256::full_mul(a as u128, b as u128), U256::from(a as u128 * b as u128));
+    }
+
+    #[test]
+    fn full_product_commutes_and_distributes(a in any::<u128>(), b in any::<u128>(), c in any::<u64>()) {
+        prop_assert_eq!(U256::full_mul(a, b), U256::full_mul(b, a));
+        // a * (b - c) + a * c = a * b
+        let b_less_c = b.saturating_sub(c as u128);
+        let sum = U256::full_mul(a, b_less_c).checked_add(U256::full_mul(a, b - b_less_c));
+        prop_assert_eq!(sum, Some(U256::full_mul(a, b)));
+    }
+
+    #[test]
+    fn division_remainder_reconstructs_the_dividend(
+        high in any::<u128>(),
+        low in any::<u128>(),
+        divisor in 1..=u128::MAX,
+    ) {
+        let dividend = U256::new(high, low);
+        let (quotient, remainder) = dividend.checked_div_rem(divisor).unwrap();
+        prop_assert!(remainder < divisor);
+        // quotient * divisor + remainder, with the quotient split in its halves
+        let high_part = U256::full_mul(quotient.high(), divisor);
+        prop_assert_eq!(high_part.high(), 0);
+        let rebuilt = U256::full_mul(quotient.low(), divisor)
+            .checked_add(U256::new(high_part.low(), 0))
+            .and_then(|sum| sum.checked_add(U256::from(remainder)));
+        prop_assert_eq!(rebuilt, Some(dividend));
+    }
+
+    #[test]
+    fn mul_div_lands_on_the_floor_or_ceiling(
+        a in any::<u128>(),
+        b in any::<u128>(),
+        denominator in 1..=u128::MAX,
+    ) {
+        let numerator = U256::full_mul(a, b);
+        for rounding in [Rounding::Down, Rounding::Up] {
+            if let Some(quotient) = mul_div(a, b, denominator, rounding) {
+                assert_quotient(numerator, denominator, quotient, rounding);
+            }
+        }
+        // Both roundings agree on exact quotients and differ by one otherwise
+        if let (Some(down), Some(up)) = (
+            mul_div(a, b, denominator, Rounding::Down),
+            mul_div(a, b, denominator, Rounding::Up),
+        ) {
+            let exact = numerator.checked_div_rem(denominator).unwrap().1 == 0;
+            prop_assert_eq!(up - down, if exact { 0 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn mul_div_is_defined_on_every_u64_amount(
+        a in any::<u64>(),
+        b in any::<u64>(),
+        denominator in 1..=u64::MAX,
+    ) {
+        // The quotient of u64 amounts always fits a u128
+        let down = mul_div(a as u128, b as u128, denominator as u128, Rounding::Down).unwrap();
+        prop_assert_eq!(down, a as u128 * b as u128 / denominator as u128);
+        let up = mul_div(a as u128, b as u128, denominator as u128, Rounding::Up).unwrap();
+        prop_assert_eq!(up, (a as u128 * b as u128).div_ceil(denominator as u128));
+    }
+}
@@ -0,0 +1,64 @@
+//! Edge cases of the integer square root, checked against the floor root definition
+
+use dex_math::calculate_sqrt;
+
+/// Panics unless `root` is the floor root of `value`: root^2 <= value < (root + 1)^2
+fn assert_floor_root(value: u128, root: u64) {
+    let root = root as u128;
+    assert!(root * root <= value, "sqrt({value}) = {root} is too large");
+    // (root + 1)^2 above u128::MAX is above every value
+    if let Some(next_square) = (root + 1).checked_mul(root + 1) {
+        assert!(next_square > value, "sqrt({value}) = {root} is too small");
+    }
+}
+
+#[test]
+fn small_values_are_exact() {
+    for value in 0..1 << 20 {
+        assert_floor_root(value, calculate_sqrt(value));
+    }
+}
+
+#[test]
+fn squares_and_their_neighbors() {
+    // Roots near every power of two and at the ends of the u64 range
+    let roots = (0..64)
+        .flat_map(|shift| {
+            let power = 1u64 << shift;
+            [power - 1, power, power + 1]
+        })
+        .chain([u64::MAX - 1, u64::MAX]);
+
+    for root in roots {
+        let square = root as u128 * root as u128;
+        assert_eq!(calculate_sqrt(square), root);
+        if square > 0 {
+            assert_eq!(calculate_sqrt(square - 1), root - 1);
+        }
+        if let Some(next) = square.checked_add(1) {
+            assert_floor_root(next, calculate_sqrt(next));
+        }
+    }
+}
+
+#[test]
+fn powers_of_two_and_their_neighbors() {
+    for shift in 0..128 {
+        let power = 1u128 << shift;
+        for value in [power - 1, power, power | (power - 1)] {
+            assert_floor_root(value, calculate_sqrt(value));
+        }
+    }
+}
+
+#[test]
+fn largest_values() {
+    assert_eq!(calculate_sqrt(u128::MAX), u64::MAX);
+    assert_eq!(calculate_sqrt(u128::MAX - 1), u64::MAX);
+
+    // The largest product of two u64 amounts is (2^64 - 1)^2, whose root the old binary search
+    // could not reach
+    let max_product = u64::MAX as u128 * u64::MAX as u128;
+    assert_eq!(calculate_sqrt(max_product), u64::MAX);
+    assert_eq!(calculate_sqrt(max_product - 1), u64::MAX - 1);
+}
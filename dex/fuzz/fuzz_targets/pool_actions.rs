@@ -0,0 +1,29 @@
+//! Random sequences of deposits, withdrawals, swaps and fee collections on one pool
+//!
+//! After every step, accepted or not, the vaults must hold exactly the liquidity plus the
+//! uncollected protocol fees, the LP supply must match the pool's total liquidity and no token
+//! may be created or lost
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dex_fuzz::{Action, PoolModel, Setup};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    setup: Setup,
+    actions: Vec<Action>,
+}
+
+fuzz_target!(|input: Input| {
+    let Some(mut model) = PoolModel::create(&input.setup) else {
+        return;
+    };
+    model.check_invariants();
+
+    for action in &input.actions {
+        model.apply(action);
+        model.check_invariants();
+    }
+});
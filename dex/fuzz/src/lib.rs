@@ -0,0 +1,462 @@
+//! State model of one dex pool for the fuzz targets
+//!
+//! `PoolModel` replays the program's deposit, withdraw, swap and collect handlers on a
+//! `LiquidityPool` and plain token balances, with the same dex-math calls in the same order. A
+//! step the program would reject (math error, missing balance, slippage) leaves the model
+//! unchanged, like a failed transaction.
+//!
+//! Token-2022 transfer fees and dynamic fees are out of scope: the model uses plain mints and the
+//! pool's static fee.
+
+use arbitrary::Arbitrary;
+use dex::LiquidityPool;
+use dex_math::{
+    calculate_fee_breakdown, calculate_initial_liquidity, calculate_input_amount,
+    calculate_input_amount_before_fee, calculate_output_amount, calculate_proportional_liquidity,
+    calculate_withdrawal_amounts,
+};
+
+/// Number of users trading against the pool
+pub const USERS: usize = 3;
+
+/// Token balances of a user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Balances {
+    pub token_a: u64,
+    pub token_b: u64,
+    pub lp_token: u64,
+}
+
+impl Balances {
+    pub fn token(&mut self, is_token_a: bool) -> &mut u64 {
+        if is_token_a {
+            &mut self.token_a
+        } else {
+            &mut self.token_b
+        }
+    }
+}
+
+/// Parameters of the pool and of its users, as picked by the fuzzer
+#[derive(Arbitrary, Clone, Debug)]
+pub struct Setup {
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub protocol_fee_percentage: u8,
+    pub balances: [(u64, u64); USERS],
+}
+
+/// One instruction sent to the pool, `user` is taken modulo USERS
+/// Amounts the user doesn't hold are clamped to their balance, so that the fuzzer reaches full
+/// exits and large trades without having to guess exact balances
+#[derive(Arbitrary, Clone, Debug)]
+pub enum Action {
+    Deposit {
+        user: u8,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    },
+    Withdraw {
+        user: u8,
+        lp_amount: u64,
+    },
+    Swap {
+        user: u8,
+        source_is_a: bool,
+        input_amount: u64,
+        minimum_output_amount: u64,
+    },
+    SwapExactOutput {
+        user: u8,
+        source_is_a: bool,
+        output_amount: u64,
+        maximum_input_amount: u64,
+    },
+    CollectFees,
+}
+
+/// A pool, its vaults and LP mint, its users and the fee collector
+#[derive(Clone)]
+pub struct PoolModel {
+    pub pool: LiquidityPool,
+    pub vault_a: u64,
+    pub vault_b: u64,
+    // Supply of the LP mint
+    pub lp_supply: u64,
+    pub users: [Balances; USERS],
+    pub fee_collector: Balances,
+    // What the pool owes its liquidity providers, kept apart from the vaults to check them: the
+    // ledger only ever moves with successful steps, so it over- or underflowing is a finding
+    pub liquidity_a: u64,
+    pub liquidity_b: u64,
+    // Supply of token A and token B, all of it minted to the users at creation
+    pub supply_a: u64,
+    pub supply_b: u64,
+}
+
+impl PoolModel {
+    /// Creates the pool with a fee the program accepts, None if the setup can't be one
+    pub fn create(setup: &Setup) -> Option<Self> {
+        // Same rules as register_fee_tier and the DEX protocol share
+        if setup.fee_numerator == 0
+            || setup.fee_numerator >= setup.fee_denominator
+            || setup.protocol_fee_percentage > 100
+        {
+            return None;
+        }
+
+        let mut pool: LiquidityPool = bytemuck::Zeroable::zeroed();
+        pool.fee_numerator = setup.fee_numerator;
+        pool.fee_denominator = setup.fee_denominator;
+        pool.protocol_fee_percentage = setup.protocol_fee_percentage;
+        pool.version = LiquidityPool::VERSION;
+
+        // A mint's supply fits in a u64
+        let mut users = [Balances::default(); USERS];
+        let (mut supply_a, mut supply_b) = (0, 0);
+        for (user, &(token_a, token_b)) in users.iter_mut().zip(&setup.balances) {
+            user.token_a = token_a.min(u64::MAX / USERS as u64);
+            user.token_b = token_b.min(u64::MAX / USERS as u64);
+            supply_a += user.token_a;
+            supply_b += user.token_b;
+        }
+
+        Some(Self {
+            pool,
+            vault_a: 0,
+            vault_b: 0,
+            lp_supply: 0,
+            users,
+            fee_collector: Balances::default(),
+            liquidity_a: 0,
+            liquidity_b: 0,
+            supply_a,
+            supply_b,
+        })
+    }
+
+    /// Applies `action`, returns false if the program would reject it
+    pub fn apply(&mut self, action: &Action) -> bool {
+        match *action {
+            Action::Deposit {
+                user,
+                token_a_amount,
+                token_b_amount,
+            } => {
+                let user = user_index(user);
+                let balances = self.users[user];
+                self.deposit(
+                    user,
+                    token_a_amount.min(balances.token_a),
+                    token_b_amount.min(balances.token_b),
+                )
+                .is_some()
+            }
+            Action::Withdraw { user, lp_amount } => {
+                let user = user_index(user);
+                let lp_amount = lp_amount.min(self.users[user].lp_token);
+                self.withdraw(user, lp_amount).is_some()
+            }
+            Action::Swap {
+                user,
+                source_is_a,
+                input_amount,
+                minimum_output_amount,
+            } => {
+                let user = user_index(user);
+                let input_amount = input_amount.min(*self.users[user].token(source_is_a));
+                self.swap(user, source_is_a, input_amount, minimum_output_amount)
+                    .is_some()
+            }
+            Action::SwapExactOutput {
+                user,
+                source_is_a,
+                output_amount,
+                maximum_input_amount,
+            } => self
+                .swap_exact_output(
+                    user_index(user),
+                    source_is_a,
+                    output_amount,
+                    maximum_input_amount,
+                )
+                .is_some(),
+            Action::CollectFees => self.collect_fees().is_some(),
+        }
+    }
+
+    /// perform_liquidity_deposit: returns the LP tokens minted
+    pub fn deposit(
+        &mut self,
+        user: usize,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    ) -> Option<u64> {
+        let mut next = self.clone();
+
+        let lp_tokens = if next.pool.total_liquidity == 0 {
+            calculate_initial_liquidity(token_a_amount, token_b_amount).ok()?
+        } else {
+            let (reserve_a, reserve_b) = next.pool.lp_reserves(next.vault_a, next.vault_b)?;
+            calculate_proportional_liquidity(
+                token_a_amount,
+                token_b_amount,
+                reserve_a,
+                reserve_b,
+                next.pool.total_liquidity,
+            )
+            .ok()?
+        };
+
+        let balances = &mut next.users[user];
+        balances.token_a = balances.token_a.checked_sub(token_a_amount)?;
+        balances.token_b = balances.token_b.checked_sub(token_b_amount)?;
+        balances.lp_token = balances.lp_token.checked_add(lp_tokens)?;
+        next.vault_a = next.vault_a.checked_add(token_a_amount)?;
+        next.vault_b = next.vault_b.checked_add(token_b_amount)?;
+        next.lp_supply = next.lp_supply.checked_add(lp_tokens)?;
+        next.pool.total_liquidity = next.pool.total_liquidity.checked_add(lp_tokens)?;
+
+        next.liquidity_a += token_a_amount;
+        next.liquidity_b += token_b_amount;
+
+        *self = next;
+        Some(lp_tokens)
+    }
+
+    /// perform_liquidity_withdrawal: returns the token A and token B amounts paid out
+    pub fn withdraw(&mut self, user: usize, lp_amount: u64) -> Option<(u64, u64)> {
+        let mut next = self.clone();
+
+        let (reserve_a, reserve_b) = next.pool.lp_reserves(next.vault_a, next.vault_b)?;
+        if next.users[user].lp_token < lp_amount || next.pool.total_liquidity < lp_amount {
+            return None;
+        }
+        let (token_a_amount, token_b_amount) = calculate_withdrawal_amounts(
+            lp_amount,
+            reserve_a,
+            reserve_b,
+            next.pool.total_liquidity,
+        )
+        .ok()?;
+
+        let balances = &mut next.users[user];
+        balances.lp_token -= lp_amount;
+        balances.token_a = balances.token_a.checked_add(token_a_amount)?;
+        balances.token_b = balances.token_b.checked_add(token_b_amount)?;
+        next.lp_supply -= lp_amount;
+        next.vault_a = next.vault_a.checked_sub(token_a_amount)?;
+        next.vault_b = next.vault_b.checked_sub(token_b_amount)?;
+        next.pool.total_liquidity -= lp_amount;
+
+        next.liquidity_a -= token_a_amount;
+        next.liquidity_b -= token_b_amount;
+
+        *self = next;
+        Some((token_a_amount, token_b_amount))
+    }
+
+    /// swap_tokens: returns the output amount
+    pub fn swap(
+        &mut self,
+        user: usize,
+        source_is_a: bool,
+        input_amount: u64,
+        minimum_output_amount: u64,
+    ) -> Option<u64> {
+        let mut next = self.clone();
+        let (source_reserve, destination_reserve) = next.reserves(source_is_a)?;
+
+        let (total_fee, protocol_fee) = calculate_fee_breakdown(
+            input_amount,
+            next.pool.fee_numerator,
+            next.pool.fee_denominator,
+            next.pool.protocol_fee_percentage,
+        )
+        .ok()?;
+        let input_amount_with_fee = input_amount.checked_sub(total_fee)?;
+        let output_amount = calculate_output_amount(
+            input_amount_with_fee,
+            source_reserve,
+            destination_reserve,
+            0,
+            1,
+        )
+        .ok()?;
+        if output_amount < minimum_output_amount {
+            return None;
+        }
+
+        next.transfer_swap(user, source_is_a, input_amount, protocol_fee, output_amount)?;
+
+        *self = next;
+        Some(output_amount)
+    }
+
+    /// swap_tokens_exact_output: returns the input amount
+    pub fn swap_exact_output(
+        &mut self,
+        user: usize,
+        source_is_a: bool,
+        output_amount: u64,
+        maximum_input_amount: u64,
+    ) -> Option<u64> {
+        let mut next = self.clone();
+        let (source_reserve, destination_reserve) = next.reserves(source_is_a)?;
+
+        let input_amount_with_fee =
+            calculate_input_amount(output_amount, source_reserve, destination_reserve).ok()?;
+        let input_amount = calculate_input_amount_before_fee(
+            input_amount_with_fee,
+            next.pool.fee_numerator,
+            next.pool.fee_denominator,
+        )
+        .ok()?;
+        if input_amount > maximum_input_amount {
+            return None;
+        }
+        let (_, protocol_fee) = calculate_fee_breakdown(
+            input_amount,
+            next.pool.fee_numerator,
+            next.pool.fee_denominator,
+            next.pool.protocol_fee_percentage,
+        )
+        .ok()?;
+
+        next.transfer_swap(user, source_is_a, input_amount, protocol_fee, output_amount)?;
+
+        *self = next;
+        Some(input_amount)
+    }
+
+    /// collect_protocol_fees: returns the token A and token B amounts collected
+    pub fn collect_fees(&mut self) -> Option<(u64, u64)> {
+        let mut next = self.clone();
+
+        let token_a_fee_amount = next.pool.protocol_fees_token_a;
+        let token_b_fee_amount = next.pool.protocol_fees_token_b;
+        next.pool.protocol_fees_token_a = 0;
+        next.pool.protocol_fees_token_b = 0;
+
+        next.vault_a = next.vault_a.checked_sub(token_a_fee_amount)?;
+        next.vault_b = next.vault_b.checked_sub(token_b_fee_amount)?;
+        next.fee_collector.token_a = next.fee_collector.token_a.checked_add(token_a_fee_amount)?;
+        next.fee_collector.token_b = next.fee_collector.token_b.checked_add(token_b_fee_amount)?;
+
+        *self = next;
+        Some((token_a_fee_amount, token_b_fee_amount))
+    }
+
+    /// Panics unless the vaults, the LP supply and the token supplies all add up
+    pub fn check_invariants(&self) {
+        // The vaults hold the liquidity and the uncollected protocol fees, nothing else
+        assert_eq!(
+            self.vault_a,
+            self.liquidity_a + self.pool.protocol_fees_token_a,
+            "token A vault doesn't match its reserve and protocol fees"
+        );
+        assert_eq!(
+            self.vault_b,
+            self.liquidity_b + self.pool.protocol_fees_token_b,
+            "token B vault doesn't match its reserve and protocol fees"
+        );
+        assert_eq!(
+            self.pool.lp_reserves(self.vault_a, self.vault_b),
+            Some((self.liquidity_a, self.liquidity_b))
+        );
+
+        // Every LP token in circulation is accounted for by the pool
+        assert_eq!(self.lp_supply, self.pool.total_liquidity);
+        assert_eq!(
+            self.users.iter().map(|user| user.lp_token).sum::<u64>(),
+            self.lp_supply
+        );
+
+        // Tokens only move between the users, the vaults and the fee collector
+        let held_a = self
+            .users
+            .iter()
+            .map(|user| user.token_a as u128)
+            .sum::<u128>()
+            + self.vault_a as u128
+            + self.fee_collector.token_a as u128;
+        let held_b = self
+            .users
+            .iter()
+            .map(|user| user.token_b as u128)
+            .sum::<u128>()
+            + self.vault_b as u128
+            + self.fee_collector.token_b as u128;
+        assert_eq!(
+            held_a, self.supply_a as u128,
+            "token A created or destroyed"
+        );
+        assert_eq!(
+            held_b, self.supply_b as u128,
+            "token B created or destroyed"
+        );
+    }
+
+    /// Liquidity reserves as (source, destination), which the program prices swaps on: the vault
+    /// balances without the uncollected protocol fees
+    fn reserves(&self, source_is_a: bool) -> Option<(u64, u64)> {
+        let (reserve_a, reserve_b) = self.pool.lp_reserves(self.vault_a, self.vault_b)?;
+        if source_is_a {
+            Some((reserve_a, reserve_b))
+        } else {
+            Some((reserve_b, reserve_a))
+        }
+    }
+
+    /// Accrues the protocol fee and moves the tokens of a priced swap
+    fn transfer_swap(
+        &mut self,
+        user: usize,
+        source_is_a: bool,
+        input_amount: u64,
+        protocol_fee: u64,
+        output_amount: u64,
+    ) -> Option<()> {
+        let protocol_fees = if source_is_a {
+            &mut self.pool.protocol_fees_token_a
+        } else {
+            &mut self.pool.protocol_fees_token_b
+        };
+        *protocol_fees = protocol_fees.checked_add(protocol_fee)?;
+
+        let balances = &mut self.users[user];
+        let source_balance = balances.token(source_is_a);
+        *source_balance = source_balance.checked_sub(input_amount)?;
+        let destination_balance = balances.token(!source_is_a);
+        *destination_balance = destination_balance.checked_add(output_amount)?;
+
+        let (source_vault, source_liquidity, destination_vault, destination_liquidity) =
+            if source_is_a {
+                (
+                    &mut self.vault_a,
+                    &mut self.liquidity_a,
+                    &mut self.vault_b,
+                    &mut self.liquidity_b,
+                )
+            } else {
+                (
+                    &mut self.vault_b,
+                    &mut self.liquidity_b,
+                    &mut self.vault_a,
+                    &mut self.liquidity_a,
+                )
+            };
+        *source_vault = source_vault.checked_add(input_amount)?;
+        *destination_vault = destination_vault.checked_sub(output_amount)?;
+
+        // The liquidity providers keep the input minus the protocol share of the fee
+        *source_liquidity += input_amount - protocol_fee;
+        *destination_liquidity -= output_amount;
+
+        Some(())
+    }
+}
+
+fn user_index(user: u8) -> usize {
+    user as usize % USERS
+}
@@ -0,0 +1 @@
+//! Tests of the dex program, run in-process with `solana-program-test`, see `tests/common`
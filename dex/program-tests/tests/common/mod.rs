@@ -0,0 +1,502 @@
+//! In-process harness for the program tests
+//!
+//! Under `cargo test` the program runs natively inside `solana-program-test` through its Anchor
+//! entrypoint, so the tests need neither the SBF toolchain nor a validator. With `SBF_OUT_DIR`
+//! set they load `dex.so` from there instead. Token-2022 and the associated token program are
+//! the builds bundled with `solana-program-test`.
+//!
+//! The tests build the program with the `test-admin` feature, whose `ADMIN_PUBKEY` is the test
+//! admin below, so that every bank starts with a real `initialize` transaction. This crate has
+//! its own workspace so that the feature never reaches the program workspace.
+//!
+//! `TestDex::start_sbf` always runs the program built by
+//! `cargo build-sbf --manifest-path programs/dex/Cargo.toml --features test-admin
+//! --sbf-out-dir target/test-deploy`, for measurements that need the real VM such as compute
+//! units. That build goes next to, never into, the `target/deploy` that `anchor deploy` ships,
+//! and declares its own program id.
+
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::{entrypoint::ProgramResult, program_pack::Pack, system_instruction},
+};
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    token_2022::{
+        spl_token_2022::{
+            self,
+            extension::StateWithExtensions,
+            state::{Account as TokenAccount, Mint},
+        },
+        ID as TOKEN_2022_PROGRAM_ID,
+    },
+};
+use dex::{DexError, DexState, LiquidityPool, PoolRegistryPage};
+use dex_client::{
+    accounts::{deserialize_dex_state, deserialize_liquidity_pool, deserialize_pool_registry_page},
+    instructions,
+    pda::{
+        find_dex_state_address, find_liquidity_pool_address, find_pool_registry_page_address,
+        get_associated_token_address,
+    },
+};
+use solana_program_test::{
+    find_file, processor, BanksClientError, ProgramTest, ProgramTestContext,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{Instruction, InstructionError},
+    rent::Rent,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+/// Fee of the DEX and of its first fee tier, as in the TypeScript tests
+pub const FEE_NUMERATOR: u64 = 10;
+pub const FEE_DENOMINATOR: u64 = 1000;
+pub const PROTOCOL_FEE_PERCENTAGE: u8 = 30;
+pub const FEE_TIER_INDEX: u16 = 0;
+
+/// Seed of the test admin, the `ADMIN_PUBKEY` of the `test-admin` feature
+const TEST_ADMIN_SEED: [u8; 32] = *b"dex test admin, never deploy it!";
+
+/// Lamports given to every test user, enough for their token accounts and pools
+const USER_LAMPORTS: u64 = 10_000_000_000;
+
+/// Token-2022 pool tests run a few CPIs per instruction, more than the default budget
+const COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Runs the program's Anchor entrypoint, which wants its accounts to live as long as their data
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    dex::entry(program_id, accounts, data)
+}
+
+/// A pool created by the harness, with its mints
+pub struct TestPool {
+    pub address: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub lp_token_mint: Pubkey,
+}
+
+pub struct TestDex {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub dex_state: Pubkey,
+    pub fee_collector: Keypair,
+    // Compute units consumed by the last transaction processed successfully
+    pub last_compute_units: u64,
+}
+
+impl TestDex {
+    /// Starts a bank with the DEX initialized by the test admin and fee tier 0 registered
+    /// The program is `dex.so` when `SBF_OUT_DIR` is set, and runs natively otherwise
+    pub async fn start() -> Self {
+        let program_test = ProgramTest::new("dex", dex::ID, processor!(process_instruction));
+
+        Self::start_with(program_test).await
+    }
+
+    /// Same as `start`, always running `dex.so` from `SBF_OUT_DIR`, which defaults to
+    /// `target/test-deploy`, panics if there is none
+    pub async fn start_sbf() -> Self {
+        if std::env::var_os("SBF_OUT_DIR").is_none() && std::env::var_os("BPF_OUT_DIR").is_none() {
+            std::env::set_var(
+                "SBF_OUT_DIR",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/../target/test-deploy"),
+            );
+        }
+        assert!(
+            find_file("dex.so").is_some(),
+            "no dex.so, run `anchor run compute-units` or build it into target/test-deploy first"
+        );
+        let mut program_test = ProgramTest::new("dex", dex::ID, None);
+        program_test.prefer_bpf(true);
+
+        Self::start_with(program_test).await
+    }
+
+    async fn start_with(mut program_test: ProgramTest) -> Self {
+        let admin = Keypair::new_from_array(TEST_ADMIN_SEED);
+        assert_eq!(
+            admin.pubkey(),
+            dex::ADMIN_PUBKEY,
+            "the program tests need the `test-admin` feature"
+        );
+        let fee_collector = Keypair::new();
+        let dex_state = find_dex_state_address(&admin.pubkey()).0;
+
+        for user in [&admin, &fee_collector] {
+            program_test.add_account(
+                user.pubkey(),
+                Account::new(USER_LAMPORTS, 0, &solana_sdk::system_program::ID),
+            );
+        }
+
+        let mut dex = Self {
+            context: program_test.start_with_context().await,
+            admin,
+            dex_state,
+            fee_collector,
+            last_compute_units: 0,
+        };
+
+        let admin = dex.admin.insecure_clone();
+        dex.process(
+            &[
+                instructions::initialize(
+                    &admin.pubkey(),
+                    FEE_NUMERATOR,
+                    FEE_DENOMINATOR,
+                    PROTOCOL_FEE_PERCENTAGE,
+                    &dex.fee_collector.pubkey(),
+                ),
+                instructions::add_fee_tier(
+                    &admin.pubkey(),
+                    &dex_state,
+                    FEE_TIER_INDEX,
+                    FEE_NUMERATOR,
+                    FEE_DENOMINATOR,
+                ),
+            ],
+            &[&admin],
+        )
+        .await
+        .unwrap();
+
+        dex
+    }
+
+    /// Sends `instructions` in one transaction paid by the bank's payer and signed by `signers`
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let mut all_instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            COMPUTE_UNIT_LIMIT,
+        )];
+        all_instructions.extend_from_slice(instructions);
+
+        // A fresh blockhash keeps identical transactions sent twice from being deduplicated
+        let blockhash = self
+            .context
+            .get_new_latest_blockhash()
+            .await
+            .map_err(BanksClientError::from)?;
+
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            &all_instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+
+        let result = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        result.result?;
+        if let Some(metadata) = result.metadata {
+            self.last_compute_units = metadata.compute_units_consumed;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a funded keypair
+    pub async fn create_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        self.process(
+            &[system_instruction::transfer(
+                &payer,
+                &user.pubkey(),
+                USER_LAMPORTS,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        user
+    }
+
+    /// Creates a Token-2022 mint with 6 decimals and no extension
+    pub async fn create_mint(&mut self, authority: &Keypair) -> Pubkey {
+        self.create_mint_with_authority(&authority.pubkey()).await
+    }
+
+    /// Creates a Token-2022 mint with 6 decimals and no extension, whose authority may be a PDA
+    pub async fn create_mint_with_authority(&mut self, authority: &Pubkey) -> Pubkey {
+        self.create_mint_of_program(authority, &TOKEN_2022_PROGRAM_ID)
+            .await
+    }
+
+    /// Creates a mint of `token_program`, Token-2022 or SPL Token, with 6 decimals and no
+    /// extension, whose authority may be a PDA
+    pub async fn create_mint_of_program(
+        &mut self,
+        authority: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        let rent = Rent::default().minimum_balance(Mint::LEN);
+
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    rent,
+                    Mint::LEN as u64,
+                    token_program,
+                ),
+                spl_token_2022::instruction::initialize_mint2(
+                    token_program,
+                    &mint.pubkey(),
+                    authority,
+                    None,
+                    6,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+
+        mint.pubkey()
+    }
+
+    /// Creates the associated token account of `owner`, which may be a PDA
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let payer = self.context.payer.pubkey();
+        self.process(
+            &[create_associated_token_account_idempotent(
+                &payer,
+                owner,
+                mint,
+                &TOKEN_2022_PROGRAM_ID,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        get_associated_token_address(owner, mint, &TOKEN_2022_PROGRAM_ID)
+    }
+
+    /// Creates the associated token account of `owner` and mints `amount` to it
+    pub async fn mint_to(
+        &mut self,
+        mint: &Pubkey,
+        authority: &Keypair,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let payer = self.context.payer.pubkey();
+        let token_account = get_associated_token_address(owner, mint, &TOKEN_2022_PROGRAM_ID);
+
+        self.process(
+            &[
+                create_associated_token_account_idempotent(
+                    &payer,
+                    owner,
+                    mint,
+                    &TOKEN_2022_PROGRAM_ID,
+                ),
+                spl_token_2022::instruction::mint_to(
+                    &TOKEN_2022_PROGRAM_ID,
+                    mint,
+                    &token_account,
+                    &authority.pubkey(),
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            ],
+            &[authority],
+        )
+        .await
+        .unwrap();
+
+        token_account
+    }
+
+    /// Creates a pool of two fresh mints on fee tier 0, `owner` being the mint authority
+    pub async fn create_pool(&mut self, owner: &Keypair) -> TestPool {
+        let token_a_mint = self.create_mint(owner).await;
+        let token_b_mint = self.create_mint(owner).await;
+        self.create_pool_with_mints(owner, token_a_mint, token_b_mint)
+            .await
+    }
+
+    /// Same as `create_pool`, on existing mints
+    pub async fn create_pool_with_mints(
+        &mut self,
+        owner: &Keypair,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+    ) -> TestPool {
+        let lp_token_mint = Keypair::new();
+        let pools_count = self.dex_state().await.pools_count;
+
+        // The vaults go first, so that the pool creation is measured on its own
+        self.process(
+            &instructions::create_pool_vaults(
+                &owner.pubkey(),
+                &token_a_mint,
+                &token_b_mint,
+                FEE_TIER_INDEX,
+                &TOKEN_2022_PROGRAM_ID,
+            ),
+            &[owner],
+        )
+        .await
+        .unwrap();
+        self.process(
+            &[instructions::create_pool(
+                &owner.pubkey(),
+                &self.dex_state,
+                pools_count,
+                &token_a_mint,
+                &token_b_mint,
+                FEE_TIER_INDEX,
+                &lp_token_mint.pubkey(),
+                &TOKEN_2022_PROGRAM_ID,
+                None,
+                None,
+            )],
+            &[owner, &lp_token_mint],
+        )
+        .await
+        .unwrap();
+
+        TestPool {
+            address: find_liquidity_pool_address(&token_a_mint, &token_b_mint, FEE_TIER_INDEX).0,
+            token_a_mint,
+            token_b_mint,
+            lp_token_mint: lp_token_mint.pubkey(),
+        }
+    }
+
+    /// Writes a rent-exempt account owned by the program holding `data`, as an earlier program
+    /// version would have left it
+    pub fn set_program_account(&mut self, address: &Pubkey, data: Vec<u8>) {
+        self.set_account(address, &dex::ID, data);
+    }
+
+    /// Writes a rent-exempt account owned by `owner` holding `data`
+    pub fn set_account(&mut self, address: &Pubkey, owner: &Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: *owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.context
+            .set_account(address, &AccountSharedData::from(account));
+    }
+
+    pub async fn dex_state(&mut self) -> DexState {
+        let account = self.account(&self.dex_state.clone()).await;
+        deserialize_dex_state(&account.data).unwrap()
+    }
+
+    pub async fn pool(&mut self, address: &Pubkey) -> LiquidityPool {
+        let account = self.account(address).await;
+        deserialize_liquidity_pool(&account.data).unwrap()
+    }
+
+    /// Registry page listing pool number `registry_index`
+    pub async fn registry_page(&mut self, registry_index: u64) -> PoolRegistryPage {
+        let address = find_pool_registry_page_address(&self.dex_state, registry_index).0;
+        let account = self.account(&address).await;
+        deserialize_pool_registry_page(&account.data).unwrap()
+    }
+
+    /// Reserves of a pool, as held by its vaults
+    pub async fn reserves(&mut self, address: &Pubkey) -> (u64, u64) {
+        let pool = self.pool(address).await;
+        (
+            self.token_balance(&pool.token_a_account).await,
+            self.token_balance(&pool.token_b_account).await,
+        )
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        let account = self.account(address).await;
+        StateWithExtensions::<TokenAccount>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    /// Balance of the associated token account of `owner` for `mint`, zero if there is none
+    pub async fn balance_of(&mut self, owner: &Pubkey, mint: &Pubkey) -> u64 {
+        let address = get_associated_token_address(owner, mint, &TOKEN_2022_PROGRAM_ID);
+        match self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+        {
+            Some(_) => self.token_balance(&address).await,
+            None => 0,
+        }
+    }
+
+    pub async fn account_exists(&mut self, address: &Pubkey) -> bool {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    pub async fn account_len(&mut self, address: &Pubkey) -> usize {
+        self.account(address).await.data.len()
+    }
+
+    pub async fn account_lamports(&mut self, address: &Pubkey) -> u64 {
+        self.account(address).await.lamports
+    }
+
+    async fn account(&mut self, address: &Pubkey) -> Account {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("account {address} not found"))
+    }
+}
+
+/// Asserts that a transaction failed with `error` raised by the program
+pub fn assert_dex_error(result: Result<(), BanksClientError>, error: DexError) {
+    match result
+        .expect_err("expected the transaction to fail")
+        .unwrap()
+    {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(code, u32::from(error), "unexpected program error")
+        }
+        other => panic!("expected {error:?}, got {other:?}"),
+    }
+}
@@ -0,0 +1,252 @@
+//! Compute units of the program's main instructions, checked against a regression budget
+//!
+//! Each instruction runs alone in its transaction on the SBF build of the program, so this needs
+//! `cargo build-sbf --features test-admin --sbf-out-dir target/test-deploy` first: the test is
+//! ignored by default and fails without `dex.so`. Units are those of the whole transaction: the compute budget instruction the
+//! harness prepends adds a constant 150.
+//!
+//! The budgets live in `compute_units.json`. An instruction fails when it consumes more than its
+//! budget plus `margin_percent`, which `DEX_CU_MARGIN_PERCENT` overrides, or when it has no
+//! budget. Running with `DEX_CU_UPDATE=1` records the measured units as the new budgets.
+//! `DEX_CU_BUDGETS` reads and records them in another file instead, to compare two builds.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use anchor_spl::token_2022::ID as TOKEN_2022_PROGRAM_ID;
+use common::*;
+use dex_client::instructions;
+use serde_json::{json, Value};
+use solana_sdk::signer::Signer;
+
+const BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compute_units.json");
+
+/// Budgets file, `compute_units.json` unless `DEX_CU_BUDGETS` names another one, such as the
+/// units recorded on another build to compare the two
+fn budgets_path() -> String {
+    std::env::var("DEX_CU_BUDGETS").unwrap_or_else(|_| BASELINE_PATH.to_string())
+}
+
+/// Runs every benchmarked instruction once, returns the compute units of each
+async fn measure(dex: &mut TestDex) -> BTreeMap<&'static str, u64> {
+    let mut compute_units = BTreeMap::new();
+    let owner = dex.create_user().await;
+
+    let pool = dex.create_pool(&owner).await;
+    compute_units.insert("create_pool", dex.last_compute_units);
+    for mint in [&pool.token_a_mint, &pool.token_b_mint] {
+        dex.mint_to(mint, &owner, &owner.pubkey(), 10_000_000).await;
+    }
+
+    for (name, amount) in [
+        ("deposit_liquidity_first", 1_000_000),
+        ("deposit_liquidity", 500_000),
+    ] {
+        let state = dex.pool(&pool.address).await;
+        dex.process(
+            &[instructions::deposit_liquidity(
+                &owner.pubkey(),
+                &pool.address,
+                &state,
+                &dex.fee_collector.pubkey(),
+                &TOKEN_2022_PROGRAM_ID,
+                &TOKEN_2022_PROGRAM_ID,
+                amount,
+                amount,
+            )],
+            &[&owner],
+        )
+        .await
+        .unwrap();
+        compute_units.insert(name, dex.last_compute_units);
+    }
+
+    // A first deposit whose product is near 2^64, where the square root takes the most steps
+    let large_pool = dex.create_pool(&owner).await;
+    for mint in [&large_pool.token_a_mint, &large_pool.token_b_mint] {
+        dex.mint_to(mint, &owner, &owner.pubkey(), 1 << 32).await;
+    }
+    let state = dex.pool(&large_pool.address).await;
+    dex.process(
+        &[instructions::deposit_liquidity(
+            &owner.pubkey(),
+            &large_pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            1 << 32,
+            1 << 32,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("deposit_liquidity_first_large", dex.last_compute_units);
+
+    let state = dex.pool(&pool.address).await;
+    dex.process(
+        &[instructions::swap(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &pool.token_a_mint,
+            &TOKEN_2022_PROGRAM_ID,
+            100_000,
+            0,
+        )
+        .unwrap()],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("swap", dex.last_compute_units);
+
+    dex.process(
+        &[instructions::swap_exact_output(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &pool.token_b_mint,
+            &TOKEN_2022_PROGRAM_ID,
+            50_000,
+            u64::MAX,
+        )
+        .unwrap()],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("swap_exact_output", dex.last_compute_units);
+
+    let state = dex.pool(&pool.address).await;
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            state.total_liquidity / 2,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("withdraw_liquidity", dex.last_compute_units);
+
+    // Both tokens have protocol fees to transfer
+    let admin = dex.admin.insecure_clone();
+    let fee_collector = dex.fee_collector.pubkey();
+    let dex_state = dex.dex_state;
+    for mint in [&pool.token_a_mint, &pool.token_b_mint] {
+        dex.mint_to(mint, &owner, &fee_collector, 0).await;
+    }
+    dex.process(
+        &[instructions::collect_fees(
+            &admin.pubkey(),
+            &dex_state,
+            &fee_collector,
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("collect_fees", dex.last_compute_units);
+
+    let state = dex.pool(&pool.address).await;
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            state.total_liquidity,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    dex.process(
+        &[instructions::close_pool(
+            &owner.pubkey(),
+            &dex_state,
+            &fee_collector,
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    compute_units.insert("close_pool", dex.last_compute_units);
+
+    compute_units
+}
+
+#[tokio::test]
+#[ignore = "needs the SBF build in target/test-deploy, then run with `--ignored`"]
+async fn instructions_stay_within_their_compute_budget() {
+    let mut dex = TestDex::start_sbf().await;
+    let measured = measure(&mut dex).await;
+
+    // A budgets file recorded for the first time starts from the margin of `compute_units.json`
+    let path = budgets_path();
+    let update = std::env::var_os("DEX_CU_UPDATE").is_some();
+    let budgets = match std::fs::read_to_string(&path) {
+        Err(_) if update => std::fs::read_to_string(BASELINE_PATH).unwrap(),
+        budgets => budgets.unwrap_or_else(|err| panic!("no budgets in {path}: {err}")),
+    };
+    let baseline: Value = serde_json::from_str(&budgets).unwrap();
+    let margin_percent = match std::env::var("DEX_CU_MARGIN_PERCENT") {
+        Ok(margin) => margin
+            .parse()
+            .expect("DEX_CU_MARGIN_PERCENT is not a number"),
+        Err(_) => baseline["margin_percent"].as_u64().unwrap(),
+    };
+
+    if update {
+        let updated = json!({
+            "margin_percent": baseline["margin_percent"],
+            "compute_units": measured,
+        });
+        std::fs::write(
+            &path,
+            serde_json::to_string_pretty(&updated).unwrap() + "\n",
+        )
+        .unwrap();
+        eprintln!("recorded compute units in {path}");
+        return;
+    }
+
+    let mut over_budget = vec![];
+    for (name, units) in &measured {
+        match baseline["compute_units"][name].as_u64() {
+            Some(budget) => {
+                let limit = budget * (100 + margin_percent) / 100;
+                eprintln!("{name}: {units} CU (budget {budget}, limit {limit})");
+                if *units > limit {
+                    over_budget.push(format!("{name} consumed {units} CU, over {limit}"));
+                }
+            }
+            None => over_budget.push(format!(
+                "{name} consumed {units} CU with no budget, record it with DEX_CU_UPDATE=1"
+            )),
+        }
+    }
+
+    assert!(
+        over_budget.is_empty(),
+        "compute budget exceeded: {}",
+        over_budget.join(", ")
+    );
+}
@@ -0,0 +1,1522 @@
+//! Behavior of the program's main flows, run in-process
+//!
+//! Ports of the scenarios of `tests/dex.ts` that don't need a validator: each test starts its
+//! own bank with the DEX initialized and fee tier 0 registered, see `common`.
+
+mod common;
+mod layouts;
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize, AccountSerialize, Discriminator};
+use anchor_spl::{
+    token::ID as SPL_TOKEN_PROGRAM_ID,
+    token_2022::{
+        spl_token_2022::{
+            extension::{BaseStateWithExtensions, StateWithExtensions},
+            state::Mint,
+        },
+        ID as TOKEN_2022_PROGRAM_ID,
+    },
+    token_interface::spl_token_metadata_interface::state::TokenMetadata,
+};
+use common::*;
+use dex::{
+    DexError, DexState, DynamicFeeParams, FeeTier, LiquidityPool, MintListing, ProtocolFeeMode,
+    BPS_DENOMINATOR, MAX_POOLS_PER_PAIR,
+};
+use dex_client::{
+    instructions,
+    pda::{
+        find_fee_tier_address, find_liquidity_pool_address, find_metaplex_metadata_address,
+        get_associated_token_address,
+    },
+    quote::{quote_deposit, quote_swap, quote_withdrawal},
+};
+use dex_math::{calculate_output_amount, calculate_proportional_liquidity};
+use solana_sdk::{rent::Rent, signature::Keypair, signer::Signer};
+
+/// Tokens minted to each user of a scenario, 10 tokens of 6 decimals
+const MINTED_AMOUNT: u64 = 10_000_000;
+
+/// Creates a pool owned by `owner`, mints MINTED_AMOUNT of both tokens to them and deposits
+/// `token_a_amount` and `token_b_amount`
+async fn create_funded_pool(
+    dex: &mut TestDex,
+    owner: &Keypair,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> TestPool {
+    let pool = dex.create_pool(owner).await;
+    dex.mint_to(&pool.token_a_mint, owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    dex.mint_to(&pool.token_b_mint, owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    deposit(dex, owner, &pool, token_a_amount, token_b_amount).await;
+
+    pool
+}
+
+async fn deposit(
+    dex: &mut TestDex,
+    owner: &Keypair,
+    pool: &TestPool,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) {
+    let state = dex.pool(&pool.address).await;
+    let fee_collector = dex.dex_state().await.fee_collector;
+    dex.process(
+        &[instructions::deposit_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &fee_collector,
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            token_a_amount,
+            token_b_amount,
+        )],
+        &[owner],
+    )
+    .await
+    .unwrap();
+}
+
+/// Swaps `input_amount` of `source_mint`, returning the amount of the other token received
+async fn swap(
+    dex: &mut TestDex,
+    owner: &Keypair,
+    pool: &TestPool,
+    source_mint: &Pubkey,
+    input_amount: u64,
+    minimum_output_amount: u64,
+) -> u64 {
+    let state = dex.pool(&pool.address).await;
+    let destination_mint = if *source_mint == pool.token_a_mint {
+        pool.token_b_mint
+    } else {
+        pool.token_a_mint
+    };
+    let balance_before = dex.balance_of(&owner.pubkey(), &destination_mint).await;
+
+    dex.process(
+        &[instructions::swap(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            source_mint,
+            &TOKEN_2022_PROGRAM_ID,
+            input_amount,
+            minimum_output_amount,
+        )
+        .unwrap()],
+        &[owner],
+    )
+    .await
+    .unwrap();
+
+    dex.balance_of(&owner.pubkey(), &destination_mint).await - balance_before
+}
+
+#[tokio::test]
+async fn initialization_with_incorrect_admin_fails() {
+    let mut dex = TestDex::start().await;
+    let attacker = dex.create_user().await;
+
+    let result = dex
+        .process(
+            &[instructions::initialize(
+                &attacker.pubkey(),
+                FEE_NUMERATOR,
+                FEE_DENOMINATOR,
+                PROTOCOL_FEE_PERCENTAGE,
+                &attacker.pubkey(),
+            )],
+            &[&attacker],
+        )
+        .await;
+
+    assert_dex_error(result, DexError::NotAdmin);
+}
+
+#[tokio::test]
+async fn initialization_runs_only_once() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    dex.create_pool(&owner).await;
+
+    // Initializing again would reset the counters the registry pages are indexed by
+    let admin = dex.admin.insecure_clone();
+    let result = dex
+        .process(
+            &[instructions::initialize(
+                &admin.pubkey(),
+                FEE_NUMERATOR,
+                FEE_DENOMINATOR,
+                PROTOCOL_FEE_PERCENTAGE,
+                &admin.pubkey(),
+            )],
+            &[&admin],
+        )
+        .await;
+    assert!(result.is_err());
+
+    let dex_state = dex.dex_state().await;
+    assert_eq!(dex_state.pools_count, 1);
+    assert_eq!(dex_state.fee_tiers_count, 1);
+    assert_eq!(dex_state.fee_collector, dex.fee_collector.pubkey());
+}
+
+#[tokio::test]
+async fn admin_sets_fees_without_resetting_the_counters() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let new_fee_collector = dex.create_user().await;
+    dex.create_pool(&owner).await;
+
+    let admin = dex.admin.insecure_clone();
+    dex.process(
+        &[instructions::set_fees(
+            &admin.pubkey(),
+            3,
+            1000,
+            50,
+            &new_fee_collector.pubkey(),
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let dex_state = dex.dex_state().await;
+    assert_eq!(
+        (dex_state.fee_numerator, dex_state.fee_denominator),
+        (3, 1000)
+    );
+    assert_eq!(dex_state.protocol_fee_percentage, 50);
+    assert_eq!(dex_state.fee_collector, new_fee_collector.pubkey());
+    assert_eq!(dex_state.pools_count, 1);
+    assert_eq!(dex_state.fee_tiers_count, 1);
+    assert_eq!(dex_state.admin, admin.pubkey());
+
+    // Same rules as at initialization
+    for (fee_numerator, fee_denominator, protocol_fee_percentage) in
+        [(0, 1000, 30), (1000, 1000, 30), (10, 1000, 101)]
+    {
+        let result = dex
+            .process(
+                &[instructions::set_fees(
+                    &admin.pubkey(),
+                    fee_numerator,
+                    fee_denominator,
+                    protocol_fee_percentage,
+                    &admin.pubkey(),
+                )],
+                &[&admin],
+            )
+            .await;
+        assert_dex_error(result, DexError::InvalidFees);
+    }
+}
+
+#[tokio::test]
+async fn only_the_admin_sets_fees() {
+    let mut dex = TestDex::start().await;
+    let attacker = dex.create_user().await;
+
+    let mut set_fees = instructions::set_fees(&dex.admin.pubkey(), 1, 1000, 0, &attacker.pubkey());
+    set_fees.accounts[0].pubkey = attacker.pubkey();
+    let result = dex.process(&[set_fees], &[&attacker]).await;
+
+    assert_dex_error(result, DexError::NotAdmin);
+    assert_eq!(
+        dex.dex_state().await.fee_collector,
+        dex.fee_collector.pubkey()
+    );
+}
+
+#[tokio::test]
+async fn registers_fee_tiers() {
+    let mut dex = TestDex::start().await;
+    let attacker = dex.create_user().await;
+
+    // Only the admin can register tiers
+    let dex_state = dex.dex_state;
+    let result = dex
+        .process(
+            &[instructions::add_fee_tier(
+                &attacker.pubkey(),
+                &dex_state,
+                1,
+                5,
+                10_000,
+            )],
+            &[&attacker],
+        )
+        .await;
+    assert_dex_error(result, DexError::NotAdmin);
+
+    // Tier 1 is a cheaper 5 bps tier
+    let admin = dex.admin.insecure_clone();
+    dex.process(
+        &[instructions::add_fee_tier(
+            &admin.pubkey(),
+            &dex_state,
+            1,
+            5,
+            10_000,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let tier_address = find_fee_tier_address(&dex_state, 1).0;
+    let tier_account = dex
+        .context
+        .banks_client
+        .get_account(tier_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let tier = FeeTier::try_deserialize(&mut &tier_account.data[..]).unwrap();
+    assert_eq!(tier.index, 1);
+    assert_eq!((tier.fee_numerator, tier.fee_denominator), (5, 10_000));
+    assert_eq!(dex.dex_state().await.fee_tiers_count, 2);
+}
+
+#[tokio::test]
+async fn fee_tiers_are_capped_at_the_pools_of_a_pair_lookup() {
+    let mut dex = TestDex::start().await;
+    let admin = dex.admin.insecure_clone();
+    let dex_state = dex.dex_state;
+    let last_tier_index = MAX_POOLS_PER_PAIR as u16 - 1;
+
+    // Skip to the last tier a pair lookup has room for
+    let mut state = dex.dex_state().await;
+    state.fee_tiers_count = last_tier_index;
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    dex.set_program_account(&dex_state, data);
+
+    dex.process(
+        &[instructions::add_fee_tier(
+            &admin.pubkey(),
+            &dex_state,
+            last_tier_index,
+            5,
+            10_000,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        dex.dex_state().await.fee_tiers_count as usize,
+        MAX_POOLS_PER_PAIR
+    );
+
+    let result = dex
+        .process(
+            &[instructions::add_fee_tier(
+                &admin.pubkey(),
+                &dex_state,
+                last_tier_index + 1,
+                5,
+                10_000,
+            )],
+            &[&admin],
+        )
+        .await;
+    assert_dex_error(result, DexError::TooManyFeeTiers);
+}
+
+#[tokio::test]
+async fn creates_a_liquidity_pool() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+
+    let pool = dex.create_pool(&owner).await;
+
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(state.creator, owner.pubkey());
+    assert_eq!(state.token_a_mint, pool.token_a_mint);
+    assert_eq!(state.token_b_mint, pool.token_b_mint);
+    assert_eq!(state.lp_token_mint, pool.lp_token_mint);
+    assert_eq!(state.total_liquidity, 0);
+    assert_eq!(
+        (state.fee_numerator, state.fee_denominator),
+        (FEE_NUMERATOR, FEE_DENOMINATOR)
+    );
+    assert_eq!(state.protocol_fee_percentage, PROTOCOL_FEE_PERCENTAGE);
+    assert_eq!(
+        (state.protocol_fees_token_a, state.protocol_fees_token_b),
+        (0, 0)
+    );
+    assert_eq!(dex.dex_state().await.pools_count, 1);
+
+    // The test mints have no metadata, so the LP token is named after their abbreviated addresses
+    let lp_mint_account = dex
+        .context
+        .banks_client
+        .get_account(pool.lp_token_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let lp_mint = StateWithExtensions::<Mint>::unpack(&lp_mint_account.data).unwrap();
+    assert_eq!(lp_mint.base.decimals, 6);
+    let metadata = lp_mint
+        .get_variable_len_extension::<TokenMetadata>()
+        .unwrap();
+    let label_a = &pool.token_a_mint.to_string()[..4];
+    let label_b = &pool.token_b_mint.to_string()[..4];
+    assert_eq!(metadata.name, format!("DEX LP {label_a}-{label_b}"));
+    assert_eq!(metadata.symbol, format!("{label_a}-{label_b}-LP"));
+    assert_eq!(
+        Option::<anchor_lang::prelude::Pubkey>::from(metadata.update_authority),
+        Some(pool.address)
+    );
+}
+
+/// Metaplex metadata account of `mint`, with the name and symbol padded like Metaplex does
+fn metaplex_metadata(mint: &Pubkey, name: &str, symbol: &str) -> Vec<u8> {
+    let mut data = vec![4];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(mint.as_ref());
+    for (value, padded_len) in [(name, 32), (symbol, 10), ("", 200)] {
+        data.extend_from_slice(&(padded_len as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data.resize(data.len() + padded_len - value.len(), 0);
+    }
+    data
+}
+
+/// Token metadata written in an LP mint
+async fn lp_token_metadata(dex: &mut TestDex, lp_token_mint: &Pubkey) -> TokenMetadata {
+    let lp_mint_account = dex
+        .context
+        .banks_client
+        .get_account(*lp_token_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let lp_mint = StateWithExtensions::<Mint>::unpack(&lp_mint_account.data).unwrap();
+    lp_mint
+        .get_variable_len_extension::<TokenMetadata>()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn names_the_lp_token_after_the_metaplex_symbols() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let token_a_mint = dex.create_mint(&owner).await;
+    let token_b_mint = dex.create_mint(&owner).await;
+    dex.set_account(
+        &find_metaplex_metadata_address(&token_a_mint).0,
+        &dex::METAPLEX_METADATA_PROGRAM_ID,
+        metaplex_metadata(&token_a_mint, "USD Coin", "USDC"),
+    );
+
+    // Token B has no metadata, so it keeps its abbreviated address
+    let pool = dex
+        .create_pool_with_mints(&owner, token_a_mint, token_b_mint)
+        .await;
+
+    let metadata = lp_token_metadata(&mut dex, &pool.lp_token_mint).await;
+    let label_b = &token_b_mint.to_string()[..4];
+    assert_eq!(metadata.name, format!("DEX LP USDC-{label_b}"));
+    assert_eq!(metadata.symbol, format!("USDC-{label_b}-LP"));
+}
+
+#[tokio::test]
+async fn metadata_accounts_not_owned_by_metaplex_are_ignored() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let token_a_mint = dex.create_mint(&owner).await;
+    let token_b_mint = dex.create_mint(&owner).await;
+    dex.set_account(
+        &find_metaplex_metadata_address(&token_a_mint).0,
+        &Pubkey::new_unique(),
+        metaplex_metadata(&token_a_mint, "USD Coin", "USDC"),
+    );
+
+    let pool = dex
+        .create_pool_with_mints(&owner, token_a_mint, token_b_mint)
+        .await;
+
+    let metadata = lp_token_metadata(&mut dex, &pool.lp_token_mint).await;
+    let label_a = &token_a_mint.to_string()[..4];
+    let label_b = &token_b_mint.to_string()[..4];
+    assert_eq!(metadata.symbol, format!("{label_a}-{label_b}-LP"));
+}
+
+#[tokio::test]
+async fn deposits_and_withdraws_liquidity() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+
+    let pool = create_funded_pool(&mut dex, &owner, 500_000, 1_000_000).await;
+
+    // The first deposit mints the geometric mean of the amounts
+    let lp_balance = dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await;
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(lp_balance, 707_106);
+    assert_eq!(state.total_liquidity, lp_balance);
+    assert_eq!(dex.reserves(&pool.address).await, (500_000, 1_000_000));
+
+    // Withdrawing half of the LP tokens returns half of the reserves
+    let lp_amount = lp_balance / 2;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let (expected_a, expected_b) =
+        quote_withdrawal(&state, reserve_a, reserve_b, lp_amount).unwrap();
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            lp_amount,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(state.total_liquidity, lp_balance - lp_amount);
+    assert_eq!(
+        dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await,
+        lp_balance - lp_amount
+    );
+    assert_eq!(
+        dex.reserves(&pool.address).await,
+        (reserve_a - expected_a, reserve_b - expected_b)
+    );
+    assert_eq!(
+        dex.balance_of(&owner.pubkey(), &pool.token_a_mint).await,
+        MINTED_AMOUNT - 500_000 + expected_a
+    );
+}
+
+#[tokio::test]
+async fn swaps_tokens_both_ways() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 700_000, 1_400_000).await;
+
+    for (source_mint, input_amount) in [(pool.token_a_mint, 50_000), (pool.token_b_mint, 30_000)] {
+        let state = dex.pool(&pool.address).await;
+        let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+        let quote =
+            quote_swap(&state, reserve_a, reserve_b, &source_mint, input_amount, 0).unwrap();
+
+        let output_amount = swap(
+            &mut dex,
+            &owner,
+            &pool,
+            &source_mint,
+            input_amount,
+            quote.output_amount * 99 / 100,
+        )
+        .await;
+        assert_eq!(output_amount, quote.output_amount);
+
+        // The pool took the whole input and paid the output
+        let source_is_a = source_mint == pool.token_a_mint;
+        let expected_reserves = if source_is_a {
+            (reserve_a + input_amount, reserve_b - output_amount)
+        } else {
+            (reserve_a - output_amount, reserve_b + input_amount)
+        };
+        assert_eq!(dex.reserves(&pool.address).await, expected_reserves);
+        assert!(
+            expected_reserves.0 as u128 * expected_reserves.1 as u128
+                >= reserve_a as u128 * reserve_b as u128
+        );
+
+        // The protocol share of the fee is set aside in the source token
+        let fees_after = dex.pool(&pool.address).await;
+        let protocol_fees_delta = if source_is_a {
+            fees_after.protocol_fees_token_a - state.protocol_fees_token_a
+        } else {
+            fees_after.protocol_fees_token_b - state.protocol_fees_token_b
+        };
+        let total_fee = input_amount * FEE_NUMERATOR / FEE_DENOMINATOR;
+        assert_eq!(
+            protocol_fees_delta,
+            total_fee * PROTOCOL_FEE_PERCENTAGE as u64 / 100
+        );
+        assert_eq!(protocol_fees_delta, quote.protocol_fee);
+    }
+}
+
+#[tokio::test]
+async fn collects_protocol_fees() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 100_000, 0).await;
+    swap(&mut dex, &owner, &pool, &pool.token_b_mint, 100_000, 0).await;
+
+    let state = dex.pool(&pool.address).await;
+    assert!(state.protocol_fees_token_a > 0 && state.protocol_fees_token_b > 0);
+
+    let admin = dex.admin.insecure_clone();
+    let fee_collector = dex.fee_collector.pubkey();
+    dex.mint_to(&pool.token_a_mint, &owner, &fee_collector, 0)
+        .await;
+    dex.mint_to(&pool.token_b_mint, &owner, &fee_collector, 0)
+        .await;
+    let dex_state = dex.dex_state;
+    dex.process(
+        &[instructions::collect_fees(
+            &admin.pubkey(),
+            &dex_state,
+            &fee_collector,
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let collected = dex.pool(&pool.address).await;
+    assert_eq!(
+        (
+            collected.protocol_fees_token_a,
+            collected.protocol_fees_token_b
+        ),
+        (0, 0)
+    );
+    assert_eq!(
+        dex.balance_of(&fee_collector, &pool.token_a_mint).await,
+        state.protocol_fees_token_a
+    );
+    assert_eq!(
+        dex.balance_of(&fee_collector, &pool.token_b_mint).await,
+        state.protocol_fees_token_b
+    );
+}
+
+#[tokio::test]
+async fn unauthorized_fee_collection_fails() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let attacker = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 100_000, 0).await;
+
+    // The attacker signs in place of the admin
+    let fee_collector = dex.fee_collector.pubkey();
+    dex.mint_to(&pool.token_a_mint, &owner, &fee_collector, 0)
+        .await;
+    dex.mint_to(&pool.token_b_mint, &owner, &fee_collector, 0)
+        .await;
+    let dex_state = dex.dex_state;
+    let state = dex.pool(&pool.address).await;
+    let result = dex
+        .process(
+            &[instructions::collect_fees(
+                &attacker.pubkey(),
+                &dex_state,
+                &fee_collector,
+                &pool.address,
+                &state,
+                &TOKEN_2022_PROGRAM_ID,
+            )],
+            &[&attacker],
+        )
+        .await;
+
+    assert_dex_error(result, DexError::NotAdmin);
+    assert_eq!(
+        dex.pool(&pool.address).await.protocol_fees_token_a,
+        state.protocol_fees_token_a
+    );
+}
+
+#[tokio::test]
+async fn withdrawals_leave_the_protocol_fees_in_the_vaults() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 100_000, 0).await;
+    swap(&mut dex, &owner, &pool, &pool.token_b_mint, 100_000, 0).await;
+
+    // Burning every LP token pays out the vaults minus the uncollected protocol fees
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let (expected_a, expected_b) =
+        quote_withdrawal(&state, reserve_a, reserve_b, state.total_liquidity).unwrap();
+    assert_eq!(
+        (expected_a, expected_b),
+        (
+            reserve_a - state.protocol_fees_token_a,
+            reserve_b - state.protocol_fees_token_b
+        )
+    );
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            state.total_liquidity,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        dex.reserves(&pool.address).await,
+        (state.protocol_fees_token_a, state.protocol_fees_token_b)
+    );
+
+    // With no liquidity left, a swap can't be paid from the protocol fees
+    let result = dex
+        .process(
+            &[instructions::swap(
+                &owner.pubkey(),
+                &pool.address,
+                &state,
+                &pool.token_a_mint,
+                &TOKEN_2022_PROGRAM_ID,
+                100_000,
+                0,
+            )
+            .unwrap()],
+            &[&owner],
+        )
+        .await;
+    assert_dex_error(result, DexError::InsufficientLiquidity);
+
+    // The fees are still there for the collector
+    let admin = dex.admin.insecure_clone();
+    let fee_collector = dex.fee_collector.pubkey();
+    dex.mint_to(&pool.token_a_mint, &owner, &fee_collector, 0)
+        .await;
+    dex.mint_to(&pool.token_b_mint, &owner, &fee_collector, 0)
+        .await;
+    let dex_state = dex.dex_state;
+    dex.process(
+        &[instructions::collect_fees(
+            &admin.pubkey(),
+            &dex_state,
+            &fee_collector,
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    assert_eq!(dex.reserves(&pool.address).await, (0, 0));
+    assert_eq!(
+        dex.balance_of(&fee_collector, &pool.token_a_mint).await,
+        state.protocol_fees_token_a
+    );
+}
+
+#[tokio::test]
+async fn deposits_and_swaps_leave_out_the_uncollected_protocol_fees() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 200_000, 0).await;
+
+    let state = dex.pool(&pool.address).await;
+    assert!(state.protocol_fees_token_a > 0);
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let lp_reserve_a = reserve_a - state.protocol_fees_token_a;
+
+    // A deposit is valued against the reserves the liquidity providers own
+    let expected_lp = calculate_proportional_liquidity(
+        100_000,
+        100_000,
+        lp_reserve_a,
+        reserve_b,
+        state.total_liquidity,
+    )
+    .unwrap();
+    assert_eq!(
+        quote_deposit(&state, reserve_a, reserve_b, 100_000, 100_000).unwrap(),
+        expected_lp
+    );
+    let lp_before = dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await;
+    deposit(&mut dex, &owner, &pool, 100_000, 100_000).await;
+    assert_eq!(
+        dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await - lp_before,
+        expected_lp
+    );
+
+    // So is a swap, whichever way
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let lp_reserve_a = reserve_a - state.protocol_fees_token_a;
+    let total_fee = (50_000 * FEE_NUMERATOR).div_ceil(FEE_DENOMINATOR);
+    let expected_output =
+        calculate_output_amount(50_000 - total_fee, reserve_b, lp_reserve_a, 0, 1).unwrap();
+    assert_eq!(
+        quote_swap(&state, reserve_a, reserve_b, &pool.token_b_mint, 50_000, 0)
+            .unwrap()
+            .output_amount,
+        expected_output
+    );
+    let output_amount = swap(&mut dex, &owner, &pool, &pool.token_b_mint, 50_000, 0).await;
+    assert_eq!(output_amount, expected_output);
+}
+
+#[tokio::test]
+async fn mints_the_protocol_fee_as_lp_tokens() {
+    let mut dex = TestDex::start().await;
+    let admin = dex.admin.insecure_clone();
+    dex.process(
+        &[instructions::set_protocol_fee_mode(
+            &admin.pubkey(),
+            ProtocolFeeMode::MintLp,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let owner = dex.create_user().await;
+    let fee_collector = dex.fee_collector.insecure_clone();
+    let pool = dex.create_pool(&owner).await;
+    let state = dex.pool(&pool.address).await;
+    assert!(state.mints_protocol_fee());
+    for mint in [&pool.token_a_mint, &pool.token_b_mint] {
+        dex.mint_to(mint, &owner, &owner.pubkey(), MINTED_AMOUNT)
+            .await;
+    }
+    // Nothing to mint to the fee collector yet, so its LP token account doesn't have to exist
+    deposit(&mut dex, &owner, &pool, 1_000_000, 1_000_000).await;
+    assert_eq!(
+        dex.pool(&pool.address).await.k_last(),
+        1_000_000 * 1_000_000
+    );
+    dex.process(
+        &[instructions::create_fee_collector_lp_token(
+            &owner.pubkey(),
+            &state,
+            &fee_collector.pubkey(),
+        )
+        .unwrap()],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+
+    // Swaps leave the whole fee in the reserves
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 100_000, 0).await;
+    swap(&mut dex, &owner, &pool, &pool.token_b_mint, 100_000, 0).await;
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(
+        (state.protocol_fees_token_a, state.protocol_fees_token_b),
+        (0, 0)
+    );
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let protocol_liquidity = state.protocol_fee_liquidity(reserve_a, reserve_b).unwrap();
+    assert!(protocol_liquidity > 0);
+
+    // The fee collector's LP tokens can't be skipped
+    let mut without_fee_collector = state;
+    without_fee_collector.protocol_fee_mode = ProtocolFeeMode::Accrued as u8;
+    let result = dex
+        .process(
+            &[instructions::deposit_liquidity(
+                &owner.pubkey(),
+                &pool.address,
+                &without_fee_collector,
+                &dex.fee_collector.pubkey(),
+                &TOKEN_2022_PROGRAM_ID,
+                &TOKEN_2022_PROGRAM_ID,
+                100_000,
+                100_000,
+            )],
+            &[&owner],
+        )
+        .await;
+    assert_dex_error(result, DexError::FeeCollectorLpTokenMissing);
+
+    // The next deposit mints them first, and is valued against the supply including them
+    let expected_lp = quote_deposit(&state, reserve_a, reserve_b, 100_000, 100_000).unwrap();
+    let owner_lp = dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await;
+    deposit(&mut dex, &owner, &pool, 100_000, 100_000).await;
+    assert_eq!(
+        dex.balance_of(&fee_collector.pubkey(), &pool.lp_token_mint)
+            .await,
+        protocol_liquidity
+    );
+    assert_eq!(
+        dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await - owner_lp,
+        expected_lp
+    );
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(
+        state.total_liquidity,
+        owner_lp + expected_lp + protocol_liquidity
+    );
+    assert_eq!(
+        state.k_last(),
+        (reserve_a + 100_000) as u128 * (reserve_b + 100_000) as u128
+    );
+
+    // The fee collector redeems its share like any liquidity provider, leaving no protocol fee
+    // to collect before the pool can close
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &fee_collector.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            protocol_liquidity,
+        )],
+        &[&fee_collector],
+    )
+    .await
+    .unwrap();
+    let protocol_a = dex
+        .balance_of(&fee_collector.pubkey(), &pool.token_a_mint)
+        .await;
+    let protocol_b = dex
+        .balance_of(&fee_collector.pubkey(), &pool.token_b_mint)
+        .await;
+    // Worth at most the share of the fees the accrued mode would have set aside, 1_000 of each
+    // token at 30%, less what the swaps moved from one token to the other
+    let accrued_share = 1_000 * PROTOCOL_FEE_PERCENTAGE as u64 / 100;
+    for amount in [protocol_a, protocol_b] {
+        assert!(amount <= accrued_share && amount > accrued_share * 9 / 10);
+    }
+
+    let state = dex.pool(&pool.address).await;
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+            state.total_liquidity,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    let dex_state = dex.dex_state;
+    dex.process(
+        &[instructions::close_pool(
+            &owner.pubkey(),
+            &dex_state,
+            &fee_collector.pubkey(),
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+            &TOKEN_2022_PROGRAM_ID,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn mints_the_protocol_fee_to_the_current_fee_collector() {
+    let mut dex = TestDex::start().await;
+    let admin = dex.admin.insecure_clone();
+    dex.process(
+        &[instructions::set_protocol_fee_mode(
+            &admin.pubkey(),
+            ProtocolFeeMode::MintLp,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, 100_000, 0).await;
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let protocol_liquidity = state.protocol_fee_liquidity(reserve_a, reserve_b).unwrap();
+    assert!(protocol_liquidity > 0);
+
+    // The pool was created under the first fee collector, the fee goes to the one set since
+    let new_fee_collector = dex.create_user().await;
+    dex.process(
+        &[instructions::set_fees(
+            &admin.pubkey(),
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_PERCENTAGE,
+            &new_fee_collector.pubkey(),
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    for fee_collector in [dex.fee_collector.pubkey(), new_fee_collector.pubkey()] {
+        dex.process(
+            &[
+                instructions::create_fee_collector_lp_token(
+                    &owner.pubkey(),
+                    &state,
+                    &fee_collector,
+                )
+                .unwrap(),
+            ],
+            &[&owner],
+        )
+        .await
+        .unwrap();
+    }
+
+    // An LP token account of anyone else is rejected
+    let result = dex
+        .process(
+            &[instructions::deposit_liquidity(
+                &owner.pubkey(),
+                &pool.address,
+                &state,
+                &dex.fee_collector.pubkey(),
+                &TOKEN_2022_PROGRAM_ID,
+                &TOKEN_2022_PROGRAM_ID,
+                100_000,
+                100_000,
+            )],
+            &[&owner],
+        )
+        .await;
+    assert_dex_error(result, DexError::InvalidFeeCollectorLpToken);
+
+    deposit(&mut dex, &owner, &pool, 100_000, 100_000).await;
+    assert_eq!(
+        dex.balance_of(&new_fee_collector.pubkey(), &pool.lp_token_mint)
+            .await,
+        protocol_liquidity
+    );
+    assert_eq!(
+        dex.balance_of(&dex.fee_collector.pubkey(), &pool.lp_token_mint)
+            .await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn swap_fees_grow_the_constant_product() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 2_000_000).await;
+
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let input_amount = 100_000;
+    swap(&mut dex, &owner, &pool, &pool.token_a_mint, input_amount, 0).await;
+    let (reserve_a_after, reserve_b_after) = dex.reserves(&pool.address).await;
+
+    // The fee stays in the pool: the product grows by the fee rate on the input's share of the
+    // new reserve
+    let product_before = reserve_a as f64 * reserve_b as f64;
+    let product_after = reserve_a_after as f64 * reserve_b_after as f64;
+    assert!(product_after > product_before);
+    let growth_percent = (product_after / product_before - 1.0) * 100.0;
+    let expected_growth_percent =
+        FEE_NUMERATOR as f64 / FEE_DENOMINATOR as f64 * 100.0 * input_amount as f64
+            / (reserve_a + input_amount) as f64;
+    assert!((growth_percent - expected_growth_percent).abs() < 0.01);
+}
+
+#[tokio::test]
+async fn a_large_swap_pays_the_dynamic_fee_of_its_own_price_impact() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let admin = dex.admin.insecure_clone();
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+    let params = DynamicFeeParams {
+        min_fee_bps: 5,
+        max_fee_bps: 200,
+        filter_period: 30,
+        decay_period: 600,
+        reduction_factor: 5000,
+        variable_fee_control: 40_000,
+        max_volatility_accumulator: 100_000,
+    };
+    let state = dex.pool(&pool.address).await;
+    dex.process(
+        &[instructions::configure_dynamic_fee(
+            &admin.pubkey(),
+            &dex.dex_state.clone(),
+            &pool.address,
+            &state,
+            Some(params),
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    // No swap moved the price yet, only this one's 10% of the reserve does
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let input_amount = 100_000;
+    let quote = quote_swap(
+        &state,
+        reserve_a,
+        reserve_b,
+        &pool.token_a_mint,
+        input_amount,
+        0,
+    )
+    .unwrap();
+    let output_amount = swap(&mut dex, &owner, &pool, &pool.token_a_mint, input_amount, 1).await;
+
+    assert_eq!(output_amount, quote.output_amount);
+    assert_eq!(
+        (quote.fee_numerator, quote.fee_denominator),
+        (params.max_fee_bps as u64, BPS_DENOMINATOR)
+    );
+    assert!(
+        output_amount
+            < calculate_output_amount(
+                input_amount,
+                reserve_a,
+                reserve_b,
+                params.min_fee_bps as u64,
+                BPS_DENOMINATOR
+            )
+            .unwrap()
+    );
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(state.protocol_fees_token_a, quote.protocol_fee);
+    assert!(state.dynamic_fee.volatility_accumulator > 0);
+}
+
+#[tokio::test]
+async fn slippage_protection_prevents_unfavorable_trades() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 2_000_000).await;
+
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let quote = quote_swap(&state, reserve_a, reserve_b, &pool.token_a_mint, 50_000, 0).unwrap();
+
+    // Asking for 50% more than the pool can pay
+    let result = dex
+        .process(
+            &[instructions::swap(
+                &owner.pubkey(),
+                &pool.address,
+                &state,
+                &pool.token_a_mint,
+                &TOKEN_2022_PROGRAM_ID,
+                50_000,
+                quote.output_amount * 3 / 2,
+            )
+            .unwrap()],
+            &[&owner],
+        )
+        .await;
+
+    assert_dex_error(result, DexError::SlippageExceeded);
+    assert_eq!(dex.reserves(&pool.address).await, (reserve_a, reserve_b));
+}
+
+#[tokio::test]
+async fn denied_mints_cannot_be_swapped() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &owner, 1_000_000, 1_000_000).await;
+
+    let admin = dex.admin.insecure_clone();
+    dex.process(
+        &[instructions::set_mint_status(
+            &admin.pubkey(),
+            &pool.token_a_mint,
+            MintListing::Denied,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    let state = dex.pool(&pool.address).await;
+    let reserves = dex.reserves(&pool.address).await;
+    for source_mint in [pool.token_a_mint, pool.token_b_mint] {
+        let swap = instructions::swap(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &source_mint,
+            &TOKEN_2022_PROGRAM_ID,
+            10_000,
+            0,
+        )
+        .unwrap();
+        // Leaving out the DEX state and the listings doesn't skip the check
+        let mut unlisted_swap = swap.clone();
+        unlisted_swap.accounts.drain(4..7);
+        let result = dex.process(&[unlisted_swap], &[&owner]).await;
+        assert!(result.is_err());
+
+        let result = dex.process(&[swap], &[&owner]).await;
+        assert_dex_error(result, DexError::MintDenied);
+    }
+    assert_eq!(dex.reserves(&pool.address).await, reserves);
+}
+
+#[tokio::test]
+async fn resists_price_manipulation_attacks() {
+    let mut dex = TestDex::start().await;
+    let provider = dex.create_user().await;
+    let manipulator = dex.create_user().await;
+    let pool = create_funded_pool(&mut dex, &provider, 1_000_000, 1_000_000).await;
+    dex.mint_to(
+        &pool.token_a_mint,
+        &provider,
+        &manipulator.pubkey(),
+        MINTED_AMOUNT,
+    )
+    .await;
+
+    // Skew the price with a large swap of token A
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let skew_amount = 8_000_000;
+    let output = swap(
+        &mut dex,
+        &manipulator,
+        &pool,
+        &pool.token_a_mint,
+        skew_amount,
+        0,
+    )
+    .await;
+    let (skewed_a, skewed_b) = dex.reserves(&pool.address).await;
+    assert!(skewed_a as f64 / skewed_b as f64 > 2.0 * reserve_a as f64 / reserve_b as f64);
+
+    // Swapping everything back loses the fees, which stay with the pool
+    swap(&mut dex, &manipulator, &pool, &pool.token_b_mint, output, 0).await;
+    let final_token_a = dex
+        .balance_of(&manipulator.pubkey(), &pool.token_a_mint)
+        .await;
+    assert!(final_token_a < MINTED_AMOUNT);
+    assert_eq!(
+        dex.balance_of(&manipulator.pubkey(), &pool.token_b_mint)
+            .await,
+        0
+    );
+
+    // Whatever the manipulator lost is now held by the pool
+    let (final_reserve_a, final_reserve_b) = dex.reserves(&pool.address).await;
+    assert_eq!(final_reserve_a, reserve_a + (MINTED_AMOUNT - final_token_a));
+    assert_eq!(final_reserve_b, reserve_b);
+}
+
+#[tokio::test]
+async fn handles_different_deposit_ratios() {
+    let mut dex = TestDex::start().await;
+    let user = dex.create_user().await;
+
+    // Balanced first deposit
+    let pool = create_funded_pool(&mut dex, &user, 1_000_000, 1_000_000).await;
+    let balanced_lp = dex.balance_of(&user.pubkey(), &pool.lp_token_mint).await;
+
+    // Doubling token A without more token B mints by the scarcer side
+    let state = dex.pool(&pool.address).await;
+    let (reserve_a, reserve_b) = dex.reserves(&pool.address).await;
+    let expected_lp = quote_deposit(&state, reserve_a, reserve_b, 2_000_000, 1_000_000).unwrap();
+    deposit(&mut dex, &user, &pool, 2_000_000, 1_000_000).await;
+
+    let imbalanced_lp = dex.balance_of(&user.pubkey(), &pool.lp_token_mint).await - balanced_lp;
+    assert_eq!(imbalanced_lp, expected_lp);
+    assert_eq!(imbalanced_lp, balanced_lp);
+    assert!(imbalanced_lp < 2 * balanced_lp);
+
+    // The surplus token A is not refunded, it accrues to every LP
+    assert_eq!(dex.reserves(&pool.address).await, (3_000_000, 2_000_000));
+}
+
+#[tokio::test]
+async fn migrates_first_release_accounts() {
+    let mut dex = TestDex::start().await;
+    let payer = dex.create_user().await;
+    let admin = dex.admin.insecure_clone();
+
+    // The DEX state and a pool as the first release wrote them, 97 and 210 bytes
+    let dex_state = dex.dex_state;
+    let pool_address = Pubkey::new_unique();
+    let first_release_state = layouts::v0::DexState {
+        admin: admin.pubkey(),
+        pools_count: 1,
+        fee_numerator: FEE_NUMERATOR,
+        fee_denominator: FEE_DENOMINATOR,
+        protocol_fee_percentage: PROTOCOL_FEE_PERCENTAGE,
+        fee_collector: dex.fee_collector.pubkey(),
+    };
+    dex.set_program_account(
+        &dex_state,
+        layouts::account_data(DexState::DISCRIMINATOR, &first_release_state),
+    );
+    dex.set_program_account(
+        &pool_address,
+        layouts::account_data(
+            LiquidityPool::DISCRIMINATOR,
+            &layouts::v0::LiquidityPool::sample(),
+        ),
+    );
+    assert_eq!(dex.account_len(&dex_state).await, 97);
+    assert_eq!(dex.account_len(&pool_address).await, 210);
+
+    dex.process(&[instructions::migrate_state(&admin.pubkey())], &[&admin])
+        .await
+        .unwrap();
+    dex.process(
+        &[instructions::migrate_pool(&payer.pubkey(), &pool_address)],
+        &[&payer],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(dex.account_len(&dex_state).await, 8 + DexState::LEN);
+    let state = dex.dex_state().await;
+    assert_eq!(state.version, DexState::VERSION);
+    assert_eq!(state.admin, admin.pubkey());
+    assert_eq!(state.pools_count, 1);
+    assert_eq!(
+        (state.fee_numerator, state.fee_denominator),
+        (FEE_NUMERATOR, FEE_DENOMINATOR)
+    );
+    assert_eq!(state.fee_collector, dex.fee_collector.pubkey());
+
+    assert_eq!(dex.account_len(&pool_address).await, 8 + LiquidityPool::LEN);
+    let pool = dex.pool(&pool_address).await;
+    assert_eq!(pool.version, LiquidityPool::VERSION);
+    assert_eq!(pool.creator, admin.pubkey());
+    assert_eq!(pool.token_a_mint, layouts::TOKEN_A_MINT);
+    assert_eq!(pool.total_liquidity, 1_000_000);
+    assert_eq!(
+        (pool.protocol_fees_token_a, pool.protocol_fees_token_b),
+        (1234, 5678)
+    );
+
+    // The payer funded the rent of the larger pool
+    let rent = Rent::default().minimum_balance(8 + LiquidityPool::LEN);
+    assert!(dex.account_lamports(&pool_address).await >= rent);
+}
+
+/// Writes a first release pool of two fresh mints, whose PDA is derived from the pair alone, with
+/// its vaults and an LP mint of `lp_token_program`, and migrates it
+async fn create_first_release_pool(
+    dex: &mut TestDex,
+    payer: &Keypair,
+    lp_token_program: &Pubkey,
+) -> TestPool {
+    let token_a_mint = dex.create_mint(payer).await;
+    let token_b_mint = dex.create_mint(payer).await;
+    let (address, bump) = Pubkey::find_program_address(
+        &[
+            b"liquidity_pool",
+            token_a_mint.as_ref(),
+            token_b_mint.as_ref(),
+        ],
+        &dex::ID,
+    );
+    let first_release_pool = layouts::v0::LiquidityPool {
+        token_a_mint,
+        token_b_mint,
+        token_a_account: dex.create_token_account(&token_a_mint, &address).await,
+        token_b_account: dex.create_token_account(&token_b_mint, &address).await,
+        lp_token_mint: dex.create_mint_of_program(&address, lp_token_program).await,
+        bump,
+        total_liquidity: 0,
+        fee_numerator: FEE_NUMERATOR,
+        fee_denominator: FEE_DENOMINATOR,
+        protocol_fee_percentage: PROTOCOL_FEE_PERCENTAGE,
+        protocol_fees_token_a: 0,
+        protocol_fees_token_b: 0,
+    };
+    let lp_token_mint = first_release_pool.lp_token_mint;
+    dex.set_program_account(
+        &address,
+        layouts::account_data(LiquidityPool::DISCRIMINATOR, &first_release_pool),
+    );
+
+    dex.process(
+        &[instructions::migrate_pool(&payer.pubkey(), &address)],
+        &[payer],
+    )
+    .await
+    .unwrap();
+
+    TestPool {
+        address,
+        token_a_mint,
+        token_b_mint,
+        lp_token_mint,
+    }
+}
+
+#[tokio::test]
+async fn migrated_pools_from_before_fee_tiers_keep_their_address() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_first_release_pool(&mut dex, &owner, &TOKEN_2022_PROGRAM_ID).await;
+
+    let fee_tier_index = dex.pool(&pool.address).await.fee_tier_index;
+    assert_eq!(fee_tier_index, LiquidityPool::LEGACY_FEE_TIER_INDEX);
+    assert_eq!(
+        find_liquidity_pool_address(&pool.token_a_mint, &pool.token_b_mint, fee_tier_index).0,
+        pool.address
+    );
+
+    // The pool still signs as its PDA, minting LP tokens and paying out swaps
+    dex.mint_to(&pool.token_a_mint, &owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    dex.mint_to(&pool.token_b_mint, &owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    deposit(&mut dex, &owner, &pool, 1_000_000, 1_000_000).await;
+    assert!(dex.balance_of(&owner.pubkey(), &pool.lp_token_mint).await > 0);
+
+    let output = swap(&mut dex, &owner, &pool, &pool.token_a_mint, 10_000, 1).await;
+    assert!(output > 0);
+    assert_eq!(
+        dex.reserves(&pool.address).await,
+        (1_010_000, 1_000_000 - output)
+    );
+}
+
+#[tokio::test]
+async fn migrated_pools_with_spl_token_lp_mints_take_deposits_and_withdrawals() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let pool = create_first_release_pool(&mut dex, &owner, &SPL_TOKEN_PROGRAM_ID).await;
+    dex.mint_to(&pool.token_a_mint, &owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    dex.mint_to(&pool.token_b_mint, &owner, &owner.pubkey(), MINTED_AMOUNT)
+        .await;
+    let state = dex.pool(&pool.address).await;
+    let fee_collector = dex.fee_collector.pubkey();
+    let deposit_with_lp_token_program = |lp_token_program| {
+        instructions::deposit_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &fee_collector,
+            &TOKEN_2022_PROGRAM_ID,
+            lp_token_program,
+            1_000_000,
+            1_000_000,
+        )
+    };
+
+    // The LP token program must be the owner of the LP mint
+    let result = dex
+        .process(
+            &[deposit_with_lp_token_program(&TOKEN_2022_PROGRAM_ID)],
+            &[&owner],
+        )
+        .await;
+    assert!(result.is_err());
+
+    dex.process(
+        &[deposit_with_lp_token_program(&SPL_TOKEN_PROGRAM_ID)],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    let user_lp_token =
+        get_associated_token_address(&owner.pubkey(), &pool.lp_token_mint, &SPL_TOKEN_PROGRAM_ID);
+    let lp_tokens = dex.token_balance(&user_lp_token).await;
+    assert!(lp_tokens > 0);
+
+    dex.process(
+        &[instructions::withdraw_liquidity(
+            &owner.pubkey(),
+            &pool.address,
+            &state,
+            &dex.fee_collector.pubkey(),
+            &TOKEN_2022_PROGRAM_ID,
+            &SPL_TOKEN_PROGRAM_ID,
+            lp_tokens,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    assert_eq!(dex.token_balance(&user_lp_token).await, 0);
+    assert_eq!(dex.reserves(&pool.address).await, (0, 0));
+}
+
+#[tokio::test]
+async fn pools_created_after_unlisted_ones_keep_their_registry_entry() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+
+    // A migrated DEX state counts the pools created before the registry, which it doesn't list
+    let mut state = dex.dex_state().await;
+    state.pools_count = 3;
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    let dex_state = dex.dex_state;
+    dex.set_program_account(&dex_state, data);
+
+    let pool = dex.create_pool(&owner).await;
+
+    assert_eq!(dex.pool(&pool.address).await.registry_index, 3);
+    let page = dex.registry_page(3).await;
+    assert_eq!(page.page_index, 0);
+    assert_eq!(page.entries_count, 4);
+    assert_eq!(page.entries[3].pool, pool.address);
+    assert!(page.entries[..3]
+        .iter()
+        .all(|entry| entry.pool == Pubkey::default()));
+}
+
+#[tokio::test]
+async fn closes_pools_created_before_close_pool() {
+    let mut dex = TestDex::start().await;
+    let owner = dex.create_user().await;
+    let admin = dex.admin.insecure_clone();
+    let fee_collector = dex.fee_collector.pubkey();
+    let pool = create_first_release_pool(&mut dex, &owner, &SPL_TOKEN_PROGRAM_ID).await;
+    dex.create_token_account(&pool.token_a_mint, &fee_collector)
+        .await;
+    dex.create_token_account(&pool.token_b_mint, &fee_collector)
+        .await;
+
+    // The admin stands in for the creator the first release didn't record, and the pool isn't
+    // listed in the registry
+    let state = dex.pool(&pool.address).await;
+    assert_eq!(state.creator, admin.pubkey());
+    dex.process(
+        &[instructions::close_pool(
+            &admin.pubkey(),
+            &dex.dex_state.clone(),
+            &fee_collector,
+            &pool.address,
+            &state,
+            &TOKEN_2022_PROGRAM_ID,
+            &SPL_TOKEN_PROGRAM_ID,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    assert!(!dex.account_exists(&pool.address).await);
+    assert!(!dex.account_exists(&state.token_a_account).await);
+    assert!(!dex.account_exists(&state.token_b_account).await);
+    // Its LP mint, of SPL Token, has no close authority: it stays open and empty
+    assert!(dex.account_exists(&pool.lp_token_mint).await);
+    assert_eq!(dex.dex_state().await.closed_pools_count, 1);
+}
@@ -0,0 +1,91 @@
+//! Pool and DEX state accounts as released by the first program version
+//!
+//! Copies of the Borsh structs the program wrote before the current layouts, field for field.
+//! The `sample` accounts are what `fixtures/*.bin` are serialized from
+//! (`DEX_FIXTURES_UPDATE=1 cargo test --test migration` rewrites them).
+
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::{borsh, Pubkey},
+    AnchorSerialize,
+};
+
+/// Data of an account as the program wrote it, discriminator included
+pub fn account_data(discriminator: &[u8], account: &impl AnchorSerialize) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    account.serialize(&mut data).unwrap();
+    data
+}
+
+/// Values of the sample accounts
+pub const TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([2; 32]);
+pub const TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([3; 32]);
+pub const TOKEN_A_ACCOUNT: Pubkey = Pubkey::new_from_array([4; 32]);
+pub const TOKEN_B_ACCOUNT: Pubkey = Pubkey::new_from_array([5; 32]);
+pub const LP_TOKEN_MINT: Pubkey = Pubkey::new_from_array([6; 32]);
+pub const ADMIN: Pubkey = Pubkey::new_from_array([9; 32]);
+pub const FEE_COLLECTOR: Pubkey = Pubkey::new_from_array([10; 32]);
+
+/// First release, without version
+pub mod v0 {
+    use super::*;
+
+    #[derive(AnchorSerialize)]
+    pub struct LiquidityPool {
+        pub token_a_mint: Pubkey,
+        pub token_b_mint: Pubkey,
+        pub token_a_account: Pubkey,
+        pub token_b_account: Pubkey,
+        pub lp_token_mint: Pubkey,
+        pub bump: u8,
+        pub total_liquidity: u64,
+        pub fee_numerator: u64,
+        pub fee_denominator: u64,
+        pub protocol_fee_percentage: u8,
+        pub protocol_fees_token_a: u64,
+        pub protocol_fees_token_b: u64,
+    }
+
+    impl LiquidityPool {
+        pub fn sample() -> Self {
+            LiquidityPool {
+                token_a_mint: TOKEN_A_MINT,
+                token_b_mint: TOKEN_B_MINT,
+                token_a_account: TOKEN_A_ACCOUNT,
+                token_b_account: TOKEN_B_ACCOUNT,
+                lp_token_mint: LP_TOKEN_MINT,
+                bump: 254,
+                total_liquidity: 1_000_000,
+                fee_numerator: 3,
+                fee_denominator: 1000,
+                protocol_fee_percentage: 20,
+                protocol_fees_token_a: 1234,
+                protocol_fees_token_b: 5678,
+            }
+        }
+    }
+
+    #[derive(AnchorSerialize)]
+    pub struct DexState {
+        pub admin: Pubkey,
+        pub pools_count: u64,
+        pub fee_numerator: u64,
+        pub fee_denominator: u64,
+        pub protocol_fee_percentage: u8,
+        pub fee_collector: Pubkey,
+    }
+
+    impl DexState {
+        pub fn sample() -> Self {
+            DexState {
+                admin: ADMIN,
+                pools_count: 4,
+                fee_numerator: 3,
+                fee_denominator: 1000,
+                protocol_fee_percentage: 20,
+                fee_collector: FEE_COLLECTOR,
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Upgrades of accounts serialized with earlier layouts
+//!
+//! The fixtures are raw account data, discriminator included, as written by earlier program
+//! versions, serialized from the released structs of `layouts`:
+//! - `liquidity_pool_v0.bin`: pool of the first release, without version
+//! - `dex_state_v0.bin`: DEX state of the first release, without version
+
+mod layouts;
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize, Discriminator};
+use dex::{
+    migrate_dex_state_data, migrate_pool_data, DexError, DexState, LiquidityPool, PoolCreationMode,
+    ProtocolFeeMode, RESERVED_SPACE,
+};
+use layouts::*;
+
+const LIQUIDITY_POOL_V0: &[u8] = include_bytes!("fixtures/liquidity_pool_v0.bin");
+const DEX_STATE_V0: &[u8] = include_bytes!("fixtures/dex_state_v0.bin");
+
+fn decode_pool(data: &[u8]) -> LiquidityPool {
+    assert_eq!(data[..8], *LiquidityPool::DISCRIMINATOR);
+    bytemuck::pod_read_unaligned(&data[8..])
+}
+
+/// Checks the fields of the first release
+fn assert_v0_pool_fields_kept(pool: &LiquidityPool) {
+    assert_eq!(pool.token_a_mint, TOKEN_A_MINT);
+    assert_eq!(pool.token_b_mint, TOKEN_B_MINT);
+    assert_eq!(pool.token_a_account, TOKEN_A_ACCOUNT);
+    assert_eq!(pool.token_b_account, TOKEN_B_ACCOUNT);
+    assert_eq!(pool.lp_token_mint, LP_TOKEN_MINT);
+    assert_eq!(pool.bump, 254);
+    assert_eq!(pool.total_liquidity, 1_000_000);
+    assert_eq!((pool.fee_numerator, pool.fee_denominator), (3, 1000));
+    assert_eq!(pool.protocol_fee_percentage, 20);
+    assert_eq!(
+        (pool.protocol_fees_token_a, pool.protocol_fees_token_b),
+        (1234, 5678)
+    );
+    assert_eq!(pool.version, LiquidityPool::VERSION);
+    // Pools from before the protocol fee modes keep accruing it in their counters
+    assert!(!pool.mints_protocol_fee());
+    assert_eq!(pool.k_last(), 0);
+    assert_eq!(pool.reserved, [0; RESERVED_SPACE - 16 - 1]);
+}
+
+#[test]
+fn fixtures_serialize_the_released_structs() {
+    let pool_discriminator = LiquidityPool::DISCRIMINATOR;
+    let fixtures = [
+        (
+            "liquidity_pool_v0.bin",
+            LIQUIDITY_POOL_V0,
+            account_data(pool_discriminator, &v0::LiquidityPool::sample()),
+        ),
+        (
+            "dex_state_v0.bin",
+            DEX_STATE_V0,
+            account_data(DexState::DISCRIMINATOR, &v0::DexState::sample()),
+        ),
+    ];
+
+    for (name, fixture, data) in fixtures {
+        if std::env::var_os("DEX_FIXTURES_UPDATE").is_some() {
+            let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+            std::fs::write(path, &data).unwrap();
+        } else {
+            assert_eq!(fixture, data, "{name} is stale");
+        }
+    }
+}
+
+#[test]
+fn fixtures_match_the_layout_sizes() {
+    // The first release, 210 and 97 bytes with the discriminator
+    assert_eq!(LIQUIDITY_POOL_V0.len(), 8 + LiquidityPool::LEN_V0);
+    assert_eq!(LIQUIDITY_POOL_V0.len(), 210);
+    assert_eq!(DEX_STATE_V0.len(), 8 + DexState::LEN_V0);
+    assert_eq!(DEX_STATE_V0.len(), 97);
+}
+
+#[test]
+fn migrates_v0_pool() {
+    let migrated = migrate_pool_data(LIQUIDITY_POOL_V0).unwrap();
+    assert_eq!(migrated.len(), 8 + LiquidityPool::LEN);
+
+    let pool = decode_pool(&migrated);
+    assert_v0_pool_fields_kept(&pool);
+    // v0 pools didn't record their creator, the admin stands in
+    assert_eq!(pool.creator, dex::ADMIN_PUBKEY);
+    // Nor a fee tier, their PDA is derived from the pair alone
+    assert_eq!(pool.fee_tier_index, LiquidityPool::LEGACY_FEE_TIER_INDEX);
+    // Nor were they listed in the registry
+    assert_eq!(pool.registry_index, LiquidityPool::UNLISTED_REGISTRY_INDEX);
+    // Nor when they were created
+    assert_eq!((pool.created_at, pool.created_slot), (0, 0));
+    // Fields added since start zeroed
+    assert!(!pool.dynamic_fee.is_enabled());
+    assert_eq!(pool.dynamic_fee.params(), Default::default());
+    assert_eq!(pool.token_a_transfer_hook, Pubkey::default());
+    assert_eq!(pool.token_b_transfer_hook, Pubkey::default());
+}
+
+#[test]
+fn migrates_v0_dex_state() {
+    let migrated = migrate_dex_state_data(DEX_STATE_V0).unwrap();
+    assert_eq!(migrated.len(), 8 + DexState::LEN);
+
+    let dex_state = DexState::try_deserialize(&mut &migrated[..]).unwrap();
+    assert_eq!(dex_state.admin, ADMIN);
+    assert_eq!(dex_state.pools_count, 4);
+    assert_eq!(
+        (dex_state.fee_numerator, dex_state.fee_denominator),
+        (3, 1000)
+    );
+    assert_eq!(dex_state.protocol_fee_percentage, 20);
+    assert_eq!(dex_state.fee_collector, FEE_COLLECTOR);
+    // Fields added since start zeroed
+    assert_eq!(dex_state.fee_tiers_count, 0);
+    assert_eq!(dex_state.closed_pools_count, 0);
+    assert_eq!(dex_state.version, DexState::VERSION);
+    assert_eq!(dex_state.pool_creation_mode, PoolCreationMode::Open);
+    assert!(!dex_state.require_allowed_mints);
+    assert_eq!(dex_state.protocol_fee_mode, ProtocolFeeMode::Accrued);
+    assert_eq!(dex_state.reserved, [0; RESERVED_SPACE - 3]);
+}
+
+#[test]
+fn rejects_migrating_twice() {
+    let migrated_pool = migrate_pool_data(LIQUIDITY_POOL_V0).unwrap();
+    assert_eq!(
+        migrate_pool_data(&migrated_pool).unwrap_err(),
+        DexError::AccountAlreadyMigrated.into()
+    );
+
+    let migrated_state = migrate_dex_state_data(DEX_STATE_V0).unwrap();
+    assert_eq!(
+        migrate_dex_state_data(&migrated_state).unwrap_err(),
+        DexError::AccountAlreadyMigrated.into()
+    );
+}
+
+#[test]
+fn rejects_unknown_layouts() {
+    // Another account type
+    assert_eq!(
+        migrate_pool_data(DEX_STATE_V0).unwrap_err(),
+        DexError::UnknownAccountLayout.into()
+    );
+    // An account of another type with the size of a pool
+    let mut dex_state_sized_as_pool = LIQUIDITY_POOL_V0.to_vec();
+    dex_state_sized_as_pool[..8].copy_from_slice(DexState::DISCRIMINATOR);
+    assert_eq!(
+        migrate_pool_data(&dex_state_sized_as_pool).unwrap_err(),
+        DexError::UnknownAccountLayout.into()
+    );
+    // A pool of no known size
+    assert_eq!(
+        migrate_pool_data(&LIQUIDITY_POOL_V0[..LIQUIDITY_POOL_V0.len() - 1]).unwrap_err(),
+        DexError::UnknownAccountLayout.into()
+    );
+    assert_eq!(
+        migrate_dex_state_data(&DEX_STATE_V0[..DEX_STATE_V0.len() - 1]).unwrap_err(),
+        DexError::UnknownAccountLayout.into()
+    );
+}
@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        extension::{
+            mint_close_authority::MintCloseAuthority, BaseStateWithExtensions, StateWithExtensions,
+        },
+        state::Mint as MintState,
+    },
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface},
+};
+
+use super::PoolSigner;
+
+/// Closes an empty pool vault, sending its rent to `destination`
+pub(crate) fn close_pool_token_account<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    pool_token: &InterfaceAccount<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    pool: &PoolSigner<'info>,
+) -> Result<()> {
+    close_account_signed_by_pool(
+        token_program.to_account_info(),
+        pool_token.to_account_info(),
+        destination,
+        pool,
+    )
+}
+
+/// Closes the LP mint of a pool once its supply is zero, sending its rent to `destination`
+/// The pool is the mint close authority. LP mints of pools created before close_pool, the SPL
+/// Token mints of the first release among them, have none and can't be closed: they stay open,
+/// empty
+pub(crate) fn close_lp_token_mint<'info>(
+    lp_token_program: &Interface<'info, TokenInterface>,
+    lp_token_mint: &InterfaceAccount<'info, Mint>,
+    destination: &AccountInfo<'info>,
+    pool: &PoolSigner<'info>,
+) -> Result<()> {
+    if !has_close_authority(lp_token_mint)? {
+        return Ok(());
+    }
+
+    close_account_signed_by_pool(
+        lp_token_program.to_account_info(),
+        lp_token_mint.to_account_info(),
+        destination,
+        pool,
+    )
+}
+
+/// Whether a mint has the close authority extension, never the case of an SPL Token mint
+fn has_close_authority(mint: &InterfaceAccount<Mint>) -> Result<bool> {
+    let mint_info = mint.to_account_info();
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    Ok(mint_state.get_extension::<MintCloseAuthority>().is_ok())
+}
+
+fn close_account_signed_by_pool<'info>(
+    token_program: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    pool: &PoolSigner<'info>,
+) -> Result<()> {
+    // Create the PDA signer for the close operation
+    let pool_seeds = pool.seeds();
+    let signer = &[&pool_seeds[..]];
+
+    close_account(CpiContext::new_with_signer(
+        token_program,
+        CloseAccount {
+            account,
+            destination: destination.clone(),
+            authority: pool.to_account_info(),
+        },
+        signer,
+    ))
+}
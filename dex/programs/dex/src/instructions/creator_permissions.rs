@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use super::{CreatorPermissions, DexError, DexState};
+
+/// Lets a pool creator change the dynamic fee of their own pools, normally an admin action
+pub const PERMISSION_CONFIGURE_DYNAMIC_FEE: u8 = 1 << 0;
+
+/// Lets a creator create pools while the DEX only accepts allowlisted creators
+pub const PERMISSION_CREATE_POOL: u8 = 1 << 1;
+
+/// Every permission governance can grant to a creator
+pub const ALL_CREATOR_PERMISSIONS: u8 = PERMISSION_CONFIGURE_DYNAMIC_FEE | PERMISSION_CREATE_POOL;
+
+/// Who may create pools on the DEX
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoolCreationMode {
+    /// Anyone can create a pool for any pair
+    #[default]
+    Open,
+    /// Only the admin creates pools
+    AdminOnly,
+    /// The admin and creators granted PERMISSION_CREATE_POOL create pools
+    Allowlisted,
+}
+
+/// Checks that `creator` may create a pool under the DEX's pool creation mode
+/// The admin can always create pools
+pub(crate) fn check_pool_creator(
+    dex_state: &DexState,
+    creator: &Pubkey,
+    creator_permissions: Option<&CreatorPermissions>,
+) -> Result<()> {
+    let allowed = match dex_state.pool_creation_mode {
+        PoolCreationMode::Open => true,
+        PoolCreationMode::AdminOnly => *creator == dex_state.admin,
+        PoolCreationMode::Allowlisted => {
+            *creator == dex_state.admin
+                || creator_permissions
+                    .is_some_and(|permissions| permissions.allows(PERMISSION_CREATE_POOL))
+        }
+    };
+    require!(allowed, DexError::PoolCreationNotAllowed);
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use anchor_lang::prelude::*;
+use dex_math::{calculate_spot_price, mul_div, Rounding};
+
+use super::DexError;
+
+/// Denominator for every fee expressed in basis points (10_000 bps = 100%)
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Precision of `variable_fee_control`
+/// variable_fee_bps = variable_fee_control * volatility_accumulator^2 / VARIABLE_FEE_PRECISION
+pub const VARIABLE_FEE_PRECISION: u128 = 100_000_000;
+
+/// Largest cap of the volatility accumulator, the 20 bits Liquidity Book packs it in
+/// (a price move of about 100x)
+pub const MAX_VOLATILITY_ACCUMULATOR: u32 = (1 << 20) - 1;
+
+/// Settings of the volatility-based fee, modelled after Trader Joe's Liquidity Book
+/// All periods are in seconds, all fees and price moves in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DynamicFeeParams {
+    // Fee charged when the market is quiet
+    pub min_fee_bps: u16,
+    // Upper bound of the fee, whatever the volatility
+    pub max_fee_bps: u16,
+    // Swaps closer together than this share the same reference price (high frequency window)
+    pub filter_period: u16,
+    // After this long without swaps the volatility is forgotten entirely
+    pub decay_period: u16,
+    // Share of the accumulator (in bps) kept as reference when a new filter period starts
+    pub reduction_factor: u16,
+    // Scales the squared volatility into a fee, see VARIABLE_FEE_PRECISION
+    pub variable_fee_control: u32,
+    // Cap on the volatility accumulator, in bps of price move
+    pub max_volatility_accumulator: u32,
+}
+
+impl DynamicFeeParams {
+    pub const LEN: usize = 2 + 2 + 2 + 2 + 2 + 4 + 4;
+
+    /// Checks that the fee range, the accumulator cap and the decay settings are coherent
+    /// A zero min fee would let quiet markets trade for free, a zero cap would never charge more
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            0 < self.min_fee_bps
+                && self.min_fee_bps <= self.max_fee_bps
+                && (self.max_fee_bps as u64) < BPS_DENOMINATOR,
+            DexError::InvalidDynamicFeeParams
+        );
+        require!(
+            0 < self.max_volatility_accumulator
+                && self.max_volatility_accumulator <= MAX_VOLATILITY_ACCUMULATOR,
+            DexError::InvalidDynamicFeeParams
+        );
+        require!(
+            (self.reduction_factor as u64) <= BPS_DENOMINATOR,
+            DexError::InvalidDynamicFeeParams
+        );
+        require!(
+            self.filter_period < self.decay_period,
+            DexError::InvalidDynamicFeeParams
+        );
+
+        Ok(())
+    }
+}
+
+/// Dynamic fee configuration and volatility tracking state stored in each pool
+/// Laid out for zero-copy: the parameters are flattened and the Q64.64 price kept as bytes, since
+/// u128 alignment differs between the host and SBF
+#[zero_copy]
+#[derive(Debug, Default)]
+pub struct DynamicFee {
+    // Unix timestamp of the last swap that updated the accumulator
+    pub last_update_timestamp: i64,
+    // Spot price (Q64.64, token B per token A, little endian) the current moves are measured
+    // against
+    pub reference_price: [u8; 16],
+    // Decaying measure of recent price moves, in bps
+    pub volatility_accumulator: u32,
+    // Part of the accumulator carried over from the previous filter period, in bps
+    pub volatility_reference: u32,
+    // DynamicFeeParams, see there for their meaning
+    pub variable_fee_control: u32,
+    pub max_volatility_accumulator: u32,
+    pub min_fee_bps: u16,
+    pub max_fee_bps: u16,
+    pub filter_period: u16,
+    pub decay_period: u16,
+    pub reduction_factor: u16,
+    // When 0 the pool charges its static fee_numerator/fee_denominator
+    pub enabled: u8,
+    pub padding: [u8; 5],
+}
+
+impl DynamicFee {
+    pub const LEN: usize = 8 + 16 + 4 + 4 + 4 + 4 + 2 + 2 + 2 + 2 + 2 + 1 + 5;
+
+    /// Enabled dynamic fee with the given parameters and no volatility recorded yet
+    pub fn new(params: DynamicFeeParams) -> Self {
+        Self {
+            variable_fee_control: params.variable_fee_control,
+            max_volatility_accumulator: params.max_volatility_accumulator,
+            min_fee_bps: params.min_fee_bps,
+            max_fee_bps: params.max_fee_bps,
+            filter_period: params.filter_period,
+            decay_period: params.decay_period,
+            reduction_factor: params.reduction_factor,
+            enabled: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != 0
+    }
+
+    pub fn params(&self) -> DynamicFeeParams {
+        DynamicFeeParams {
+            min_fee_bps: self.min_fee_bps,
+            max_fee_bps: self.max_fee_bps,
+            filter_period: self.filter_period,
+            decay_period: self.decay_period,
+            reduction_factor: self.reduction_factor,
+            variable_fee_control: self.variable_fee_control,
+            max_volatility_accumulator: self.max_volatility_accumulator,
+        }
+    }
+
+    pub fn reference_price(&self) -> u128 {
+        u128::from_le_bytes(self.reference_price)
+    }
+
+    /// Updates the volatility accumulator with a swap moving the price from `price_before` to
+    /// `price_after` and returns the fee to charge, in basis points
+    /// The larger of both moves from the reference counts, so that a swap pays for its own price
+    /// impact and not only for the moves of the swaps before it
+    pub fn update(&mut self, now: i64, price_before: u128, price_after: u128) -> Result<u64> {
+        let params = self.params();
+        let elapsed = now.saturating_sub(self.last_update_timestamp);
+
+        // A new filter period starts: move the reference to the current price and decay
+        // the volatility gathered so far
+        if self.reference_price() == 0 || elapsed >= params.filter_period as i64 {
+            self.volatility_reference = if elapsed < params.decay_period as i64 {
+                ((self.volatility_accumulator as u64)
+                    .checked_mul(params.reduction_factor as u64)
+                    .ok_or(error!(DexError::InvalidDynamicFeeParams))?
+                    / BPS_DENOMINATOR) as u32
+            } else {
+                0
+            };
+            self.reference_price = price_before.to_le_bytes();
+        }
+
+        // Accumulate the price move against the reference, capped by the configuration
+        let price_move_bps = calculate_price_move_bps(self.reference_price(), price_before).max(
+            calculate_price_move_bps(self.reference_price(), price_after),
+        );
+        self.volatility_accumulator = (self.volatility_reference as u128)
+            .saturating_add(price_move_bps)
+            .min(params.max_volatility_accumulator as u128)
+            as u32;
+        self.last_update_timestamp = now;
+
+        calculate_dynamic_fee_bps(&params, self.volatility_accumulator)
+    }
+}
+
+/// Spot prices (token B per token A) before a swap and after it moved along the curve, taking
+/// `source_amount` in and giving `destination_amount` out
+/// None if the amounts don't fit the reserves
+pub fn calculate_swap_prices(
+    is_source_token_a: bool,
+    source_reserve: u64,
+    destination_reserve: u64,
+    source_amount: u64,
+    destination_amount: u64,
+) -> Option<(u128, u128)> {
+    let source_reserve_after = source_reserve.checked_add(source_amount)?;
+    let destination_reserve_after = destination_reserve.checked_sub(destination_amount)?;
+
+    Some(if is_source_token_a {
+        (
+            calculate_spot_price(source_reserve, destination_reserve),
+            calculate_spot_price(source_reserve_after, destination_reserve_after),
+        )
+    } else {
+        (
+            calculate_spot_price(destination_reserve, source_reserve),
+            calculate_spot_price(destination_reserve_after, source_reserve_after),
+        )
+    })
+}
+
+/// Relative move between two prices, in basis points of the reference price
+/// Rounded up, the fee it feeds into is paid to the pool
+fn calculate_price_move_bps(reference_price: u128, current_price: u128) -> u128 {
+    if reference_price == 0 {
+        return 0;
+    }
+
+    mul_div(
+        current_price.abs_diff(reference_price),
+        BPS_DENOMINATOR as u128,
+        reference_price,
+        Rounding::Up,
+    )
+    .unwrap_or(u128::MAX)
+}
+
+/// Fee for a given volatility: min fee plus the variable part, clamped to the max fee
+pub(crate) fn calculate_dynamic_fee_bps(
+    params: &DynamicFeeParams,
+    volatility_accumulator: u32,
+) -> Result<u64> {
+    let squared_volatility = (volatility_accumulator as u128)
+        .checked_mul(volatility_accumulator as u128)
+        .ok_or(error!(DexError::InvalidDynamicFeeParams))?;
+
+    let variable_fee_bps = squared_volatility
+        .checked_mul(params.variable_fee_control as u128)
+        .ok_or(error!(DexError::InvalidDynamicFeeParams))?
+        / VARIABLE_FEE_PRECISION;
+
+    let fee_bps = (params.min_fee_bps as u128)
+        .saturating_add(variable_fee_bps)
+        .min(params.max_fee_bps as u128);
+
+    Ok(fee_bps as u64)
+}
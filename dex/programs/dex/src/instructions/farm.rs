@@ -0,0 +1,220 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use super::{transfer_checked_with_hook, DexError, DexState, Farm, RewardStream, StakePosition};
+
+/// Scale applied to the reward-per-share accumulators to keep precision with integer math
+pub const REWARD_PER_SHARE_PRECISION: u128 = 1_000_000_000_000;
+
+/// Maximum number of concurrent reward streams in a farm
+pub const MAX_REWARD_STREAMS: usize = 4;
+
+/// Maximum number of accounts (besides the admin) allowed to add reward streams to a farm
+pub const MAX_FARM_FUNDERS: usize = 4;
+
+/// Brings one stream's accumulator up to date with the rewards emitted since its last update
+/// Emission only happens inside the [start_time, end_time] window; rewards emitted while no LP
+/// is staked are carried forward to the stakers of the next update
+pub(crate) fn update_reward_stream(
+    stream: &mut RewardStream,
+    total_staked: u64,
+    now: i64,
+) -> Result<()> {
+    // Rewards stop accruing at the end of the window
+    let applicable_time = std::cmp::min(now, stream.end_time);
+    let emitted = if applicable_time > stream.last_update_time {
+        let elapsed = (applicable_time - stream.last_update_time) as u64;
+        stream.last_update_time = applicable_time;
+        elapsed
+            .checked_mul(stream.reward_per_second)
+            .ok_or(error!(DexError::InvalidFarmSchedule))?
+    } else {
+        0
+    };
+
+    // Nobody staked: keep the emitted rewards for the next stakers
+    if total_staked == 0 {
+        stream.carried_rewards = stream
+            .carried_rewards
+            .checked_add(emitted)
+            .ok_or(error!(DexError::InvalidFarmSchedule))?;
+        return Ok(());
+    }
+
+    let rewards = emitted
+        .checked_add(stream.carried_rewards)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+    if rewards == 0 {
+        return Ok(());
+    }
+
+    // acc_reward_per_share += rewards * PRECISION / total_staked
+    let reward_per_share = (rewards as u128)
+        .checked_mul(REWARD_PER_SHARE_PRECISION)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?
+        .checked_div(total_staked as u128)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+
+    stream.acc_reward_per_share = stream
+        .acc_reward_per_share
+        .checked_add(reward_per_share)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+    stream.carried_rewards = 0;
+    stream.unpaid_rewards = stream
+        .unpaid_rewards
+        .checked_add(rewards)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+
+    Ok(())
+}
+
+/// Reward tokens a stream's vault must hold to pay everything the stream owes and will still
+/// emit until the end of its window
+pub(crate) fn calculate_reward_obligations(stream: &RewardStream, now: i64) -> Result<u64> {
+    let remaining_time = stream
+        .end_time
+        .saturating_sub(std::cmp::max(now, stream.last_update_time))
+        .max(0) as u64;
+
+    remaining_time
+        .checked_mul(stream.reward_per_second)
+        .and_then(|remaining| remaining.checked_add(stream.unpaid_rewards))
+        .and_then(|owed| owed.checked_add(stream.carried_rewards))
+        .ok_or(error!(DexError::InvalidFarmSchedule))
+}
+
+/// Brings every active stream of the farm up to date
+pub(crate) fn update_farm_accumulators(farm: &mut Farm, now: i64) -> Result<()> {
+    let total_staked = farm.total_staked;
+
+    for stream in farm
+        .reward_streams
+        .iter_mut()
+        .take(farm.reward_stream_count as usize)
+    {
+        update_reward_stream(stream, total_staked, now)?;
+    }
+
+    Ok(())
+}
+
+/// Rewards a staked amount is entitled to at the given accumulator value
+pub(crate) fn calculate_reward_debt(
+    staked_amount: u64,
+    acc_reward_per_share: u128,
+) -> Result<u128> {
+    Ok((staked_amount as u128)
+        .checked_mul(acc_reward_per_share)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?
+        / REWARD_PER_SHARE_PRECISION)
+}
+
+/// Rewards earned by a position since its reward debt was last settled
+pub(crate) fn calculate_pending_rewards(
+    staked_amount: u64,
+    acc_reward_per_share: u128,
+    reward_debt: u128,
+) -> Result<u64> {
+    let accrued = calculate_reward_debt(staked_amount, acc_reward_per_share)?;
+
+    let pending = accrued
+        .checked_sub(reward_debt)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+
+    u64::try_from(pending).map_err(|_| error!(DexError::InvalidFarmSchedule))
+}
+
+/// Checks whether an account may add reward streams to a farm: the admin or a registered funder
+pub(crate) fn is_authorized_funder(farm: &Farm, dex_state: &DexState, key: &Pubkey) -> bool {
+    *key == dex_state.admin
+        || farm
+            .funders
+            .iter()
+            .any(|funder| *funder != Pubkey::default() && funder == key)
+}
+
+/// Transfers tokens from a user (or funder) to one of the farm vaults
+pub(crate) fn transfer_user_tokens_to_farm<'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    from_account: &InterfaceAccount<'info, TokenAccount>,
+    to_account: &InterfaceAccount<'info, TokenAccount>,
+    authority: &Signer<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    transfer_checked_with_hook(
+        mint,
+        token_program,
+        from_account,
+        to_account,
+        authority.to_account_info(),
+        remaining_accounts,
+        amount,
+        &[],
+    )
+}
+
+/// Transfers tokens from one of the farm vaults to a user
+pub(crate) fn transfer_farm_tokens_to_user<'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    from_account: &InterfaceAccount<'info, TokenAccount>,
+    to_account: &InterfaceAccount<'info, TokenAccount>,
+    farm: &Account<'info, Farm>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    // Create the PDA signer for the transfer operation
+    let farm_seeds = &[b"farm", farm.pool.as_ref(), &[farm.bump]];
+    let signer = &[&farm_seeds[..]];
+
+    transfer_checked_with_hook(
+        mint,
+        token_program,
+        from_account,
+        to_account,
+        farm.to_account_info(),
+        remaining_accounts,
+        amount,
+        signer,
+    )
+}
+
+/// Moves the rewards earned by a position since its last settlement into its pending rewards,
+/// for every stream of the farm
+pub(crate) fn settle_position_rewards(farm: &Farm, position: &mut StakePosition) -> Result<()> {
+    for (stream, reward) in farm
+        .reward_streams
+        .iter()
+        .zip(position.rewards.iter_mut())
+        .take(farm.reward_stream_count as usize)
+    {
+        let pending = calculate_pending_rewards(
+            position.amount,
+            stream.acc_reward_per_share,
+            reward.reward_debt,
+        )?;
+
+        reward.pending_rewards = reward
+            .pending_rewards
+            .checked_add(pending)
+            .ok_or(error!(DexError::InvalidFarmSchedule))?;
+    }
+
+    Ok(())
+}
+
+/// Resets the reward debts of a position to its current stake, for every stream of the farm
+pub(crate) fn reset_position_reward_debts(farm: &Farm, position: &mut StakePosition) -> Result<()> {
+    for (stream, reward) in farm
+        .reward_streams
+        .iter()
+        .zip(position.rewards.iter_mut())
+        .take(farm.reward_stream_count as usize)
+    {
+        reward.reward_debt = calculate_reward_debt(position.amount, stream.acc_reward_per_share)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::{
+    token_2022::{
+        spl_token_2022::{
+            extension::{BaseStateWithExtensions, StateWithExtensions},
+            state::Mint as MintState,
+        },
+        Token2022,
+    },
+    token_interface::{
+        spl_pod::optional_keys::OptionalNonZeroPubkey,
+        spl_token_metadata_interface::state::TokenMetadata, token_metadata_initialize, Mint,
+        TokenMetadataInitialize,
+    },
+};
+
+use super::PoolSigner;
+
+/// Prefix of every LP token name, e.g. "DEX LP USDC-SOL"
+pub const LP_TOKEN_NAME_PREFIX: &str = "DEX LP";
+
+/// Suffix of every LP token symbol, e.g. "USDC-SOL-LP"
+pub const LP_TOKEN_SYMBOL_SUFFIX: &str = "LP";
+
+/// Longest label kept from an underlying mint's symbol
+pub const MAX_MINT_LABEL_LEN: usize = 10;
+
+/// Number of base58 characters used when an underlying mint has no symbol
+const ABBREVIATED_MINT_LEN: usize = 4;
+
+/// Metaplex Token Metadata program, which holds the metadata of most SPL Token mints
+pub const METAPLEX_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Account key of a Metaplex `Metadata` account
+const METAPLEX_METADATA_V1_KEY: u8 = 4;
+
+/// Offset of the name in a Metaplex `Metadata` account, after its key, update authority and mint
+const METAPLEX_NAME_OFFSET: usize = 1 + 32 + 32;
+
+/// Short label of an underlying mint: the symbol of its Metaplex metadata account when one is
+/// passed and has it, else its Token-2022 metadata symbol, otherwise the first characters of its
+/// address
+/// `metadata` must be the Metaplex metadata address of the mint, which the accounts check
+pub(crate) fn mint_label(mint: &InterfaceAccount<Mint>, metadata: Option<&AccountInfo>) -> String {
+    let symbol: String = metadata
+        .and_then(read_metaplex_symbol)
+        .filter(|symbol| !symbol.is_empty())
+        .or_else(|| read_mint_symbol(&mint.to_account_info()))
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(MAX_MINT_LABEL_LEN)
+        .collect();
+
+    if symbol.is_empty() {
+        mint.key().to_string()[..ABBREVIATED_MINT_LEN].to_string()
+    } else {
+        symbol
+    }
+}
+
+/// Symbol stored in the token-metadata extension of a Token-2022 mint, if any
+/// Legacy SPL mints and mints whose metadata lives in another account return None
+fn read_mint_symbol(mint_info: &AccountInfo) -> Option<String> {
+    if *mint_info.owner != Token2022::id() {
+        return None;
+    }
+
+    let data = mint_info.try_borrow_data().ok()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data).ok()?;
+    let metadata = mint.get_variable_len_extension::<TokenMetadata>().ok()?;
+
+    Some(metadata.symbol)
+}
+
+/// Symbol stored in a Metaplex metadata account, if the account exists
+/// Metaplex pads the name and the symbol with NUL characters, which are trimmed
+fn read_metaplex_symbol(metadata_info: &AccountInfo) -> Option<String> {
+    if *metadata_info.owner != METAPLEX_METADATA_PROGRAM_ID {
+        return None;
+    }
+
+    let data = metadata_info.try_borrow_data().ok()?;
+    if data.first() != Some(&METAPLEX_METADATA_V1_KEY) {
+        return None;
+    }
+    let mut fields = data.get(METAPLEX_NAME_OFFSET..)?;
+    let _name = String::deserialize(&mut fields).ok()?;
+    let symbol = String::deserialize(&mut fields).ok()?;
+
+    Some(symbol.trim_end_matches('\0').to_string())
+}
+
+/// Name and symbol of the LP token of a pair, derived from the underlying mint labels
+pub(crate) fn lp_token_name_and_symbol(label_a: &str, label_b: &str) -> (String, String) {
+    (
+        format!("{} {}-{}", LP_TOKEN_NAME_PREFIX, label_a, label_b),
+        format!("{}-{}-{}", label_a, label_b, LP_TOKEN_SYMBOL_SUFFIX),
+    )
+}
+
+/// Writes the token metadata into the LP mint, with the pool as update authority
+/// The mint is topped up with the rent needed for the metadata before the CPI reallocates it
+pub(crate) fn initialize_lp_token_metadata<'info>(
+    lp_token_program: &Program<'info, Token2022>,
+    lp_token_mint: &InterfaceAccount<'info, Mint>,
+    pool: &PoolSigner<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    name: String,
+    symbol: String,
+) -> Result<()> {
+    let metadata = TokenMetadata {
+        update_authority: OptionalNonZeroPubkey(pool.key()),
+        mint: lp_token_mint.key(),
+        name: name.clone(),
+        symbol: symbol.clone(),
+        uri: String::new(),
+        additional_metadata: vec![],
+    };
+
+    // Pay for the space the metadata will take in the mint account
+    let mint_info = lp_token_mint.to_account_info();
+    let new_size = mint_info.data_len() + metadata.tlv_size_of()?;
+    let lamports_needed = Rent::get()?
+        .minimum_balance(new_size)
+        .saturating_sub(mint_info.lamports());
+
+    if lamports_needed > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: mint_info.clone(),
+                },
+            ),
+            lamports_needed,
+        )?;
+    }
+
+    // Create the PDA signer, the pool is the mint authority
+    let pool_seeds = pool.seeds();
+    let signer = &[&pool_seeds[..]];
+
+    token_metadata_initialize(
+        CpiContext::new_with_signer(
+            lp_token_program.to_account_info(),
+            TokenMetadataInitialize {
+                program_id: lp_token_program.to_account_info(),
+                metadata: mint_info.clone(),
+                update_authority: pool.to_account_info(),
+                mint_authority: pool.to_account_info(),
+                mint: mint_info,
+            },
+            signer,
+        ),
+        name,
+        symbol,
+        String::new(),
+    )
+}
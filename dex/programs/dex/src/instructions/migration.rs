@@ -0,0 +1,170 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use super::{DexError, DexState, DynamicFee, LiquidityPool, ProtocolFeeMode};
+
+/// Zeroed bytes at the end of `DexState` and `LiquidityPool`, for fields added later
+pub const RESERVED_SPACE: usize = 64;
+
+/// Sizes of the pool layouts, discriminator excluded: the first release (v0), then the current
+/// one. Accounts are told apart by their size, so no two layouts may share one
+const POOL_LAYOUT_LENS: [usize; 2] = [LiquidityPool::LEN_V0, LiquidityPool::LEN];
+
+/// Sizes of the DEX state layouts, discriminator excluded, like POOL_LAYOUT_LENS
+const DEX_STATE_LAYOUT_LENS: [usize; 2] = [DexState::LEN_V0, DexState::LEN];
+
+const _: () = assert!(distinct_lens(&POOL_LAYOUT_LENS));
+const _: () = assert!(distinct_lens(&DEX_STATE_LAYOUT_LENS));
+
+/// Returns true if no two layouts have the same size
+const fn distinct_lens(lens: &[usize]) -> bool {
+    let mut i = 0;
+    while i < lens.len() {
+        let mut j = i + 1;
+        while j < lens.len() {
+            if lens[i] == lens[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Pool of the first release (v0), without version
+#[derive(AnchorDeserialize)]
+struct LiquidityPoolV0 {
+    token_a_mint: Pubkey,
+    token_b_mint: Pubkey,
+    token_a_account: Pubkey,
+    token_b_account: Pubkey,
+    lp_token_mint: Pubkey,
+    bump: u8,
+    total_liquidity: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    protocol_fees_token_a: u64,
+    protocol_fees_token_b: u64,
+}
+
+/// Fields added since v0 start zeroed, except that v0 pools are attributed to the admin, who may
+/// close them, keep signing with their pair-only PDA seeds and stay unlisted
+impl From<LiquidityPoolV0> for LiquidityPool {
+    fn from(pool: LiquidityPoolV0) -> Self {
+        LiquidityPool {
+            creator: crate::ADMIN_PUBKEY,
+            token_a_mint: pool.token_a_mint,
+            token_b_mint: pool.token_b_mint,
+            token_a_account: pool.token_a_account,
+            token_b_account: pool.token_b_account,
+            lp_token_mint: pool.lp_token_mint,
+            token_a_transfer_hook: Pubkey::default(),
+            token_b_transfer_hook: Pubkey::default(),
+            total_liquidity: pool.total_liquidity,
+            fee_numerator: pool.fee_numerator,
+            fee_denominator: pool.fee_denominator,
+            protocol_fees_token_a: pool.protocol_fees_token_a,
+            protocol_fees_token_b: pool.protocol_fees_token_b,
+            registry_index: LiquidityPool::UNLISTED_REGISTRY_INDEX,
+            created_at: 0,
+            created_slot: 0,
+            dynamic_fee: DynamicFee::default(),
+            fee_tier_index: LiquidityPool::LEGACY_FEE_TIER_INDEX,
+            bump: pool.bump,
+            protocol_fee_percentage: pool.protocol_fee_percentage,
+            version: LiquidityPool::VERSION,
+            padding: [0; 3],
+            k_last: [0; 16],
+            protocol_fee_mode: ProtocolFeeMode::Accrued as u8,
+            reserved: [0; RESERVED_SPACE - 16 - 1],
+        }
+    }
+}
+
+/// Checks that account data is an account of the type of `discriminator` in an earlier layout
+fn check_previous_layout(data: &[u8], discriminator: &[u8], current_len: usize) -> Result<()> {
+    require!(
+        data.len() >= 8 && data[..8] == *discriminator,
+        DexError::UnknownAccountLayout
+    );
+    require!(
+        data.len() != 8 + current_len,
+        DexError::AccountAlreadyMigrated
+    );
+
+    Ok(())
+}
+
+/// Upgrades the data of a pool account, discriminator included, to the current layout
+/// v0 pools are decoded through Borsh and rewritten zero-copy
+pub fn migrate_pool_data(data: &[u8]) -> Result<Vec<u8>> {
+    check_previous_layout(data, LiquidityPool::DISCRIMINATOR, LiquidityPool::LEN)?;
+    require!(
+        data.len() == 8 + LiquidityPool::LEN_V0,
+        DexError::UnknownAccountLayout
+    );
+
+    let pool_v0 = LiquidityPoolV0::deserialize(&mut &data[8..])
+        .map_err(|_| error!(DexError::UnknownAccountLayout))?;
+    let pool = LiquidityPool::from(pool_v0);
+
+    let mut migrated = Vec::with_capacity(8 + LiquidityPool::LEN);
+    migrated.extend_from_slice(LiquidityPool::DISCRIMINATOR);
+    migrated.extend_from_slice(bytemuck::bytes_of(&pool));
+
+    Ok(migrated)
+}
+
+/// Upgrades the data of a DEX state account, discriminator included, to the current layout
+/// The current layout only appended fields to v0, so v0 is a prefix of it and the added fields
+/// read as zeros
+pub fn migrate_dex_state_data(data: &[u8]) -> Result<Vec<u8>> {
+    check_previous_layout(data, DexState::DISCRIMINATOR, DexState::LEN)?;
+    require!(
+        data.len() == 8 + DexState::LEN_V0,
+        DexError::UnknownAccountLayout
+    );
+
+    let mut padded = data.to_vec();
+    padded.resize(8 + DexState::LEN, 0);
+
+    let mut dex_state = DexState::try_deserialize(&mut &padded[..])?;
+    dex_state.version = DexState::VERSION;
+
+    let mut migrated = Vec::with_capacity(padded.len());
+    dex_state.try_serialize(&mut migrated)?;
+
+    Ok(migrated)
+}
+
+/// Resizes a program account to hold `migrated_data` and writes it, `payer` topping up the rent
+pub(crate) fn write_migrated_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    migrated_data: &[u8],
+) -> Result<()> {
+    let missing_rent = Rent::get()?
+        .minimum_balance(migrated_data.len())
+        .saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            missing_rent,
+        )?;
+    }
+
+    account.realloc(migrated_data.len(), false)?;
+    account
+        .try_borrow_mut_data()?
+        .copy_from_slice(migrated_data);
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::{
+        spl_token_2022::{
+            extension::{
+                transfer_hook, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+            },
+            state::Mint as MintState,
+        },
+        Token2022,
+    },
+    token_interface::Mint,
+};
+
+use super::{DexError, ExtensionAllowlist};
+
+/// Maximum number of extension types governance can allow on top of the defaults
+pub const MAX_ALLOWED_EXTENSIONS: usize = 16;
+
+/// Maximum number of mints governance can exempt from the extension checks
+pub const MAX_TRUSTED_MINTS: usize = 16;
+
+/// Mint extensions accepted without governance approval: none of them can move tokens out of
+/// a pool vault, freeze it or hide its balance
+/// Permanent delegate, non-transferable, default account state, confidential transfers,
+/// mint close authority and any future extension need an allowlist entry, transfer hooks need
+/// their program to be allowlisted
+pub const DEFAULT_ALLOWED_EXTENSIONS: [ExtensionType; 8] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
+/// Checks that every extension of a pool mint is allowed by default or by governance
+/// Legacy SPL mints have no extensions, trusted mints skip the check entirely
+pub(crate) fn check_mint_extensions(
+    mint: &InterfaceAccount<Mint>,
+    allowlist: Option<&ExtensionAllowlist>,
+) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != Token2022::id() {
+        return Ok(());
+    }
+
+    if allowlist.is_some_and(|allowlist| allowlist.is_trusted_mint(&mint.key())) {
+        return Ok(());
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    for extension in mint_state.get_extension_types()? {
+        let allowed = match extension {
+            // Hooks run arbitrary code on every pool transfer: the program itself must be accepted
+            ExtensionType::TransferHook => match transfer_hook::get_program_id(&mint_state) {
+                Some(program_id) => allowlist
+                    .is_some_and(|allowlist| allowlist.is_allowed_hook_program(&program_id)),
+                None => true,
+            },
+            _ => {
+                DEFAULT_ALLOWED_EXTENSIONS.contains(&extension)
+                    || allowlist
+                        .is_some_and(|allowlist| allowlist.is_allowed_extension(extension.into()))
+            }
+        };
+
+        if !allowed {
+            msg!(
+                "Mint {} has disallowed extension {:?}",
+                mint.key(),
+                extension
+            );
+            return err!(DexError::MintExtensionNotAllowed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates an extension type id passed to the allowlist instructions
+/// Transfer hooks are allowed per program, see `set_allowed_hook_program`
+pub(crate) fn parse_extension_type(extension_type: u16) -> Result<ExtensionType> {
+    match ExtensionType::try_from(extension_type) {
+        Ok(ExtensionType::Uninitialized | ExtensionType::TransferHook) | Err(_) => {
+            err!(DexError::InvalidExtensionType)
+        }
+        Ok(extension) => Ok(extension),
+    }
+}
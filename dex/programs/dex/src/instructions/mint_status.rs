@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use super::{DexError, DexState, MintStatus};
+
+/// Governance's verdict on a token
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MintListing {
+    /// Never reviewed, the same as having no status account
+    #[default]
+    Unknown,
+    /// Reviewed and accepted, required for new pools when the DEX only accepts allowed mints
+    Allowed,
+    /// Blocked from new pools and from trading in existing ones
+    Denied,
+}
+
+/// Reads the listing of a mint from its status account
+/// The address is checked by the accounts' seeds: a status governance never set is an empty
+/// account, which reads as Unknown
+pub(crate) fn mint_listing(mint_status: &AccountInfo) -> Result<MintListing> {
+    if mint_status.owner != &crate::ID || mint_status.data_is_empty() {
+        return Ok(MintListing::Unknown);
+    }
+
+    let data = mint_status.try_borrow_data()?;
+    Ok(MintStatus::try_deserialize(&mut &data[..])?.status)
+}
+
+/// Checks that a mint may be paired in a new pool
+/// Denied mints never are, and only allowed mints are once the DEX requires them
+pub(crate) fn check_pool_mint(dex_state: &DexState, mint_status: &AccountInfo) -> Result<()> {
+    let listing = mint_listing(mint_status)?;
+    require!(listing != MintListing::Denied, DexError::MintDenied);
+    require!(
+        !dex_state.require_allowed_mints || listing == MintListing::Allowed,
+        DexError::MintNotAllowed
+    );
+
+    Ok(())
+}
+
+/// Checks that a mint may still be traded, only denied mints are blocked
+pub(crate) fn check_swap_mint(mint_status: &AccountInfo) -> Result<()> {
+    require!(
+        mint_listing(mint_status)? != MintListing::Denied,
+        DexError::MintDenied
+    );
+
+    Ok(())
+}
@@ -1,13 +1,45 @@
+mod close_pool;
+mod creator_permissions;
 mod deposit;
+mod dynamic_fee;
+mod farm;
+mod lp_metadata;
+mod migration;
+mod mint_extensions;
+mod mint_status;
+mod pool_registry;
+mod pool_signer;
+mod protocol_fee;
 mod swap;
+mod transfer_fee;
+mod transfer_hook;
 mod withdrawal;
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
+    token_2022::Token2022,
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
+use close_pool::*;
+pub use creator_permissions::*;
 use deposit::*;
+use dex_math::{
+    calculate_fee_breakdown, calculate_initial_liquidity, calculate_input_amount,
+    calculate_input_amount_before_fee, calculate_output_amount, calculate_proportional_liquidity,
+    calculate_protocol_fee_liquidity, calculate_withdrawal_amounts,
+};
+pub use dynamic_fee::*;
+pub use farm::*;
+pub use lp_metadata::*;
+pub use migration::*;
+pub use mint_extensions::*;
+pub use mint_status::*;
+pub use pool_registry::*;
+use pool_signer::*;
+pub use protocol_fee::*;
 use swap::*;
+use transfer_fee::*;
+pub use transfer_hook::*;
 use withdrawal::*;
 
 use crate::DexError;
@@ -24,14 +56,7 @@ pub fn initialize_dex(
     protocol_fee_percentage: u8,
     fee_collector: Pubkey,
 ) -> Result<()> {
-    // Check that fee values are valid (non-zero numerator and numerator < denominator)
-    require!(
-        fee_numerator != 0 && fee_numerator < fee_denominator,
-        DexError::InvalidFees
-    );
-
-    // Check that protocol fee percentage is valid (0-100)
-    require!(protocol_fee_percentage <= 100, DexError::InvalidFees);
+    check_dex_fees(fee_numerator, fee_denominator, protocol_fee_percentage)?;
 
     // Initialize the dex state
     let dex_state = &mut ctx.accounts.dex_state;
@@ -41,47 +66,247 @@ pub fn initialize_dex(
     dex_state.fee_denominator = fee_denominator;
     dex_state.protocol_fee_percentage = protocol_fee_percentage;
     dex_state.fee_collector = fee_collector;
+    dex_state.version = DexState::VERSION;
 
     Ok(())
 }
 
 /*
- * Creates a new trading pair with custom LP tokens
- * Anyone can create a pool for any token pair
+ * Changes the DEX fees and fee collector set at initialization
+ * Only the admin can call this function, the pool counters are left untouched
  */
-pub fn create_liquidity_pool(ctx: Context<CreatePool>) -> Result<()> {
-    let pool = &mut ctx.accounts.pool;
+pub fn set_dex_fees(
+    ctx: Context<SetFees>,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+    fee_collector: Pubkey,
+) -> Result<()> {
+    check_dex_fees(fee_numerator, fee_denominator, protocol_fee_percentage)?;
+
     let dex_state = &mut ctx.accounts.dex_state;
+    dex_state.fee_numerator = fee_numerator;
+    dex_state.fee_denominator = fee_denominator;
+    dex_state.protocol_fee_percentage = protocol_fee_percentage;
+    dex_state.fee_collector = fee_collector;
 
-    // Initialize pool data
-    pool.token_a_mint = ctx.accounts.token_a_mint.key();
-    pool.token_b_mint = ctx.accounts.token_b_mint.key();
-    pool.token_a_account = ctx.accounts.pool_token_a.key();
-    pool.token_b_account = ctx.accounts.pool_token_b.key();
-    pool.lp_token_mint = ctx.accounts.lp_token_mint.key();
+    msg!(
+        "Fees set to {}/{}, protocol fee {}%, fee collector {}",
+        fee_numerator,
+        fee_denominator,
+        protocol_fee_percentage,
+        fee_collector
+    );
 
-    // Save the PDA bump for future references
-    pool.bump = ctx.bumps.pool;
+    Ok(())
+}
+
+/// Checks the fees of the DEX state
+fn check_dex_fees(
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_percentage: u8,
+) -> Result<()> {
+    // Check that fee values are valid (non-zero numerator and numerator < denominator)
+    require!(
+        fee_numerator != 0 && fee_numerator < fee_denominator,
+        DexError::InvalidFees
+    );
+
+    // Check that protocol fee percentage is valid (0-100)
+    require!(protocol_fee_percentage <= 100, DexError::InvalidFees);
+
+    Ok(())
+}
+
+/*
+ * Registers a new fee tier that pools can be created with
+ * Tiers are numbered in registration order, only the admin can add them
+ */
+pub fn register_fee_tier(
+    ctx: Context<AddFeeTier>,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<()> {
+    // Same rules as the global fee (non-zero numerator and numerator < denominator)
+    require!(
+        fee_numerator != 0 && fee_numerator < fee_denominator,
+        DexError::InvalidFees
+    );
+
+    let dex_state = &mut ctx.accounts.dex_state;
+    let fee_tier = &mut ctx.accounts.fee_tier;
+
+    // A pair lookup holds one pool per tier, up to MAX_POOLS_PER_PAIR
+    require!(
+        (dex_state.fee_tiers_count as usize) < MAX_POOLS_PER_PAIR,
+        DexError::TooManyFeeTiers
+    );
+
+    fee_tier.index = dex_state.fee_tiers_count;
+    fee_tier.fee_numerator = fee_numerator;
+    fee_tier.fee_denominator = fee_denominator;
+    fee_tier.bump = ctx.bumps.fee_tier;
+
+    // Increment the tiers counter in DEX state
+    dex_state.fee_tiers_count = dex_state
+        .fee_tiers_count
+        .checked_add(1)
+        .ok_or(error!(DexError::InvalidFees))?;
+
+    msg!(
+        "Fee tier {} registered: {}/{}",
+        fee_tier.index,
+        fee_numerator,
+        fee_denominator
+    );
+
+    Ok(())
+}
 
-    // Initialize liquidity
-    pool.total_liquidity = 0;
+/*
+ * Creates a new trading pair with custom LP tokens
+ * Anyone can create a pool for any token pair, one pool per fee tier
+ */
+pub fn create_liquidity_pool(ctx: Context<CreatePool>, fee_tier_index: u16) -> Result<()> {
+    // Only creators the pool creation mode accepts
+    check_pool_creator(
+        &ctx.accounts.dex_state,
+        &ctx.accounts.owner.key(),
+        ctx.accounts
+            .creator_permissions
+            .as_deref()
+            .map(|permissions| &**permissions),
+    )?;
 
-    // Copy fee settings from DEX state
-    pool.fee_numerator = dex_state.fee_numerator;
-    pool.fee_denominator = dex_state.fee_denominator;
-    pool.protocol_fee_percentage = dex_state.protocol_fee_percentage;
+    // Refuse mints governance denied, or didn't allow when it requires allowed mints
+    check_pool_mint(&ctx.accounts.dex_state, &ctx.accounts.token_a_mint_status)?;
+    check_pool_mint(&ctx.accounts.dex_state, &ctx.accounts.token_b_mint_status)?;
+
+    // Refuse mints whose extensions could drain or freeze the pool vaults
+    let extension_allowlist = ctx
+        .accounts
+        .extension_allowlist
+        .as_deref()
+        .map(|allowlist| &**allowlist);
+    check_mint_extensions(&ctx.accounts.token_a_mint, extension_allowlist)?;
+    check_mint_extensions(&ctx.accounts.token_b_mint, extension_allowlist)?;
+
+    let dex_state = &mut ctx.accounts.dex_state;
+    let fee_tier = &ctx.accounts.fee_tier;
+    let pool_key = ctx.accounts.pool.key();
+    let token_a_mint = ctx.accounts.token_a_mint.key();
+    let token_b_mint = ctx.accounts.token_b_mint.key();
+    let clock = Clock::get()?;
+
+    // The discriminator is only written when the instruction exits, the pool can't be loaded
+    // again before that: keep what the rest of the instruction needs
+    let pool_signer = {
+        let pool = &mut ctx.accounts.pool.load_init()?;
+
+        // Initialize pool data
+        pool.creator = ctx.accounts.owner.key();
+        pool.token_a_mint = token_a_mint;
+        pool.token_b_mint = token_b_mint;
+        pool.token_a_account = ctx.accounts.pool_token_a.key();
+        pool.token_b_account = ctx.accounts.pool_token_b.key();
+        pool.lp_token_mint = ctx.accounts.lp_token_mint.key();
+
+        // Save the PDA bump and fee tier (part of the seeds) for future references
+        pool.bump = ctx.bumps.pool;
+        pool.fee_tier_index = fee_tier_index;
+
+        // Initialize liquidity
+        pool.total_liquidity = 0;
+
+        // Copy fee settings from the selected tier, protocol share from DEX state
+        pool.fee_numerator = fee_tier.fee_numerator;
+        pool.fee_denominator = fee_tier.fee_denominator;
+        pool.protocol_fee_percentage = dex_state.protocol_fee_percentage;
+
+        // Initialize protocol fees, taken the way the DEX is configured for new pools
+        pool.protocol_fees_token_a = 0;
+        pool.protocol_fees_token_b = 0;
+        pool.protocol_fee_mode = dex_state.protocol_fee_mode as u8;
+
+        // Pools start with the static fee, dynamic fees are opted into by the admin
+        pool.dynamic_fee = DynamicFee::default();
+
+        // Pin the transfer hook programs that passed the extension checks
+        pool.token_a_transfer_hook =
+            get_transfer_hook_program(&ctx.accounts.token_a_mint)?.unwrap_or_default();
+        pool.token_b_transfer_hook =
+            get_transfer_hook_program(&ctx.accounts.token_b_mint)?.unwrap_or_default();
+
+        // Record when the pool was created, for analytics
+        pool.created_at = clock.unix_timestamp;
+        pool.created_slot = clock.slot;
+        pool.version = LiquidityPool::VERSION;
+
+        // The pool is listed in the registry at the position given by the pools counter
+        pool.registry_index = dex_state.pools_count;
+
+        PoolSigner::new(&ctx.accounts.pool, pool)
+    };
+    // Name the LP token after the pair so wallets can display it
+    let (lp_token_name, lp_token_symbol) = lp_token_name_and_symbol(
+        &mint_label(
+            &ctx.accounts.token_a_mint,
+            ctx.accounts.token_a_metadata.as_deref(),
+        ),
+        &mint_label(
+            &ctx.accounts.token_b_mint,
+            ctx.accounts.token_b_metadata.as_deref(),
+        ),
+    );
+    initialize_lp_token_metadata(
+        &ctx.accounts.lp_token_program,
+        &ctx.accounts.lp_token_mint,
+        &pool_signer,
+        &ctx.accounts.owner,
+        &ctx.accounts.system_program,
+        lp_token_name.clone(),
+        lp_token_symbol.clone(),
+    )?;
 
-    // Initialize protocol fees
-    pool.protocol_fees_token_a = 0;
-    pool.protocol_fees_token_b = 0;
+    // List the pool in the registry and in the lookup of its mint pair
+    ctx.accounts.registry_page.push_entry(
+        dex_state.key(),
+        dex_state.pools_count,
+        ctx.bumps.registry_page,
+        PoolRegistryEntry {
+            pool: pool_key,
+            token_a_mint,
+            token_b_mint,
+            created_slot: clock.slot,
+            closed: false,
+        },
+    )?;
+    ctx.accounts.pool_pair.add_pool(
+        sorted_pair_mints(token_a_mint, token_b_mint),
+        ctx.bumps.pool_pair,
+        pool_key,
+        fee_tier_index,
+    )?;
 
     // Increment the pools counter in DEX state
     dex_state.pools_count += 1;
 
-    msg!("Pool created: {}", pool.key());
-    msg!("Token A Mint: {}", pool.token_a_mint);
-    msg!("Token B Mint: {}", pool.token_b_mint);
-    msg!("LP Token Mint: {}", pool.lp_token_mint);
+    msg!("Pool created: {}", pool_key);
+    msg!("Token A Mint: {}", token_a_mint);
+    msg!("Token B Mint: {}", token_b_mint);
+    msg!(
+        "LP Token Mint: {} ({}, {})",
+        ctx.accounts.lp_token_mint.key(),
+        lp_token_name,
+        lp_token_symbol
+    );
+    msg!(
+        "Fee tier {}: {}/{}",
+        fee_tier_index,
+        fee_tier.fee_numerator,
+        fee_tier.fee_denominator
+    );
     msg!(
         "Owner LP Token Account: {}",
         ctx.accounts.owner_lp_token.key()
@@ -94,13 +319,12 @@ pub fn create_liquidity_pool(ctx: Context<CreatePool>) -> Result<()> {
  * Adds liquidity to a pool and mints LP tokens
  * The first deposit sets the initial price ratio
  */
-pub fn perform_liquidity_deposit(
-    ctx: Context<DepositLiquidity>,
+pub fn perform_liquidity_deposit<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DepositLiquidity<'info>>,
     token_a_amount: u64,
     token_b_amount: u64,
 ) -> Result<()> {
     // Get references to all accounts
-    let pool = &mut ctx.accounts.pool;
     let pool_token_a = &mut ctx.accounts.pool_token_a;
     let pool_token_b = &mut ctx.accounts.pool_token_b;
     let lp_token_mint = &mut ctx.accounts.lp_token_mint;
@@ -111,24 +335,57 @@ pub fn perform_liquidity_deposit(
     let user_lp_token = &ctx.accounts.user_lp_token;
     let owner = &ctx.accounts.owner;
     let token_program = &ctx.accounts.token_program;
+    let lp_token_program = &ctx.accounts.lp_token_program;
+
+    // The pool data can't stay borrowed while the pool signs CPIs
+    let (pool_signer, protocol_fee_liquidity, total_liquidity, reserve_a, reserve_b) = {
+        let pool = ctx.accounts.pool.load()?;
+
+        // Refuse transfers through a hook program the pool did not accept
+        check_pool_transfer_hook(&pool, token_a_mint)?;
+        check_pool_transfer_hook(&pool, token_b_mint)?;
+
+        // Get current pool reserves, leaving out the protocol fees waiting to be collected
+        let (reserve_a, reserve_b) = pool
+            .lp_reserves(pool_token_a.amount, pool_token_b.amount)
+            .ok_or(error!(DexError::InsufficientLiquidity))?;
+
+        // The protocol's LP tokens for the fees earned so far are minted first, the deposit is
+        // valued against the supply including them
+        let protocol_fee_liquidity = pool
+            .protocol_fee_liquidity(reserve_a, reserve_b)
+            .map_err(DexError::from)?;
+
+        (
+            PoolSigner::new(&ctx.accounts.pool, &pool),
+            protocol_fee_liquidity,
+            pool.total_liquidity
+                .checked_add(protocol_fee_liquidity)
+                .ok_or(error!(DexError::InsufficientLiquidity))?,
+            reserve_a,
+            reserve_b,
+        )
+    };
 
-    // Get current pool reserves
-    let reserve_a = pool_token_a.amount;
-    let reserve_b = pool_token_b.amount;
+    // Value the deposit on what the pool actually receives after Token-2022 transfer fees
+    let net_token_a_amount = calculate_amount_received(token_a_mint, token_a_amount)?;
+    let net_token_b_amount = calculate_amount_received(token_b_mint, token_b_amount)?;
 
     // Calculate LP tokens to mint based on current pool state
-    let lp_tokens_to_mint = if pool.total_liquidity == 0 {
+    let lp_tokens_to_mint = if total_liquidity == 0 {
         // For first deposit, calculate using geometric mean
-        calculate_initial_liquidity(token_a_amount, token_b_amount)?
+        calculate_initial_liquidity(net_token_a_amount, net_token_b_amount)
+            .map_err(DexError::from)?
     } else {
         // For subsequent deposits, calculate proportionally
         calculate_proportional_liquidity(
-            token_a_amount,
-            token_b_amount,
+            net_token_a_amount,
+            net_token_b_amount,
             reserve_a,
             reserve_b,
-            pool.total_liquidity,
-        )?
+            total_liquidity,
+        )
+        .map_err(DexError::from)?
     };
 
     // Transfer both tokens from user to pool
@@ -139,6 +396,7 @@ pub fn perform_liquidity_deposit(
         user_token_a,
         pool_token_a,
         owner,
+        ctx.remaining_accounts,
         token_a_amount,
     )?;
 
@@ -149,23 +407,42 @@ pub fn perform_liquidity_deposit(
         user_token_b,
         pool_token_b,
         owner,
+        ctx.remaining_accounts,
         token_b_amount,
     )?;
 
+    // Mint the protocol's LP tokens to the fee collector
+    mint_protocol_fee_liquidity(
+        lp_token_program,
+        lp_token_mint,
+        &ctx.accounts.dex_state.fee_collector,
+        ctx.accounts.fee_collector_lp_token.as_ref(),
+        &pool_signer,
+        protocol_fee_liquidity,
+    )?;
+
     // Mint LP tokens to user
     mint_lp_tokens_to_user(
-        token_program,
+        lp_token_program,
         lp_token_mint,
-        user_lp_token,
-        pool,
+        &user_lp_token.to_account_info(),
+        &pool_signer,
         lp_tokens_to_mint,
     )?;
 
-    // Update pool total liquidity
-    pool.total_liquidity = pool
-        .total_liquidity
+    // Update pool total liquidity, and the product of the reserves fees are measured from
+    let mut pool = ctx.accounts.pool.load_mut()?;
+    pool.total_liquidity = total_liquidity
         .checked_add(lp_tokens_to_mint)
         .ok_or(error!(DexError::InsufficientLiquidity))?;
+    pool.update_k_last(
+        reserve_a
+            .checked_add(net_token_a_amount)
+            .ok_or(error!(DexError::InsufficientLiquidity))?,
+        reserve_b
+            .checked_add(net_token_b_amount)
+            .ok_or(error!(DexError::InsufficientLiquidity))?,
+    );
 
     msg!(
         "Deposited {} token A and {} token B for {} LP tokens",
@@ -181,9 +458,11 @@ pub fn perform_liquidity_deposit(
  * Removes liquidity from a pool by burning LP tokens
  * Returns tokens proportional to the share of the pool being withdrawn
  */
-pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount: u64) -> Result<()> {
+pub fn perform_liquidity_withdrawal<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawLiquidity<'info>>,
+    lp_amount: u64,
+) -> Result<()> {
     // Get references to all accounts
-    let pool = &mut ctx.accounts.pool;
     let pool_token_a = &mut ctx.accounts.pool_token_a;
     let pool_token_b = &mut ctx.accounts.pool_token_b;
     let lp_token_mint = &mut ctx.accounts.lp_token_mint;
@@ -194,10 +473,37 @@ pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount:
     let user_lp_token = &ctx.accounts.user_lp_token;
     let owner = &ctx.accounts.owner;
     let token_program = &ctx.accounts.token_program;
+    let lp_token_program = &ctx.accounts.lp_token_program;
+
+    // The pool data can't stay borrowed while the pool signs CPIs
+    let (pool_signer, protocol_fee_liquidity, total_liquidity, reserve_a, reserve_b) = {
+        let pool = ctx.accounts.pool.load()?;
+
+        // Refuse transfers through a hook program the pool did not accept
+        check_pool_transfer_hook(&pool, token_a_mint)?;
+        check_pool_transfer_hook(&pool, token_b_mint)?;
+
+        // Get current pool reserves, leaving out the protocol fees waiting to be collected
+        let (reserve_a, reserve_b) = pool
+            .lp_reserves(pool_token_a.amount, pool_token_b.amount)
+            .ok_or(error!(DexError::InsufficientLiquidity))?;
 
-    // Get current pool reserves
-    let reserve_a = pool_token_a.amount;
-    let reserve_b = pool_token_b.amount;
+        // The protocol's LP tokens for the fees earned so far are minted first, so that they
+        // dilute the withdrawn share too
+        let protocol_fee_liquidity = pool
+            .protocol_fee_liquidity(reserve_a, reserve_b)
+            .map_err(DexError::from)?;
+
+        (
+            PoolSigner::new(&ctx.accounts.pool, &pool),
+            protocol_fee_liquidity,
+            pool.total_liquidity
+                .checked_add(protocol_fee_liquidity)
+                .ok_or(error!(DexError::InsufficientLiquidity))?,
+            reserve_a,
+            reserve_b,
+        )
+    };
 
     // Ensure user has enough LP tokens
     require!(
@@ -207,17 +513,28 @@ pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount:
 
     // Ensure pool has enough total liquidity
     require!(
-        pool.total_liquidity >= lp_amount,
+        total_liquidity >= lp_amount,
         DexError::InsufficientLiquidity
     );
 
     // Calculate token amounts to withdraw based on user's share
     let (token_a_amount, token_b_amount) =
-        calculate_withdrawal_amounts(lp_amount, reserve_a, reserve_b, pool.total_liquidity)?;
+        calculate_withdrawal_amounts(lp_amount, reserve_a, reserve_b, total_liquidity)
+            .map_err(DexError::from)?;
+
+    // Mint the protocol's LP tokens to the fee collector
+    mint_protocol_fee_liquidity(
+        lp_token_program,
+        lp_token_mint,
+        &ctx.accounts.dex_state.fee_collector,
+        ctx.accounts.fee_collector_lp_token.as_ref(),
+        &pool_signer,
+        protocol_fee_liquidity,
+    )?;
 
     // Burn user's LP tokens
     burn_lp_tokens(
-        token_program,
+        lp_token_program,
         lp_token_mint,
         user_lp_token,
         owner,
@@ -231,7 +548,8 @@ pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount:
         token_program,
         pool_token_a,
         user_token_a,
-        pool,
+        &pool_signer,
+        ctx.remaining_accounts,
         token_a_amount,
     )?;
 
@@ -241,15 +559,17 @@ pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount:
         token_program,
         pool_token_b,
         user_token_b,
-        pool,
+        &pool_signer,
+        ctx.remaining_accounts,
         token_b_amount,
     )?;
 
-    // Update pool total liquidity
-    pool.total_liquidity = pool
-        .total_liquidity
+    // Update pool total liquidity, and the product of the reserves fees are measured from
+    let mut pool = ctx.accounts.pool.load_mut()?;
+    pool.total_liquidity = total_liquidity
         .checked_sub(lp_amount)
         .ok_or(error!(DexError::InsufficientLiquidity))?;
+    pool.update_k_last(reserve_a - token_a_amount, reserve_b - token_b_amount);
 
     msg!(
         "Withdrawn {} token A and {} token B by burning {} LP tokens",
@@ -264,13 +584,13 @@ pub fn perform_liquidity_withdrawal(ctx: Context<WithdrawLiquidity>, lp_amount:
 /*
  * Swaps one token for another using the constant product formula
  */
-pub fn swap_tokens(
-    ctx: Context<Swap>,
+pub fn swap_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
     input_amount: u64,
     minimum_output_amount: u64,
 ) -> Result<()> {
     // Get references to all accounts
-    let pool = &mut ctx.accounts.pool;
+    let mut pool = ctx.accounts.pool.load_mut()?;
     let source_mint = &ctx.accounts.source_mint;
     let destination_mint = &ctx.accounts.destination_mint;
     let user_source_token = &ctx.accounts.user_source_token;
@@ -278,8 +598,16 @@ pub fn swap_tokens(
     let owner = &ctx.accounts.owner;
     let token_program = &ctx.accounts.token_program;
 
+    // Refuse transfers through a hook program the pool did not accept
+    check_pool_transfer_hook(&pool, source_mint)?;
+    check_pool_transfer_hook(&pool, destination_mint)?;
+
+    // Refuse trading a mint governance denied
+    check_swap_mint(&ctx.accounts.source_mint_status)?;
+    check_swap_mint(&ctx.accounts.destination_mint_status)?;
+
     // Determine which token is being swapped in/out
-    let is_source_token_a = is_token_a(pool, &source_mint.key());
+    let is_source_token_a = is_token_a(&pool, &source_mint.key());
 
     // Get pool token accounts based on source/destination
     let (pool_source_token, pool_destination_token) = if is_source_token_a {
@@ -288,33 +616,48 @@ pub fn swap_tokens(
         (&ctx.accounts.pool_token_b, &ctx.accounts.pool_token_a)
     };
 
-    // Get current reserves
-    let source_reserve = pool_source_token.amount;
-    let destination_reserve = pool_destination_token.amount;
+    // Get current reserves, leaving out the protocol fees waiting to be collected
+    let (reserve_a, reserve_b) = pool
+        .lp_reserves(
+            ctx.accounts.pool_token_a.amount,
+            ctx.accounts.pool_token_b.amount,
+        )
+        .ok_or(error!(DexError::InsufficientLiquidity))?;
+    let (source_reserve, destination_reserve) = if is_source_token_a {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+
+    // Price on what the pool actually receives: Token-2022 transfer fees are withheld in transit
+    let net_input_amount = calculate_amount_received(source_mint, input_amount)?;
+
+    // Resolve the fee rate for this swap: either the static pool fee or the volatility-based fee,
+    // which includes the price impact of the whole input moving along the curve
+    let curve_output_amount =
+        calculate_output_amount(net_input_amount, source_reserve, destination_reserve, 0, 1)
+            .map_err(DexError::from)?;
+    let (fee_numerator, fee_denominator) = resolve_swap_fee(
+        &mut pool,
+        is_source_token_a,
+        (source_reserve, destination_reserve),
+        (net_input_amount, curve_output_amount),
+    )?;
 
     // Calculate fee breakdown (total fee and protocol portion)
     let (total_fee, protocol_fee) = calculate_fee_breakdown(
-        input_amount,
-        pool.fee_numerator,
-        pool.fee_denominator,
-        pool.protocol_fee_percentage,
-    )?;
+        net_input_amount,
+        fee_numerator,
+        fee_denominator,
+        pool.accrued_protocol_fee_percentage(),
+    )
+    .map_err(DexError::from)?;
 
     // Update accumulated protocol fees
-    if is_source_token_a {
-        pool.protocol_fees_token_a = pool
-            .protocol_fees_token_a
-            .checked_add(protocol_fee)
-            .ok_or(error!(DexError::InsufficientLiquidity))?;
-    } else {
-        pool.protocol_fees_token_b = pool
-            .protocol_fees_token_b
-            .checked_add(protocol_fee)
-            .ok_or(error!(DexError::InsufficientLiquidity))?;
-    }
+    accrue_protocol_fee(&mut pool, is_source_token_a, protocol_fee)?;
 
     // Calculate input amount after fee
-    let input_amount_with_fee = input_amount
+    let input_amount_with_fee = net_input_amount
         .checked_sub(total_fee)
         .ok_or(error!(DexError::InsufficientLiquidity))?;
 
@@ -326,14 +669,148 @@ pub fn swap_tokens(
         destination_reserve,
         0, // No additional fee should be charged
         1,
+    )
+    .map_err(DexError::from)?;
+
+    // Check slippage tolerance against what reaches the user after the outgoing transfer fee
+    let received_amount = calculate_amount_received(destination_mint, output_amount)?;
+    require!(
+        received_amount >= minimum_output_amount,
+        DexError::SlippageExceeded
+    );
+
+    // Release the pool data, the pool signs the outgoing transfer
+    let pool_signer = PoolSigner::new(&ctx.accounts.pool, &pool);
+    drop(pool);
+
+    // Perform the swap:
+    // 1. Transfer source tokens from user to pool
+    transfer_source_tokens_to_pool(
+        source_mint,
+        token_program,
+        user_source_token,
+        pool_source_token,
+        owner,
+        ctx.remaining_accounts,
+        input_amount,
+    )?;
+
+    // 2. Transfer destination tokens from pool to user
+    transfer_destination_tokens_to_user(
+        destination_mint,
+        token_program,
+        pool_destination_token,
+        user_destination_token,
+        &pool_signer,
+        ctx.remaining_accounts,
+        output_amount,
+    )?;
+
+    // Log swap details
+    msg!(
+        "Swapped {} tokens for {} tokens (fee: {}/{}, protocol fee: {})",
+        input_amount,
+        received_amount,
+        fee_numerator,
+        fee_denominator,
+        protocol_fee
+    );
+
+    Ok(())
+}
+
+/*
+ * Swaps as little input as needed for the user to receive exactly `output_amount`
+ * Outgoing transfer fees are grossed up so the user is credited the full amount
+ */
+pub fn swap_tokens_exact_output<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+    output_amount: u64,
+    maximum_input_amount: u64,
+) -> Result<()> {
+    // Get references to all accounts
+    let mut pool = ctx.accounts.pool.load_mut()?;
+    let source_mint = &ctx.accounts.source_mint;
+    let destination_mint = &ctx.accounts.destination_mint;
+    let user_source_token = &ctx.accounts.user_source_token;
+    let user_destination_token = &ctx.accounts.user_destination_token;
+    let owner = &ctx.accounts.owner;
+    let token_program = &ctx.accounts.token_program;
+
+    // Refuse transfers through a hook program the pool did not accept
+    check_pool_transfer_hook(&pool, source_mint)?;
+    check_pool_transfer_hook(&pool, destination_mint)?;
+
+    // Refuse trading a mint governance denied
+    check_swap_mint(&ctx.accounts.source_mint_status)?;
+    check_swap_mint(&ctx.accounts.destination_mint_status)?;
+
+    // Determine which token is being swapped in/out
+    let is_source_token_a = is_token_a(&pool, &source_mint.key());
+
+    // Get pool token accounts based on source/destination
+    let (pool_source_token, pool_destination_token) = if is_source_token_a {
+        (&ctx.accounts.pool_token_a, &ctx.accounts.pool_token_b)
+    } else {
+        (&ctx.accounts.pool_token_b, &ctx.accounts.pool_token_a)
+    };
+
+    // Get current reserves, leaving out the protocol fees waiting to be collected
+    let (reserve_a, reserve_b) = pool
+        .lp_reserves(
+            ctx.accounts.pool_token_a.amount,
+            ctx.accounts.pool_token_b.amount,
+        )
+        .ok_or(error!(DexError::InsufficientLiquidity))?;
+    let (source_reserve, destination_reserve) = if is_source_token_a {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+
+    // The pool sends enough to cover the destination mint's transfer fee
+    let gross_output_amount = calculate_amount_to_send(destination_mint, output_amount)?;
+
+    // Work back from the output: curve input, then pool fee, then the source transfer fee
+    let input_amount_with_fee =
+        calculate_input_amount(gross_output_amount, source_reserve, destination_reserve)
+            .map_err(DexError::from)?;
+
+    // Resolve the fee rate for this swap: either the static pool fee or the volatility-based fee,
+    // which includes the price impact of the swap along the curve
+    let (fee_numerator, fee_denominator) = resolve_swap_fee(
+        &mut pool,
+        is_source_token_a,
+        (source_reserve, destination_reserve),
+        (input_amount_with_fee, gross_output_amount),
     )?;
+    let net_input_amount =
+        calculate_input_amount_before_fee(input_amount_with_fee, fee_numerator, fee_denominator)
+            .map_err(DexError::from)?;
+    let input_amount = calculate_amount_to_send(source_mint, net_input_amount)?;
 
     // Check slippage tolerance
     require!(
-        output_amount >= minimum_output_amount,
+        input_amount <= maximum_input_amount,
         DexError::SlippageExceeded
     );
 
+    // Calculate fee breakdown on what the pool receives, like an exact input swap
+    let (_, protocol_fee) = calculate_fee_breakdown(
+        net_input_amount,
+        fee_numerator,
+        fee_denominator,
+        pool.accrued_protocol_fee_percentage(),
+    )
+    .map_err(DexError::from)?;
+
+    // Update accumulated protocol fees
+    accrue_protocol_fee(&mut pool, is_source_token_a, protocol_fee)?;
+
+    // Release the pool data, the pool signs the outgoing transfer
+    let pool_signer = PoolSigner::new(&ctx.accounts.pool, &pool);
+    drop(pool);
+
     // Perform the swap:
     // 1. Transfer source tokens from user to pool
     transfer_source_tokens_to_pool(
@@ -342,6 +819,7 @@ pub fn swap_tokens(
         user_source_token,
         pool_source_token,
         owner,
+        ctx.remaining_accounts,
         input_amount,
     )?;
 
@@ -351,15 +829,18 @@ pub fn swap_tokens(
         token_program,
         pool_destination_token,
         user_destination_token,
-        pool,
-        output_amount,
+        &pool_signer,
+        ctx.remaining_accounts,
+        gross_output_amount,
     )?;
 
     // Log swap details
     msg!(
-        "Swapped {} tokens for {} tokens (protocol fee: {})",
+        "Swapped {} tokens for exactly {} tokens (fee: {}/{}, protocol fee: {})",
         input_amount,
         output_amount,
+        fee_numerator,
+        fee_denominator,
         protocol_fee
     );
 
@@ -367,8 +848,9 @@ pub fn swap_tokens(
 }
 
 /// Collects protocol fees from a pool and sends them to the fee collector account
-pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
-    let pool = &mut ctx.accounts.pool;
+pub fn collect_protocol_fees<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CollectProtocolFees<'info>>,
+) -> Result<()> {
     let token_a_mint = &ctx.accounts.token_a_mint;
     let token_b_mint = &ctx.accounts.token_b_mint;
     let pool_token_a = &ctx.accounts.pool_token_a;
@@ -377,13 +859,27 @@ pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
     let fee_collector_token_b = &ctx.accounts.fee_collector_token_b;
     let token_program = &ctx.accounts.token_program;
 
-    // Get accumulated protocol fees
-    let token_a_fee_amount = pool.protocol_fees_token_a;
-    let token_b_fee_amount = pool.protocol_fees_token_b;
+    let (pool_signer, token_a_fee_amount, token_b_fee_amount) = {
+        let mut pool = ctx.accounts.pool.load_mut()?;
 
-    // Reset protocol fee accumulators (do this before transfers to prevent reentrancy issues)
-    pool.protocol_fees_token_a = 0;
-    pool.protocol_fees_token_b = 0;
+        // Refuse transfers through a hook program the pool did not accept
+        check_pool_transfer_hook(&pool, token_a_mint)?;
+        check_pool_transfer_hook(&pool, token_b_mint)?;
+
+        // Get accumulated protocol fees
+        let token_a_fee_amount = pool.protocol_fees_token_a;
+        let token_b_fee_amount = pool.protocol_fees_token_b;
+
+        // Reset protocol fee accumulators (do this before transfers to prevent reentrancy issues)
+        pool.protocol_fees_token_a = 0;
+        pool.protocol_fees_token_b = 0;
+
+        (
+            PoolSigner::new(&ctx.accounts.pool, &pool),
+            token_a_fee_amount,
+            token_b_fee_amount,
+        )
+    };
 
     // Transfer token A fees if any
     if token_a_fee_amount > 0 {
@@ -392,7 +888,8 @@ pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
             token_program,
             pool_token_a,
             fee_collector_token_a,
-            pool,
+            &pool_signer,
+            ctx.remaining_accounts,
             token_a_fee_amount,
         )?;
     }
@@ -404,7 +901,8 @@ pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
             token_program,
             pool_token_b,
             fee_collector_token_b,
-            pool,
+            &pool_signer,
+            ctx.remaining_accounts,
             token_b_fee_amount,
         )?;
     }
@@ -419,424 +917,2554 @@ pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
     Ok(())
 }
 
-// NOTE: Types
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        mut,
-        // Check that the signer is the admin.
-        constraint = admin.key() == crate::ADMIN_PUBKEY @ DexError::NotAdmin
-    )]
-    pub admin: Signer<'info>,
-    #[account(
-        init_if_needed,
-        payer = admin,
-        space = 8 + DexState::LEN,
-        seeds = [
-            b"dex_state",
-            admin.key().as_ref(),
-        ],
-        bump
-    )]
-    pub dex_state: Account<'info, DexState>,
-    pub system_program: Program<'info, System>,
-}
+/*
+ * Retires an empty pool: closes its vaults, LP mint and pool account and returns their rent to
+ * the pool creator
+ * Only the creator or the admin can close a pool, once all liquidity is withdrawn and the
+ * protocol fees are collected
+ */
+pub fn close_liquidity_pool<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClosePool<'info>>,
+) -> Result<()> {
+    let pool = ctx.accounts.pool.load()?;
+    let pool_signer = PoolSigner::new(&ctx.accounts.pool, &pool);
+    let token_a_mint = &ctx.accounts.token_a_mint;
+    let token_b_mint = &ctx.accounts.token_b_mint;
+    let pool_token_a = &ctx.accounts.pool_token_a;
+    let pool_token_b = &ctx.accounts.pool_token_b;
+    let token_program = &ctx.accounts.token_program;
+    let creator = ctx.accounts.creator.to_account_info();
 
-/// This struct defines all the accounts needed to create a new trading pool
-#[derive(Accounts)]
-pub struct CreatePool<'info> {
-    // The person creating the pool - needs to be mutable because they'll pay for account creation
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    // Nobody owns what is left in the vaults (rounding dust, direct transfers): sweep it to the
+    // fee collector so the vaults can be closed
+    let dust_a = pool_token_a.amount;
+    if dust_a > 0 {
+        check_pool_transfer_hook(&pool, token_a_mint)?;
+        transfer_fee_tokens_to_collector(
+            token_a_mint,
+            token_program,
+            pool_token_a,
+            &ctx.accounts.fee_collector_token_a,
+            &pool_signer,
+            ctx.remaining_accounts,
+            dust_a,
+        )?;
+    }
+    let dust_b = pool_token_b.amount;
+    if dust_b > 0 {
+        check_pool_transfer_hook(&pool, token_b_mint)?;
+        transfer_fee_tokens_to_collector(
+            token_b_mint,
+            token_program,
+            pool_token_b,
+            &ctx.accounts.fee_collector_token_b,
+            &pool_signer,
+            ctx.remaining_accounts,
+            dust_b,
+        )?;
+    }
 
-    // The main DEX configuration - mutable because we'll update the pools counter
-    #[account(mut)]
-    pub dex_state: Account<'info, DexState>,
+    // Token-2022 refuses to close accounts holding withheld transfer fees
+    harvest_withheld_transfer_fees(token_a_mint, token_program, pool_token_a)?;
+    harvest_withheld_transfer_fees(token_b_mint, token_program, pool_token_b)?;
+
+    // Close the vaults and the LP mint, the pool account is closed by its `close` constraint
+    close_pool_token_account(token_program, pool_token_a, &creator, &pool_signer)?;
+    close_pool_token_account(token_program, pool_token_b, &creator, &pool_signer)?;
+    close_lp_token_mint(
+        &ctx.accounts.lp_token_program,
+        &ctx.accounts.lp_token_mint,
+        &creator,
+        &pool_signer,
+    )?;
 
-    // The two token definitions for this trading pair
-    pub token_a_mint: InterfaceAccount<'info, Mint>,
-    pub token_b_mint: InterfaceAccount<'info, Mint>,
+    // Unlist the pool: its registry entry stays for history, the pair can get a new pool on the
+    // fee tier. Pools created before the registry were never listed
+    if pool.registry_index != LiquidityPool::UNLISTED_REGISTRY_INDEX {
+        ctx.accounts
+            .registry_page
+            .as_mut()
+            .ok_or(error!(DexError::InvalidRegistryEntry))?
+            .mark_closed(pool.registry_index, pool_signer.key())?;
+        ctx.accounts
+            .pool_pair
+            .as_mut()
+            .ok_or(error!(DexError::InvalidRegistryEntry))?
+            .remove_pool(pool_signer.key());
+    }
 
-    // The pool account that stores all information about this trading pair
-    // - init: Create a new account
-    // - payer = owner: The creator pays for account creation
-    // - space: Allocate enough storage for the account data
-    // - seeds: Generate a deterministic address from these values (ensures unique address for this
-    //   token pair)
-    #[account(
+    // Increment the closed pools counter in DEX state
+    ctx.accounts.dex_state.closed_pools_count += 1;
+
+    msg!("Pool closed: {}", pool_signer.key());
+    msg!("Swept dust: {} token A, {} token B", dust_a, dust_b);
+    msg!("Rent returned to: {}", pool.creator);
+
+    Ok(())
+}
+
+/// Turns the volatility-based fee of a pool on (with new parameters) or off
+/// Passing `None` switches the pool back to its static fee
+pub fn configure_pool_dynamic_fee(
+    ctx: Context<ConfigureDynamicFee>,
+    params: Option<DynamicFeeParams>,
+) -> Result<()> {
+    let pool_key = ctx.accounts.pool.key();
+    let mut pool = ctx.accounts.pool.load_mut()?;
+
+    match params {
+        Some(params) => {
+            params.validate()?;
+
+            // Start tracking volatility from scratch with the new parameters
+            pool.dynamic_fee = DynamicFee::new(params);
+
+            msg!(
+                "Dynamic fee enabled for pool {}: {}-{} bps",
+                pool_key,
+                params.min_fee_bps,
+                params.max_fee_bps
+            );
+        }
+        None => {
+            pool.dynamic_fee = DynamicFee::default();
+
+            msg!("Dynamic fee disabled for pool {}", pool_key);
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Switches who may create pools: anyone, only the admin, or the admin and allowlisted creators
+ * Only the admin can call this function, creators are allowlisted with set_creator_permissions
+ */
+pub fn set_dex_pool_creation_mode(
+    ctx: Context<SetPoolCreationMode>,
+    mode: PoolCreationMode,
+) -> Result<()> {
+    ctx.accounts.dex_state.pool_creation_mode = mode;
+
+    msg!("Pool creation mode set to {:?}", mode);
+
+    Ok(())
+}
+
+/*
+ * Grants or revokes governance permissions of a pool creator
+ * Only the admin can call this function, `permissions` replaces the creator's current set
+ */
+pub fn set_pool_creator_permissions(
+    ctx: Context<SetCreatorPermissions>,
+    creator: Pubkey,
+    permissions: u8,
+) -> Result<()> {
+    require!(
+        permissions & !ALL_CREATOR_PERMISSIONS == 0,
+        DexError::InvalidPermissions
+    );
+
+    let creator_permissions = &mut ctx.accounts.creator_permissions;
+    creator_permissions.dex_state = ctx.accounts.dex_state.key();
+    creator_permissions.creator = creator;
+    creator_permissions.permissions = permissions;
+    creator_permissions.bump = ctx.bumps.creator_permissions;
+
+    msg!(
+        "Creator {} permissions set to {:#04x}",
+        creator,
+        permissions
+    );
+
+    Ok(())
+}
+
+/*
+ * Lists a mint as allowed, denied or unknown
+ * Only the admin can call this function, denying a mint blocks new pools and swaps of the mint
+ * in existing pools without touching them
+ */
+pub fn set_dex_mint_status(
+    ctx: Context<SetMintStatus>,
+    mint: Pubkey,
+    status: MintListing,
+) -> Result<()> {
+    let mint_status = &mut ctx.accounts.mint_status;
+    mint_status.mint = mint;
+    mint_status.status = status;
+    mint_status.bump = ctx.bumps.mint_status;
+
+    msg!("Mint {} listed as {:?}", mint, status);
+
+    Ok(())
+}
+
+/*
+ * Requires new pools to pair mints governance listed as allowed, or only refuses denied mints
+ * Only the admin can call this function, existing pools keep trading either way
+ */
+pub fn set_dex_require_allowed_mints(
+    ctx: Context<SetRequireAllowedMints>,
+    required: bool,
+) -> Result<()> {
+    ctx.accounts.dex_state.require_allowed_mints = required;
+
+    msg!("Allowed mints required: {}", required);
+
+    Ok(())
+}
+
+/*
+ * Switches how pools created from now on take the protocol fee: set aside by every swap and
+ * collected with collect_fees, or minted as LP tokens to the fee collector
+ * Only the admin can call this function, existing pools keep their mode
+ */
+pub fn set_dex_protocol_fee_mode(
+    ctx: Context<SetProtocolFeeMode>,
+    mode: ProtocolFeeMode,
+) -> Result<()> {
+    ctx.accounts.dex_state.protocol_fee_mode = mode;
+
+    msg!("Protocol fee mode set to {:?}", mode);
+
+    Ok(())
+}
+
+/*
+ * Upgrades a pool laid out as an earlier version to the current layout
+ * Anyone can migrate a pool, the payer funds the rent of the larger account
+ * Fields added since the pool's version start zeroed (creation time and slot of v0 pools stay
+ * unknown), except the creator of pools that didn't record one, which is the admin
+ */
+pub fn migrate_liquidity_pool(ctx: Context<MigratePool>) -> Result<()> {
+    let pool_info = ctx.accounts.pool.to_account_info();
+    let migrated_data = migrate_pool_data(&pool_info.try_borrow_data()?)?;
+
+    write_migrated_account(
+        &pool_info,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+        &migrated_data,
+    )?;
+
+    msg!("Pool migrated: {}", pool_info.key());
+
+    Ok(())
+}
+
+/*
+ * Upgrades the DEX state laid out as an earlier version to the current layout
+ * Only the admin can call this function, and pays for the larger account
+ */
+pub fn migrate_dex_state(ctx: Context<MigrateState>) -> Result<()> {
+    let dex_state_info = ctx.accounts.dex_state.to_account_info();
+    let migrated_data = migrate_dex_state_data(&dex_state_info.try_borrow_data()?)?;
+
+    write_migrated_account(
+        &dex_state_info,
+        &ctx.accounts.admin,
+        &ctx.accounts.system_program,
+        &migrated_data,
+    )?;
+
+    msg!("DEX state migrated: {}", dex_state_info.key());
+
+    Ok(())
+}
+
+/*
+ * Creates a liquidity mining farm for a pool
+ * Reward streams are added to it separately, by the admin or an authorized funder
+ */
+pub fn initialize_farm(ctx: Context<CreateFarm>) -> Result<()> {
+    let farm = &mut ctx.accounts.farm;
+
+    farm.pool = ctx.accounts.pool.key();
+    farm.lp_token_mint = ctx.accounts.lp_token_mint.key();
+    farm.lp_vault = ctx.accounts.lp_vault.key();
+    farm.total_staked = 0;
+
+    // No rewards and no funders besides the admin yet
+    farm.reward_stream_count = 0;
+    farm.reward_streams = Default::default();
+    farm.funders = Default::default();
+
+    // Save the PDA bump for future references
+    farm.bump = ctx.bumps.farm;
+
+    msg!("Farm created: {}", farm.key());
+    msg!("Pool: {}", farm.pool);
+
+    Ok(())
+}
+
+/*
+ * Authorizes or revokes an account allowed to add reward streams to a farm
+ * Only the admin can manage funders
+ */
+pub fn set_farm_funder(
+    ctx: Context<SetFarmFunder>,
+    funder: Pubkey,
+    authorized: bool,
+) -> Result<()> {
+    require!(funder != Pubkey::default(), DexError::UnauthorizedFunder);
+
+    let farm = &mut ctx.accounts.farm;
+    let existing_slot = farm.funders.iter().position(|key| *key == funder);
+
+    match (authorized, existing_slot) {
+        // Take the first free slot
+        (true, None) => {
+            let free_slot = farm
+                .funders
+                .iter()
+                .position(|key| *key == Pubkey::default())
+                .ok_or(error!(DexError::TooManyFarmFunders))?;
+            farm.funders[free_slot] = funder;
+        }
+        (false, Some(slot)) => farm.funders[slot] = Pubkey::default(),
+        // Already in the requested state
+        _ => {}
+    }
+
+    msg!(
+        "Farm {} funder {} authorized: {}",
+        farm.key(),
+        funder,
+        authorized
+    );
+
+    Ok(())
+}
+
+/*
+ * Adds a reward stream to a farm
+ * Stakers earn `reward_per_second` reward tokens between start and end time, existing streams
+ * keep their schedules and accumulators untouched
+ */
+pub fn add_farm_reward_stream(
+    ctx: Context<AddRewardStream>,
+    reward_per_second: u64,
+    start_time: i64,
+    end_time: i64,
+) -> Result<()> {
+    let farm = &mut ctx.accounts.farm;
+    let funder = &ctx.accounts.funder;
+
+    require!(
+        is_authorized_funder(farm, &ctx.accounts.dex_state, &funder.key()),
+        DexError::UnauthorizedFunder
+    );
+
+    // The emission window must be in the future and non-empty
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        start_time >= now && start_time < end_time,
+        DexError::InvalidFarmSchedule
+    );
+
+    // Streams are never removed, so their index stays valid for the lifetime of the farm
+    let stream_index = farm.reward_stream_count as usize;
+    require!(
+        stream_index < MAX_REWARD_STREAMS,
+        DexError::TooManyRewardStreams
+    );
+
+    farm.reward_streams[stream_index] = RewardStream {
+        reward_mint: ctx.accounts.reward_mint.key(),
+        reward_vault: ctx.accounts.reward_vault.key(),
+        funder: funder.key(),
+        reward_per_second,
+        start_time,
+        end_time,
+        // Nothing accrues before the start of the window
+        last_update_time: start_time,
+        acc_reward_per_share: 0,
+        carried_rewards: 0,
+        unpaid_rewards: 0,
+    };
+    farm.reward_stream_count += 1;
+
+    msg!(
+        "Reward stream {} added to farm {}",
+        stream_index,
+        farm.key()
+    );
+    msg!("Reward Mint: {}", ctx.accounts.reward_mint.key());
+    msg!(
+        "Emitting {} per second from {} to {}",
+        reward_per_second,
+        start_time,
+        end_time
+    );
+
+    Ok(())
+}
+
+/*
+ * Deposits reward tokens into the vault of one of the farm's streams
+ * Only the admin or the funder of the stream can fund it
+ */
+pub fn fund_farm_rewards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, FundFarm<'info>>,
+    stream_index: u8,
+    amount: u64,
+) -> Result<()> {
+    let stream = &ctx.accounts.farm.reward_streams[stream_index as usize];
+    let funder = &ctx.accounts.funder;
+
+    require!(
+        funder.key() == ctx.accounts.dex_state.admin || funder.key() == stream.funder,
+        DexError::UnauthorizedFunder
+    );
+    require!(amount > 0, DexError::InsufficientRewards);
+
+    transfer_user_tokens_to_farm(
+        &ctx.accounts.reward_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.funder_reward_token,
+        &ctx.accounts.reward_vault,
+        funder,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    msg!(
+        "Funded stream {} of farm {} with {} reward tokens",
+        stream_index,
+        ctx.accounts.farm.key(),
+        amount
+    );
+
+    Ok(())
+}
+
+/*
+ * Moves the end of a stream's emission window and sets its new emission rate
+ * Rewards accrued so far are settled at the old rate first, other streams are not touched
+ * The stream's vault must be funded for the new schedule beforehand
+ */
+pub fn extend_farm_schedule(
+    ctx: Context<ExtendFarm>,
+    stream_index: u8,
+    end_time: i64,
+    reward_per_second: u64,
+) -> Result<()> {
+    let farm = &mut ctx.accounts.farm;
+    let total_staked = farm.total_staked;
+    let stream = &mut farm.reward_streams[stream_index as usize];
+    let funder = &ctx.accounts.funder;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        funder.key() == ctx.accounts.dex_state.admin || funder.key() == stream.funder,
+        DexError::UnauthorizedFunder
+    );
+
+    // Streams can only be extended, and not into the past
+    require!(
+        end_time >= stream.end_time && end_time > now,
+        DexError::InvalidFarmSchedule
+    );
+
+    // Settle the rewards emitted under the previous schedule
+    update_reward_stream(stream, total_staked, now)?;
+
+    // A stream that already ended restarts emitting from now
+    if stream.last_update_time < now {
+        stream.last_update_time = now;
+    }
+    stream.end_time = end_time;
+    stream.reward_per_second = reward_per_second;
+
+    // The vault must already hold what the stream owes and everything the new schedule emits
+    require!(
+        ctx.accounts.reward_vault.amount >= calculate_reward_obligations(stream, now)?,
+        DexError::UnfundedRewardSchedule
+    );
+
+    msg!(
+        "Stream {} of farm {} extended to {} at {} per second",
+        stream_index,
+        farm.key(),
+        end_time,
+        reward_per_second
+    );
+
+    Ok(())
+}
+
+/*
+ * Stakes LP tokens into a farm
+ * Rewards earned so far on every stream are kept as pending on the position
+ */
+pub fn stake_lp_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, StakeLp<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, DexError::InsufficientStake);
+
+    let farm = &mut ctx.accounts.farm;
+    let stake_position = &mut ctx.accounts.stake_position;
+
+    // First stake initializes the position
+    if stake_position.owner == Pubkey::default() {
+        stake_position.farm = farm.key();
+        stake_position.owner = ctx.accounts.owner.key();
+        stake_position.bump = ctx.bumps.stake_position;
+    }
+
+    // Settle rewards up to now before the stake changes
+    update_farm_accumulators(farm, Clock::get()?.unix_timestamp)?;
+    settle_position_rewards(farm, stake_position)?;
+
+    transfer_user_tokens_to_farm(
+        &ctx.accounts.lp_token_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.user_lp_token,
+        &ctx.accounts.lp_vault,
+        &ctx.accounts.owner,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    // Update staked amounts and reset the reward debts to the new stake
+    stake_position.amount = stake_position
+        .amount
+        .checked_add(amount)
+        .ok_or(error!(DexError::InsufficientStake))?;
+    farm.total_staked = farm
+        .total_staked
+        .checked_add(amount)
+        .ok_or(error!(DexError::InsufficientStake))?;
+    reset_position_reward_debts(farm, stake_position)?;
+
+    msg!("Staked {} LP tokens in farm {}", amount, farm.key());
+
+    Ok(())
+}
+
+/*
+ * Withdraws staked LP tokens from a farm
+ * Rewards earned so far on every stream are kept as pending on the position
+ */
+pub fn unstake_lp_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, UnstakeLp<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let farm = &mut ctx.accounts.farm;
+    let stake_position = &mut ctx.accounts.stake_position;
+
+    // Ensure the position holds enough LP tokens
+    require!(
+        amount > 0 && stake_position.amount >= amount,
+        DexError::InsufficientStake
+    );
+
+    // Settle rewards up to now before the stake changes
+    update_farm_accumulators(farm, Clock::get()?.unix_timestamp)?;
+    settle_position_rewards(farm, stake_position)?;
+
+    // Update staked amounts and reset the reward debts to the new stake
+    stake_position.amount -= amount;
+    farm.total_staked = farm
+        .total_staked
+        .checked_sub(amount)
+        .ok_or(error!(DexError::InsufficientStake))?;
+    reset_position_reward_debts(farm, stake_position)?;
+
+    transfer_farm_tokens_to_user(
+        &ctx.accounts.lp_token_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.lp_vault,
+        &ctx.accounts.user_lp_token,
+        farm,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    msg!("Unstaked {} LP tokens from farm {}", amount, farm.key());
+
+    Ok(())
+}
+
+/*
+ * Pays out the rewards earned by a stake position on one stream of the farm
+ * An underfunded vault pays what it holds, the rest stays pending until the stream is funded
+ */
+pub fn claim_farm_rewards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimRewards<'info>>,
+    stream_index: u8,
+) -> Result<()> {
+    let farm = &mut ctx.accounts.farm;
+    let stake_position = &mut ctx.accounts.stake_position;
+    let index = stream_index as usize;
+
+    // Settle this stream up to now
+    let total_staked = farm.total_staked;
+    let stream = &mut farm.reward_streams[index];
+    update_reward_stream(stream, total_staked, Clock::get()?.unix_timestamp)?;
+
+    let staked_amount = stake_position.amount;
+    let reward = &mut stake_position.rewards[index];
+    let pending = calculate_pending_rewards(
+        staked_amount,
+        stream.acc_reward_per_share,
+        reward.reward_debt,
+    )?;
+    let claimable = reward
+        .pending_rewards
+        .checked_add(pending)
+        .ok_or(error!(DexError::InvalidFarmSchedule))?;
+
+    require!(claimable > 0, DexError::InsufficientRewards);
+
+    // Pay what the vault holds when the stream was not funded for all it emitted
+    let paid = claimable.min(ctx.accounts.reward_vault.amount);
+    require!(paid > 0, DexError::InsufficientRewards);
+
+    // Settle the position before the transfer, keeping the unpaid part pending
+    reward.pending_rewards = claimable - paid;
+    reward.reward_debt = calculate_reward_debt(staked_amount, stream.acc_reward_per_share)?;
+    // Rounding leaves the stream owing a little more than its stakers claim
+    stream.unpaid_rewards = stream.unpaid_rewards.saturating_sub(paid);
+
+    transfer_farm_tokens_to_user(
+        &ctx.accounts.reward_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.reward_vault,
+        &ctx.accounts.user_reward_token,
+        farm,
+        ctx.remaining_accounts,
+        paid,
+    )?;
+
+    msg!(
+        "Claimed {} reward tokens from stream {} of farm {}, {} left pending",
+        paid,
+        stream_index,
+        farm.key(),
+        claimable - paid
+    );
+
+    Ok(())
+}
+
+/*
+ * Creates the governance allowlist that relaxes the mint extension checks of pool creation
+ * Only the admin can create it, once per DEX
+ */
+pub fn initialize_extension_allowlist(ctx: Context<CreateExtensionAllowlist>) -> Result<()> {
+    let extension_allowlist = &mut ctx.accounts.extension_allowlist;
+
+    extension_allowlist.dex_state = ctx.accounts.dex_state.key();
+
+    // Only the default extensions are accepted until governance adds more
+    extension_allowlist.allowed_extensions = Default::default();
+    extension_allowlist.trusted_mints = Default::default();
+    extension_allowlist.allowed_hook_programs = Default::default();
+
+    // Save the PDA bump for future references
+    extension_allowlist.bump = ctx.bumps.extension_allowlist;
+
+    msg!("Extension allowlist created: {}", extension_allowlist.key());
+
+    Ok(())
+}
+
+/*
+ * Allows or disallows a Token-2022 mint extension type in new pools
+ * Only the admin can manage the allowlist
+ */
+pub fn set_allowed_mint_extension(
+    ctx: Context<UpdateExtensionAllowlist>,
+    extension_type: u16,
+    allowed: bool,
+) -> Result<()> {
+    let extension = parse_extension_type(extension_type)?;
+
+    let extension_allowlist = &mut ctx.accounts.extension_allowlist;
+    let existing_slot = extension_allowlist
+        .allowed_extensions
+        .iter()
+        .position(|allowed_type| *allowed_type == extension_type);
+
+    match (allowed, existing_slot) {
+        // Take the first free slot
+        (true, None) => {
+            let free_slot = extension_allowlist
+                .allowed_extensions
+                .iter()
+                .position(|allowed_type| *allowed_type == 0)
+                .ok_or(error!(DexError::TooManyAllowlistEntries))?;
+            extension_allowlist.allowed_extensions[free_slot] = extension_type;
+        }
+        (false, Some(slot)) => extension_allowlist.allowed_extensions[slot] = 0,
+        // Already in the requested state
+        _ => {}
+    }
+
+    msg!("Mint extension {:?} allowed: {}", extension, allowed);
+
+    Ok(())
+}
+
+/*
+ * Exempts a mint from the extension checks of pool creation, or revokes the exemption
+ * Only the admin can manage the allowlist
+ */
+pub fn set_extension_trusted_mint(
+    ctx: Context<UpdateExtensionAllowlist>,
+    mint: Pubkey,
+    trusted: bool,
+) -> Result<()> {
+    require!(mint != Pubkey::default(), DexError::InvalidExtensionType);
+
+    let extension_allowlist = &mut ctx.accounts.extension_allowlist;
+    let existing_slot = extension_allowlist
+        .trusted_mints
+        .iter()
+        .position(|key| *key == mint);
+
+    match (trusted, existing_slot) {
+        // Take the first free slot
+        (true, None) => {
+            let free_slot = extension_allowlist
+                .trusted_mints
+                .iter()
+                .position(|key| *key == Pubkey::default())
+                .ok_or(error!(DexError::TooManyAllowlistEntries))?;
+            extension_allowlist.trusted_mints[free_slot] = mint;
+        }
+        (false, Some(slot)) => extension_allowlist.trusted_mints[slot] = Pubkey::default(),
+        // Already in the requested state
+        _ => {}
+    }
+
+    msg!("Mint {} trusted: {}", mint, trusted);
+
+    Ok(())
+}
+
+/*
+ * Accepts or stops accepting a transfer hook program for the mints of new pools
+ * Only the admin can manage the allowlist
+ */
+pub fn set_extension_hook_program(
+    ctx: Context<UpdateExtensionAllowlist>,
+    program_id: Pubkey,
+    allowed: bool,
+) -> Result<()> {
+    require!(
+        program_id != Pubkey::default(),
+        DexError::InvalidExtensionType
+    );
+
+    let extension_allowlist = &mut ctx.accounts.extension_allowlist;
+    let existing_slot = extension_allowlist
+        .allowed_hook_programs
+        .iter()
+        .position(|key| *key == program_id);
+
+    match (allowed, existing_slot) {
+        // Take the first free slot
+        (true, None) => {
+            let free_slot = extension_allowlist
+                .allowed_hook_programs
+                .iter()
+                .position(|key| *key == Pubkey::default())
+                .ok_or(error!(DexError::TooManyAllowlistEntries))?;
+            extension_allowlist.allowed_hook_programs[free_slot] = program_id;
+        }
+        (false, Some(slot)) => extension_allowlist.allowed_hook_programs[slot] = Pubkey::default(),
+        // Already in the requested state
+        _ => {}
+    }
+
+    msg!("Transfer hook program {} allowed: {}", program_id, allowed);
+
+    Ok(())
+}
+
+// NOTE: Types
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        mut,
+        // Check that the signer is the admin.
+        constraint = admin.key() == crate::ADMIN_PUBKEY @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+    // One-shot: running it again would reset the counters the pool registry is indexed by
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + DexState::LEN,
+        seeds = [
+            b"dex_state",
+            admin.key().as_ref(),
+        ],
+        bump
+    )]
+    pub dex_state: Account<'info, DexState>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Defines the accounts required for changing the DEX fees
+#[derive(Accounts)]
+pub struct SetFees<'info> {
+    // Only the admin sets the fees
+    #[account(
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state holding the fees
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+}
+
+/// Defines the accounts required for registering a fee tier
+#[derive(Accounts)]
+pub struct AddFeeTier<'info> {
+    // Only the admin can register tiers, and pays for the tier account
+    #[account(
+        mut,
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state - mutable because we'll update the tiers counter
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+
+    // The new tier, addressed by its index in registration order
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FeeTier::LEN,
+        seeds = [
+            b"fee_tier",
+            dex_state.key().as_ref(),
+            dex_state.fee_tiers_count.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub fee_tier: Account<'info, FeeTier>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// This struct defines all the accounts needed to create a new trading pool
+#[derive(Accounts)]
+#[instruction(fee_tier_index: u16)]
+pub struct CreatePool<'info> {
+    // The person creating the pool - needs to be mutable because they'll pay for account creation
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // The main DEX configuration - mutable because we'll update the pools counter
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+
+    // The fee tier selected for this pool
+    #[account(
+        seeds = [
+            b"fee_tier",
+            dex_state.key().as_ref(),
+            fee_tier_index.to_le_bytes().as_ref(),
+        ],
+        bump = fee_tier.bump
+    )]
+    pub fee_tier: Account<'info, FeeTier>,
+
+    // Governance overrides of the mint extension checks
+    // - Optional: without it only the default extensions are accepted
+    #[account(
+        seeds = [
+            b"extension_allowlist",
+            dex_state.key().as_ref(),
+        ],
+        bump = extension_allowlist.bump
+    )]
+    pub extension_allowlist: Option<Box<Account<'info, ExtensionAllowlist>>>,
+
+    // Permissions governance granted to the pool creator
+    // - Optional: only needed when the DEX restricts pool creation to allowlisted creators
+    #[account(
+        seeds = [
+            b"creator_permissions",
+            dex_state.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump = creator_permissions.bump
+    )]
+    pub creator_permissions: Option<Box<Account<'info, CreatorPermissions>>>,
+
+    // The two token definitions for this trading pair
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    // Governance's listing of each mint, denied mints can't be paired
+    // - Empty when governance never listed the mint
+    /// CHECK: address checked by the seeds, read by check_pool_mint
+    #[account(
+        seeds = [
+            b"mint_status",
+            dex_state.key().as_ref(),
+            token_a_mint.key().as_ref(),
+        ],
+        bump
+    )]
+    pub token_a_mint_status: UncheckedAccount<'info>,
+    /// CHECK: address checked by the seeds, read by check_pool_mint
+    #[account(
+        seeds = [
+            b"mint_status",
+            dex_state.key().as_ref(),
+            token_b_mint.key().as_ref(),
+        ],
+        bump
+    )]
+    pub token_b_mint_status: UncheckedAccount<'info>,
+
+    // Metaplex metadata of each mint, whose symbol names the LP token
+    // - Optional: without it the LP token takes the mint's Token-2022 metadata symbol, if any
+    /// CHECK: address checked by the seeds, read by mint_label when Metaplex owns it
+    #[account(
+        seeds = [
+            b"metadata",
+            METAPLEX_METADATA_PROGRAM_ID.as_ref(),
+            token_a_mint.key().as_ref(),
+        ],
+        seeds::program = METAPLEX_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub token_a_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: address checked by the seeds, read by mint_label when Metaplex owns it
+    #[account(
+        seeds = [
+            b"metadata",
+            METAPLEX_METADATA_PROGRAM_ID.as_ref(),
+            token_b_mint.key().as_ref(),
+        ],
+        seeds::program = METAPLEX_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub token_b_metadata: Option<UncheckedAccount<'info>>,
+
+    // The pool account that stores all information about this trading pair
+    // - init: Create a new account
+    // - payer = owner: The creator pays for account creation
+    // - space: Allocate enough storage for the account data
+    // - seeds: Generate a deterministic address from these values (ensures unique address for this
+    //   token pair and fee tier)
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + LiquidityPool::LEN,
+        seeds = [
+            b"liquidity_pool",
+            token_a_mint.key().as_ref(),
+            token_b_mint.key().as_ref(),
+            fee_tier_index.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Registry page the pool is listed on, created by the first pool of each page
+    // - The pools counter is the position of the new pool in the registry
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PoolRegistryPage::LEN,
+        seeds = [
+            b"pool_registry",
+            dex_state.key().as_ref(),
+            registry_page_index(dex_state.pools_count).to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub registry_page: Box<Account<'info, PoolRegistryPage>>,
+
+    // Lookup of the pools trading this mint pair, created by the first pool of the pair
+    // - Seeded with the DEX state and the sorted mints so both mint orders share it
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PoolPair::LEN,
+        seeds = [
+            b"pool_pair",
+            dex_state.key().as_ref(),
+            sorted_pair_mints(token_a_mint.key(), token_b_mint.key()).0.as_ref(),
+            sorted_pair_mints(token_a_mint.key(), token_b_mint.key()).1.as_ref(),
+        ],
+        bump
+    )]
+    pub pool_pair: Box<Account<'info, PoolPair>>,
+
+    // Create a token account to hold the pool's reserves of Token A
+    // - The pool itself has control over this account
+    #[account(
+        mut,
+        token::mint = token_a_mint,
+        token::authority = pool,
+    )]
+    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // Create a token account to hold the pool's reserves of Token B
+    // - The pool itself has control over this account
+    #[account(
+        mut,
+        token::mint = token_b_mint,
+        token::authority = pool,
+    )]
+    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // Create a new token type that represents shares in this pool
+    // - 6 decimal places for precision
+    // - The pool has authority to mint these tokens
+    // - Always a Token-2022 mint whose metadata pointer targets itself, so the name and symbol
+    //   written by the handler live in the mint account
+    // - The pool is the close authority, so a retired pool can reclaim the mint's rent
+    #[account(
         init,
         payer = owner,
-        space = 8 + LiquidityPool::LEN,
+        mint::decimals = 6,
+        mint::authority = pool,
+        mint::token_program = lp_token_program,
+        extensions::metadata_pointer::authority = pool,
+        extensions::metadata_pointer::metadata_address = lp_token_mint,
+        extensions::close_authority::authority = pool,
+    )]
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
+
+    // Create a token account for the pool creator to receive LP tokens
+    // - Only created if it doesn't exist already
+    // - The owner has control over this account
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = lp_token_program,
+    )]
+    pub owner_lp_token: InterfaceAccount<'info, TokenAccount>,
+
+    // Required Solana programs for handling tokens and accounts
+    // token_program owns the pair's mints, lp_token_program the LP mint
+    pub token_program: Interface<'info, TokenInterface>,
+    pub lp_token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[account]
+pub struct DexState {
+    pub admin: Pubkey,
+    /// Incremented each time a new trading pair is created
+    pub pools_count: u64,
+    /// Using two integers to represent a fraction allows for exact calculations using only integer
+    /// math, which is more deterministic.
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    /// Protocol fee as a percentage of the total fee
+    /// protocol_fee_percentage is a number between 0 and 100
+    /// If set to 30, it means 30% of fees go to protocol, 70% to LPs
+    pub protocol_fee_percentage: u8,
+    /// Account that collects protocol fees
+    pub fee_collector: Pubkey,
+    /// Incremented each time a fee tier is registered, also the index of the next tier
+    pub fee_tiers_count: u16,
+    /// Incremented each time a pool is closed, pools_count keeps counting closed pools
+    pub closed_pools_count: u64,
+    /// Layout version of the account, DexState::VERSION once initialized or migrated
+    pub version: u8,
+    /// Who may create pools, carved out of the reserved space (zero is Open)
+    pub pool_creation_mode: PoolCreationMode,
+    /// New pools only accept mints governance listed as allowed, carved out of the reserved
+    /// space (zero accepts any mint that isn't denied)
+    pub require_allowed_mints: bool,
+    /// How pools created from now on take the protocol fee, carved out of the reserved space
+    /// (zero is Accrued)
+    pub protocol_fee_mode: ProtocolFeeMode,
+    /// Zeroed space for future fields, so they can be added without a realloc
+    pub reserved: [u8; RESERVED_SPACE - 3],
+}
+
+impl DexState {
+    pub const LEN: usize = Self::LEN_V0 + 2 + 8 + 1 + 1 + 1 + 1 + (RESERVED_SPACE - 3); // v0 fields + fee_tiers_count + closed_pools_count + version + pool_creation_mode + require_allowed_mints + protocol_fee_mode + reserved
+
+    // Size of v0 states, the first release, created before layouts were versioned
+    pub const LEN_V0: usize = 32 + 8 + 8 + 8 + 1 + 32; // admin + pools_count + fee_numerator + fee_denominator + protocol_fee_percentage + fee_collector
+
+    // Current layout version
+    pub const VERSION: u8 = 1;
+}
+
+/// A fee level pools can be created with (e.g. 1, 5, 30 or 100 bps)
+#[account]
+pub struct FeeTier {
+    /// Position of the tier in registration order, part of the PDA seeds of its pools
+    pub index: u16,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl FeeTier {
+    pub const LEN: usize = 2 + 8 + 8 + 1; // index + fee_numerator + fee_denominator + bump
+}
+
+#[account(zero_copy)]
+#[derive(Debug)]
+pub struct LiquidityPool {
+    // Account that created the pool, receives the rent back when it is closed
+    pub creator: Pubkey,
+    // Token A mint address
+    pub token_a_mint: Pubkey,
+    // Token B mint address
+    pub token_b_mint: Pubkey,
+    // Pool's token A account holding reserves
+    pub token_a_account: Pubkey,
+    // Pool's token B account holding reserves
+    pub token_b_account: Pubkey,
+    // LP token mint issued to liquidity providers
+    pub lp_token_mint: Pubkey,
+    // Transfer hook programs of token A and token B accepted at creation (default when none)
+    pub token_a_transfer_hook: Pubkey,
+    pub token_b_transfer_hook: Pubkey,
+    // Total LP tokens minted for this pool
+    pub total_liquidity: u64,
+    // Fee numerator (e.g. 10 for a 1% fee)
+    pub fee_numerator: u64,
+    // Fee denominator (e.g. 1000 for a 1% fee)
+    pub fee_denominator: u64,
+    // Accumulated fees for token A (for protocol)
+    pub protocol_fees_token_a: u64,
+    // Accumulated fees for token B (for protocol)
+    pub protocol_fees_token_b: u64,
+    // Position of the pool in the registry, which is also its creation order, or
+    // UNLISTED_REGISTRY_INDEX for pools created before the registry
+    pub registry_index: u64,
+    // Unix timestamp and slot of the pool creation (0 for pools migrated from v0)
+    pub created_at: i64,
+    pub created_slot: u64,
+    // Optional volatility-based fee replacing the static fee when enabled
+    pub dynamic_fee: DynamicFee,
+    // Index of the fee tier the pool was created with (part of the PDA seeds), or
+    // LEGACY_FEE_TIER_INDEX for pools created before fee tiers
+    pub fee_tier_index: u16,
+    // Bump seed for PDA derivation
+    pub bump: u8,
+    // Protocol fee percentage (0-100)
+    pub protocol_fee_percentage: u8,
+    // Layout version of the account, LiquidityPool::VERSION once created or migrated
+    pub version: u8,
+    // Aligns the reserved space, fields are laid out without implicit padding
+    pub padding: [u8; 3],
+    // Product of the reserves after the last deposit or withdrawal (u128, little endian), only
+    // tracked by MintLp pools
+    pub k_last: [u8; 16],
+    // ProtocolFeeMode the pool was created with
+    pub protocol_fee_mode: u8,
+    // Zeroed space for future fields, so they can be added without a realloc
+    // - k_last and protocol_fee_mode were carved out of it
+    pub reserved: [u8; RESERVED_SPACE - 16 - 1],
+}
+
+impl LiquidityPool {
+    pub const LEN: usize = 32 * 8 + 8 * 8 + DynamicFee::LEN + 2 + 1 + 1 + 1 + 3 + RESERVED_SPACE; // pubkeys + u64 amounts, fees, registry_index and creation time + dynamic_fee + fee_tier_index + bump + protocol_fee_percentage + version + padding + reserved (protocol fee fields included)
+
+    // Size of v0 pools, the first release, created before layouts were versioned
+    pub const LEN_V0: usize = 32 * 5 + 1 + 8 + 8 + 8 + 1 + 8 + 8; // token_a_mint + token_b_mint + token_a_account + token_b_account + lp_token_mint + bump + total_liquidity + fees + protocol_fee_percentage + protocol_fees
+
+    // Current layout version, the first zero-copy one
+    pub const VERSION: u8 = 3;
+
+    // Fee tier index of the pools created before fee tiers, whose PDA seeds are only the pair
+    // It is never registered: fee tiers are capped at MAX_POOLS_PER_PAIR
+    pub const LEGACY_FEE_TIER_INDEX: u16 = u16::MAX;
+
+    // Registry index of the pools created before the registry, listed neither in it nor in the
+    // lookup of their pair
+    pub const UNLISTED_REGISTRY_INDEX: u64 = u64::MAX;
+
+    /// Part of the vault balances owned by the liquidity providers, as (token A, token B)
+    /// Accrued protocol fees stay in the vaults until they are collected, None if the vaults
+    /// hold less than them
+    pub fn lp_reserves(&self, vault_a_amount: u64, vault_b_amount: u64) -> Option<(u64, u64)> {
+        Some((
+            vault_a_amount.checked_sub(self.protocol_fees_token_a)?,
+            vault_b_amount.checked_sub(self.protocol_fees_token_b)?,
+        ))
+    }
+
+    /// Whether the pool takes the protocol fee as LP tokens rather than in its fee counters
+    pub fn mints_protocol_fee(&self) -> bool {
+        self.protocol_fee_mode == ProtocolFeeMode::MintLp as u8
+    }
+
+    /// Part of the swap fees set aside in the protocol fee counters, in percent
+    /// MintLp pools leave the whole fee in the reserves
+    pub fn accrued_protocol_fee_percentage(&self) -> u8 {
+        if self.mints_protocol_fee() {
+            0
+        } else {
+            self.protocol_fee_percentage
+        }
+    }
+
+    pub fn k_last(&self) -> u128 {
+        u128::from_le_bytes(self.k_last)
+    }
+
+    /// LP tokens owed to the fee collector for the fees earned since the last deposit or
+    /// withdrawal, given the current reserves (always 0 for Accrued pools)
+    pub fn protocol_fee_liquidity(&self, reserve_a: u64, reserve_b: u64) -> dex_math::Result<u64> {
+        if !self.mints_protocol_fee() {
+            return Ok(0);
+        }
+
+        calculate_protocol_fee_liquidity(
+            reserve_a,
+            reserve_b,
+            self.k_last(),
+            self.total_liquidity,
+            self.protocol_fee_percentage,
+        )
+    }
+
+    /// Records the reserves left by a deposit or withdrawal, fees are measured from them
+    pub fn update_k_last(&mut self, reserve_a: u64, reserve_b: u64) {
+        if self.mints_protocol_fee() {
+            self.k_last = (reserve_a as u128 * reserve_b as u128).to_le_bytes();
+        }
+    }
+}
+
+const _: () = assert!(core::mem::size_of::<LiquidityPool>() == LiquidityPool::LEN);
+
+/// Permissions governance granted to a pool creator
+#[account]
+pub struct CreatorPermissions {
+    /// DEX granting the permissions
+    pub dex_state: Pubkey,
+    /// Creator the permissions apply to (part of the PDA seeds)
+    pub creator: Pubkey,
+    /// Bit set of PERMISSION_* flags
+    pub permissions: u8,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl CreatorPermissions {
+    pub const LEN: usize = 32 + 32 + 1 + 1; // dex_state + creator + permissions + bump
+
+    /// Returns true if the creator holds every permission of `permission`
+    pub fn allows(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+}
+
+/// Governance's listing of a token, shared by every pool trading it
+#[account]
+pub struct MintStatus {
+    /// Mint the listing applies to (part of the PDA seeds)
+    pub mint: Pubkey,
+    /// Whether the mint is allowed, denied or was never reviewed
+    pub status: MintListing,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl MintStatus {
+    pub const LEN: usize = 32 + 1 + 1; // mint + status + bump
+}
+
+/// A pool listed in the registry
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct PoolRegistryEntry {
+    /// Address of the pool
+    pub pool: Pubkey,
+    /// Mints of the pool, in the order it was created with
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    /// Slot the pool was created in
+    pub created_slot: u64,
+    /// Set when the pool is closed, the entry stays listed
+    pub closed: bool,
+}
+
+impl PoolRegistryEntry {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 1; // pool + token_a_mint + token_b_mint + created_slot + closed
+}
+
+/// A page of the on-chain pool registry, listing POOLS_PER_REGISTRY_PAGE pools in creation order
+/// Pool number n of the DEX is entry n % POOLS_PER_REGISTRY_PAGE of page
+/// n / POOLS_PER_REGISTRY_PAGE, so clients can enumerate pools from `DexState::pools_count`
+/// Pools created before the registry aren't listed: their entries stay default, and pages listing
+/// none of the later pools don't exist
+#[account]
+pub struct PoolRegistryPage {
+    /// DEX whose pools are listed
+    pub dex_state: Pubkey,
+    /// Position of the page in the registry (part of the PDA seeds)
+    pub page_index: u64,
+    /// Number of entries up to the last one written to the page
+    pub entries_count: u8,
+    /// Listed pools (unused slots are default)
+    pub entries: [PoolRegistryEntry; POOLS_PER_REGISTRY_PAGE],
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl PoolRegistryPage {
+    pub const LEN: usize = 32 + 8 + 1 + PoolRegistryEntry::LEN * POOLS_PER_REGISTRY_PAGE + 1; // dex_state + page_index + entries_count + entries + bump
+
+    /// Lists pool number `registry_index` on the page, setting the page up when it was just
+    /// created
+    pub fn push_entry(
+        &mut self,
+        dex_state: Pubkey,
+        registry_index: u64,
+        bump: u8,
+        entry: PoolRegistryEntry,
+    ) -> Result<()> {
+        if self.entries_count == 0 {
+            self.dex_state = dex_state;
+            self.page_index = registry_page_index(registry_index);
+            self.bump = bump;
+        }
+
+        // The entry of the pool number, even when pools created before the registry left the
+        // previous ones unused
+        let position = (registry_index % POOLS_PER_REGISTRY_PAGE as u64) as usize;
+        let slot = self
+            .entries
+            .get_mut(position)
+            .filter(|slot| slot.pool == Pubkey::default())
+            .ok_or(error!(DexError::RegistryPageFull))?;
+        *slot = entry;
+        self.entries_count = position as u8 + 1;
+
+        Ok(())
+    }
+
+    /// Flags the entry of a closed pool
+    pub fn mark_closed(&mut self, registry_index: u64, pool: Pubkey) -> Result<()> {
+        let entry = self
+            .entries
+            .get_mut((registry_index % POOLS_PER_REGISTRY_PAGE as u64) as usize)
+            .filter(|entry| entry.pool == pool)
+            .ok_or(error!(DexError::InvalidRegistryEntry))?;
+        entry.closed = true;
+
+        Ok(())
+    }
+}
+
+/// Lookup of the pools trading a mint pair, one per fee tier, whatever the mint order they were
+/// created with
+#[account]
+pub struct PoolPair {
+    /// Mints of the pair, sorted (part of the PDA seeds)
+    pub mint_0: Pubkey,
+    pub mint_1: Pubkey,
+    /// Number of pools of the pair
+    pub pools_count: u8,
+    /// Pools of the pair (unused slots are default)
+    pub pools: [Pubkey; MAX_POOLS_PER_PAIR],
+    /// Fee tier of each pool
+    pub fee_tier_indexes: [u16; MAX_POOLS_PER_PAIR],
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl PoolPair {
+    pub const LEN: usize = 32 + 32 + 1 + 32 * MAX_POOLS_PER_PAIR + 2 * MAX_POOLS_PER_PAIR + 1; // mint_0 + mint_1 + pools_count + pools + fee_tier_indexes + bump
+
+    /// Pool of the pair on a fee tier, if any
+    pub fn pool_for_fee_tier(&self, fee_tier_index: u16) -> Option<Pubkey> {
+        self.fee_tier_indexes[..self.pools_count as usize]
+            .iter()
+            .position(|index| *index == fee_tier_index)
+            .map(|position| self.pools[position])
+    }
+
+    /// Adds a pool to the lookup, setting the lookup up when it was just created
+    /// Refuses a second pool of the pair on the same fee tier, with the mints in either order
+    pub fn add_pool(
+        &mut self,
+        (mint_0, mint_1): (Pubkey, Pubkey),
+        bump: u8,
+        pool: Pubkey,
+        fee_tier_index: u16,
+    ) -> Result<()> {
+        if self.pools_count == 0 {
+            self.mint_0 = mint_0;
+            self.mint_1 = mint_1;
+            self.bump = bump;
+        }
+
+        require!(
+            self.pool_for_fee_tier(fee_tier_index).is_none(),
+            DexError::PoolAlreadyExists
+        );
+        let position = self.pools_count as usize;
+        require!(position < MAX_POOLS_PER_PAIR, DexError::TooManyPoolsForPair);
+
+        self.pools[position] = pool;
+        self.fee_tier_indexes[position] = fee_tier_index;
+        self.pools_count += 1;
+
+        Ok(())
+    }
+
+    /// Removes a closed pool from the lookup, moving the last pool into its slot
+    pub fn remove_pool(&mut self, pool: Pubkey) {
+        let count = self.pools_count as usize;
+        let Some(position) = self.pools[..count].iter().position(|entry| *entry == pool) else {
+            return;
+        };
+
+        let last = count - 1;
+        self.pools[position] = self.pools[last];
+        self.fee_tier_indexes[position] = self.fee_tier_indexes[last];
+        self.pools[last] = Pubkey::default();
+        self.fee_tier_indexes[last] = 0;
+        self.pools_count -= 1;
+    }
+}
+
+/// Governance overrides of the Token-2022 extension checks run at pool creation
+#[account]
+pub struct ExtensionAllowlist {
+    /// DEX whose pool creation this allowlist governs
+    pub dex_state: Pubkey,
+    /// Token-2022 extension types accepted on top of the defaults (unused slots are 0)
+    pub allowed_extensions: [u16; MAX_ALLOWED_EXTENSIONS],
+    /// Mints accepted whatever their extensions (unused slots are default)
+    pub trusted_mints: [Pubkey; MAX_TRUSTED_MINTS],
+    /// Transfer hook programs pool mints may call (unused slots are default)
+    pub allowed_hook_programs: [Pubkey; MAX_HOOK_PROGRAMS],
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl ExtensionAllowlist {
+    pub const LEN: usize =
+        32 + 2 * MAX_ALLOWED_EXTENSIONS + 32 * MAX_TRUSTED_MINTS + 32 * MAX_HOOK_PROGRAMS + 1; // dex_state + allowed_extensions + trusted_mints + allowed_hook_programs + bump
+
+    /// Returns true if governance allowed this extension type
+    pub fn is_allowed_extension(&self, extension_type: u16) -> bool {
+        extension_type != 0 && self.allowed_extensions.contains(&extension_type)
+    }
+
+    /// Returns true if governance exempted this mint from the extension checks
+    pub fn is_trusted_mint(&self, mint: &Pubkey) -> bool {
+        *mint != Pubkey::default() && self.trusted_mints.contains(mint)
+    }
+
+    /// Returns true if governance accepted this transfer hook program
+    pub fn is_allowed_hook_program(&self, program_id: &Pubkey) -> bool {
+        *program_id != Pubkey::default() && self.allowed_hook_programs.contains(program_id)
+    }
+}
+
+/// A liquidity mining program rewarding the stakers of a pool's LP token with up to
+/// MAX_REWARD_STREAMS reward tokens
+#[account]
+pub struct Farm {
+    /// Pool whose LP token is staked
+    pub pool: Pubkey,
+    /// LP token mint of the pool
+    pub lp_token_mint: Pubkey,
+    /// Farm-owned account holding the staked LP tokens
+    pub lp_vault: Pubkey,
+    /// Total LP tokens staked in the farm
+    pub total_staked: u64,
+    /// Number of streams in use in `reward_streams`
+    pub reward_stream_count: u8,
+    /// Concurrent reward emissions, each with its own schedule, vault and accumulator
+    pub reward_streams: [RewardStream; MAX_REWARD_STREAMS],
+    /// Accounts besides the admin allowed to add reward streams (unused slots are default)
+    pub funders: [Pubkey; MAX_FARM_FUNDERS],
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl Farm {
+    pub const LEN: usize =
+        32 + 32 + 32 + 8 + 1 + RewardStream::LEN * MAX_REWARD_STREAMS + 32 * MAX_FARM_FUNDERS + 1; // pool + lp_token_mint + lp_vault + total_staked + reward_stream_count + reward_streams + funders + bump
+
+    /// Returns the stream at `index` if it is in use
+    pub fn reward_stream(&self, index: u8) -> Option<&RewardStream> {
+        self.reward_streams[..self.reward_stream_count as usize].get(index as usize)
+    }
+}
+
+/// One reward token emitted by a farm
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct RewardStream {
+    // Token paid out as reward
+    pub reward_mint: Pubkey,
+    // Farm-owned account holding the reward tokens
+    pub reward_vault: Pubkey,
+    // Account that added the stream, allowed to fund and extend it
+    pub funder: Pubkey,
+    // Reward tokens emitted per second, shared between all stakers
+    pub reward_per_second: u64,
+    // Emission window (unix timestamps)
+    pub start_time: i64,
+    pub end_time: i64,
+    // Last time the accumulator was brought up to date
+    pub last_update_time: i64,
+    // Rewards emitted per staked LP token, scaled by REWARD_PER_SHARE_PRECISION
+    pub acc_reward_per_share: u128,
+    // Rewards emitted while nothing was staked, shared by the stakers of the next update
+    pub carried_rewards: u64,
+    // Rewards shared between the stakers and not paid out yet, rounding dust included
+    pub unpaid_rewards: u64,
+}
+
+impl RewardStream {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 16 + 8 + 8; // reward_mint + reward_vault + funder + reward_per_second + start_time + end_time + last_update_time + acc_reward_per_share + carried_rewards + unpaid_rewards
+}
+
+/// LP tokens staked by one user in one farm
+#[account]
+pub struct StakePosition {
+    pub farm: Pubkey,
+    pub owner: Pubkey,
+    /// LP tokens staked
+    pub amount: u64,
+    /// Reward bookkeeping, one entry per stream of the farm (same index)
+    pub rewards: [PositionReward; MAX_REWARD_STREAMS],
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl StakePosition {
+    pub const LEN: usize = 32 + 32 + 8 + PositionReward::LEN * MAX_REWARD_STREAMS + 1; // farm + owner + amount + rewards + bump
+}
+
+/// Rewards of a stake position on one stream
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct PositionReward {
+    // Rewards already accounted for at the current stake (amount * acc_reward_per_share)
+    pub reward_debt: u128,
+    // Rewards earned but not claimed yet, including what an underfunded vault could not pay
+    pub pending_rewards: u64,
+}
+
+impl PositionReward {
+    pub const LEN: usize = 16 + 8; // reward_debt + pending_rewards
+}
+
+#[derive(Accounts)]
+pub struct DepositLiquidity<'info> {
+    // Liquidity provider
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // Target pool for deposit
+    #[account(mut)]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Token mint definitions
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    // Pool's token A reserve account
+    #[account(
+        mut,
+        constraint = pool_token_a.key() == pool.load()?.token_a_account,
+        constraint = token_a_mint.key() == pool.load()?.token_a_mint
+    )]
+    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // Pool's token B reserve account
+    #[account(
+        mut,
+        constraint = pool_token_b.key() == pool.load()?.token_b_account,
+        constraint = token_b_mint.key() == pool.load()?.token_b_mint
+    )]
+    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // LP token mint to issue shares
+    #[account(
+        mut,
+        constraint = lp_token_mint.key() == pool.load()?.lp_token_mint,
+        constraint = lp_token_mint.to_account_info().owner == lp_token_program.key
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // User's token A source account
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_a_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // User's token B source account
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_b_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // User's account to receive LP tokens
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = lp_token_program,
+    )]
+    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
+
+    // The DEX whose fee collector receives the protocol fee of MintLp pools, there is only the
+    // one of ADMIN_PUBKEY
+    #[account(
         seeds = [
-            b"liquidity_pool",
-            token_a_mint.key().as_ref(),
-            token_b_mint.key().as_ref(),
+            b"dex_state",
+            crate::ADMIN_PUBKEY.as_ref(),
+        ],
+        bump
+    )]
+    pub dex_state: Box<Account<'info, DexState>>,
+
+    // Fee collector's LP token account, receives the protocol fee of MintLp pools
+    // - Optional: only needed, and only has to exist, when the pool has protocol fee LP tokens
+    //   to mint
+    /// CHECK: owner and mint checked by mint_protocol_fee_liquidity when it mints to it
+    #[account(mut)]
+    pub fee_collector_lp_token: Option<UncheckedAccount<'info>>,
+
+    // Required programs
+    // - lp_token_program owns the LP mint: Token-2022, or SPL Token for pools of the first release
+    pub token_program: Interface<'info, TokenInterface>,
+    pub lp_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawLiquidity<'info> {
+    // LP owner removing liquidity
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // Pool to withdraw from
+    #[account(mut)]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Token mint addresses
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    // Pool's token A reserve account
+    #[account(
+        mut,
+        constraint = pool_token_a.key() == pool.load()?.token_a_account,
+        constraint = token_a_mint.key() == pool.load()?.token_a_mint
+    )]
+    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // Pool's token B reserve account
+    #[account(
+        mut,
+        constraint = pool_token_b.key() == pool.load()?.token_b_account,
+        constraint = token_b_mint.key() == pool.load()?.token_b_mint
+    )]
+    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // LP token mint to burn from
+    #[account(
+        mut,
+        constraint = lp_token_mint.key() == pool.load()?.lp_token_mint,
+        constraint = lp_token_mint.to_account_info().owner == lp_token_program.key
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // User's token A account to receive funds
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_a_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // User's token B account to receive funds
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_b_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // User's LP tokens to burn
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = lp_token_program,
+    )]
+    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
+
+    // The DEX whose fee collector receives the protocol fee of MintLp pools, there is only the
+    // one of ADMIN_PUBKEY
+    #[account(
+        seeds = [
+            b"dex_state",
+            crate::ADMIN_PUBKEY.as_ref(),
+        ],
+        bump
+    )]
+    pub dex_state: Box<Account<'info, DexState>>,
+
+    // Fee collector's LP token account, receives the protocol fee of MintLp pools
+    // - Optional: only needed, and only has to exist, when the pool has protocol fee LP tokens
+    //   to mint
+    /// CHECK: owner and mint checked by mint_protocol_fee_liquidity when it mints to it
+    #[account(mut)]
+    pub fee_collector_lp_token: Option<UncheckedAccount<'info>>,
+
+    // Required programs
+    // - lp_token_program owns the LP mint: Token-2022, or SPL Token for pools of the first release
+    pub token_program: Interface<'info, TokenInterface>,
+    pub lp_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    // User swapping tokens and paying for tx fees
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // Pool that contains the trading pair
+    #[account(mut)]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Token the user is swapping from
+    pub source_mint: InterfaceAccount<'info, Mint>,
+
+    // Token the user is swapping to
+    pub destination_mint: InterfaceAccount<'info, Mint>,
+
+    // The DEX the mint listings belong to, there is only the one of ADMIN_PUBKEY
+    #[account(
+        seeds = [
+            b"dex_state",
+            crate::ADMIN_PUBKEY.as_ref(),
+        ],
+        bump
+    )]
+    pub dex_state: Box<Account<'info, DexState>>,
+
+    // Governance's listing of each mint, denied mints can't be traded
+    // - Empty when governance never listed the mint
+    /// CHECK: address checked by the seeds, read by check_swap_mint
+    #[account(
+        seeds = [
+            b"mint_status",
+            dex_state.key().as_ref(),
+            source_mint.key().as_ref(),
+        ],
+        bump
+    )]
+    pub source_mint_status: UncheckedAccount<'info>,
+    /// CHECK: address checked by the seeds, read by check_swap_mint
+    #[account(
+        seeds = [
+            b"mint_status",
+            dex_state.key().as_ref(),
+            destination_mint.key().as_ref(),
         ],
         bump
     )]
-    pub pool: Account<'info, LiquidityPool>,
+    pub destination_mint_status: UncheckedAccount<'info>,
+
+    // Pool's token A account
+    // Verifies account matches pool record and is part of the swap
+    #[account(
+        mut,
+        constraint = pool_token_a.key() == pool.load()?.token_a_account,
+        constraint = (source_mint.key() == pool.load()?.token_a_mint || 
+                     destination_mint.key() == pool.load()?.token_a_mint)
+    )]
+    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    // Pool's token B account
+    // Verifies account matches pool record and is part of the swap
+    #[account(
+        mut,
+        constraint = pool_token_b.key() == pool.load()?.token_b_account,
+        constraint = (source_mint.key() == pool.load()?.token_b_mint || 
+                     destination_mint.key() == pool.load()?.token_b_mint)
+    )]
+    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    // User's source token account (where tokens come from)
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = source_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_source_token: InterfaceAccount<'info, TokenAccount>,
+
+    // User's destination token account (where tokens go)
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = destination_mint,
+        associated_token::authority = owner,
+    )]
+    pub user_destination_token: InterfaceAccount<'info, TokenAccount>,
 
-    // Create a token account to hold the pool's reserves of Token A
-    // - The pool itself has control over this account
+    // Required program references
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Defines the accounts required for collecting protocol fees
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    // Only the admin can collect fees
     #[account(
         mut,
-        token::mint = token_a_mint,
-        token::authority = pool,
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state to verify admin and fee collector
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+
+    // The pool to collect fees from
+    #[account(mut)]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Token A mint info
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+
+    // Token B mint info
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    // Pool's token A account holding reserves
+    #[account(
+        mut,
+        constraint = pool_token_a.key() == pool.load()?.token_a_account,
+        constraint = token_a_mint.key() == pool.load()?.token_a_mint
     )]
     pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
 
-    // Create a token account to hold the pool's reserves of Token B
-    // - The pool itself has control over this account
+    // Pool's token B account holding reserves
     #[account(
         mut,
-        token::mint = token_b_mint,
-        token::authority = pool,
+        constraint = pool_token_b.key() == pool.load()?.token_b_account,
+        constraint = token_b_mint.key() == pool.load()?.token_b_mint
     )]
     pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
 
-    // Create a new token type that represents shares in this pool
-    // - 6 decimal places for precision
-    // - The pool has authority to mint these tokens
+    // Fee collector's token A account
     #[account(
-        init,
-        payer = owner,
-        mint::decimals = 6,
-        mint::authority = pool,
+        mut,
+        constraint = fee_collector_token_a.owner == dex_state.fee_collector,
+        constraint = fee_collector_token_a.mint == pool.load()?.token_a_mint
     )]
-    pub lp_token_mint: InterfaceAccount<'info, Mint>,
+    pub fee_collector_token_a: InterfaceAccount<'info, TokenAccount>,
 
-    // Create a token account for the pool creator to receive LP tokens
-    // - Only created if it doesn't exist already
-    // - The owner has control over this account
+    // Fee collector's token B account
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = lp_token_mint,
-        associated_token::authority = owner,
+        mut, 
+        constraint = fee_collector_token_b.owner == dex_state.fee_collector,
+        constraint = fee_collector_token_b.mint == pool.load()?.token_b_mint
     )]
-    pub owner_lp_token: InterfaceAccount<'info, TokenAccount>,
+    pub fee_collector_token_b: InterfaceAccount<'info, TokenAccount>,
 
-    // Required Solana programs for handling tokens and accounts
+    // Required for token operations
     pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
-#[account]
-pub struct DexState {
-    pub admin: Pubkey,
-    /// Incremented each time a new trading pair is created
-    pub pools_count: u64,
-    /// Using two integers to represent a fraction allows for exact calculations using only integer
-    /// math, which is more deterministic.
-    pub fee_numerator: u64,
-    pub fee_denominator: u64,
-    /// Protocol fee as a percentage of the total fee
-    /// protocol_fee_percentage is a number between 0 and 100
-    /// If set to 30, it means 30% of fees go to protocol, 70% to LPs
-    pub protocol_fee_percentage: u8,
-    /// Account that collects protocol fees
-    pub fee_collector: Pubkey,
+/// Defines the accounts required for closing an empty pool
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    // Only the pool creator or the admin can close a pool
+    #[account(
+        constraint = (authority.key() == pool.load()?.creator
+            || authority.key() == dex_state.admin) @ DexError::NotPoolCreator
+    )]
+    pub authority: Signer<'info>,
+
+    // The pool creator receives the rent of every closed account
+    #[account(
+        mut,
+        address = pool.load()?.creator
+    )]
+    pub creator: SystemAccount<'info>,
+
+    // DEX state to verify admin and fee collector, and count closed pools
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+
+    // The pool to close
+    // - Only once all liquidity is withdrawn and the protocol fees are collected
+    #[account(
+        mut,
+        close = creator,
+        constraint = pool.load()?.total_liquidity == 0 @ DexError::PoolNotEmpty,
+        constraint = (pool.load()?.protocol_fees_token_a == 0
+            && pool.load()?.protocol_fees_token_b == 0) @ DexError::ProtocolFeesNotCollected
+    )]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Registry page listing the pool
+    // - None for pools created before the registry, which aren't listed
+    #[account(
+        mut,
+        seeds = [
+            b"pool_registry",
+            dex_state.key().as_ref(),
+            registry_page_index(pool.load()?.registry_index).to_le_bytes().as_ref(),
+        ],
+        bump = registry_page.bump
+    )]
+    pub registry_page: Option<Box<Account<'info, PoolRegistryPage>>>,
+
+    // Lookup of the pools of the pool's mint pair
+    // - None for pools created before the registry, which aren't listed
+    #[account(
+        mut,
+        seeds = [
+            b"pool_pair",
+            dex_state.key().as_ref(),
+            sorted_pair_mints(pool.load()?.token_a_mint, pool.load()?.token_b_mint).0.as_ref(),
+            sorted_pair_mints(pool.load()?.token_a_mint, pool.load()?.token_b_mint).1.as_ref(),
+        ],
+        bump = pool_pair.bump
+    )]
+    pub pool_pair: Option<Box<Account<'info, PoolPair>>>,
+
+    // Token A mint info, mutable to receive withheld transfer fees
+    #[account(
+        mut,
+        address = pool.load()?.token_a_mint
+    )]
+    pub token_a_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // Token B mint info, mutable to receive withheld transfer fees
+    #[account(
+        mut,
+        address = pool.load()?.token_b_mint
+    )]
+    pub token_b_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // Pool's token A account, closed
+    #[account(
+        mut,
+        address = pool.load()?.token_a_account
+    )]
+    pub pool_token_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Pool's token B account, closed
+    #[account(
+        mut,
+        address = pool.load()?.token_b_account
+    )]
+    pub pool_token_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // LP token mint of the pool, closed
+    #[account(
+        mut,
+        address = pool.load()?.lp_token_mint,
+        constraint = lp_token_mint.to_account_info().owner == lp_token_program.key
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // Fee collector's token A account, receives what is left in the vault
+    #[account(
+        mut,
+        constraint = fee_collector_token_a.owner == dex_state.fee_collector,
+        constraint = fee_collector_token_a.mint == pool.load()?.token_a_mint
+    )]
+    pub fee_collector_token_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Fee collector's token B account, receives what is left in the vault
+    #[account(
+        mut,
+        constraint = fee_collector_token_b.owner == dex_state.fee_collector,
+        constraint = fee_collector_token_b.mint == pool.load()?.token_b_mint
+    )]
+    pub fee_collector_token_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // token_program owns the pair's mints, lp_token_program the LP mint (SPL Token for pools of
+    // the first release)
+    pub token_program: Interface<'info, TokenInterface>,
+    pub lp_token_program: Interface<'info, TokenInterface>,
 }
 
-impl DexState {
-    pub const LEN: usize = 32 + 8 + 8 + 8 + 1 + 32; // admin + pools_count + fee_numerator + fee_denominator + protocol_fee_percentage +
-                                                    // fee_collector
+/// Defines the accounts required for configuring the dynamic fee of a pool
+#[derive(Accounts)]
+pub struct ConfigureDynamicFee<'info> {
+    // The admin, or the pool creator when governance granted them the permission
+    #[account(
+        constraint = (authority.key() == dex_state.admin
+            || (authority.key() == pool.load()?.creator
+                && creator_permissions.as_ref().is_some_and(|permissions| {
+                    permissions.allows(PERMISSION_CONFIGURE_DYNAMIC_FEE)
+                }))) @ DexError::NotAdmin
+    )]
+    pub authority: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The pool whose fee mode is changed
+    #[account(mut)]
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // Permissions governance granted to the pool creator
+    // - Optional: only needed when the creator signs
+    #[account(
+        seeds = [
+            b"creator_permissions",
+            dex_state.key().as_ref(),
+            pool.load()?.creator.as_ref(),
+        ],
+        bump = creator_permissions.bump
+    )]
+    pub creator_permissions: Option<Account<'info, CreatorPermissions>>,
 }
 
-#[account]
-pub struct LiquidityPool {
-    // Token A mint address
-    pub token_a_mint: Pubkey,
-    // Token B mint address
-    pub token_b_mint: Pubkey,
-    // Pool's token A account holding reserves
-    pub token_a_account: Pubkey,
-    // Pool's token B account holding reserves
-    pub token_b_account: Pubkey,
-    // LP token mint issued to liquidity providers
-    pub lp_token_mint: Pubkey,
-    // Bump seed for PDA derivation
-    pub bump: u8,
-    // Total LP tokens minted for this pool
-    pub total_liquidity: u64,
-    // Fee numerator (e.g. 10 for a 1% fee)
-    pub fee_numerator: u64,
-    // Fee denominator (e.g. 1000 for a 1% fee)
-    pub fee_denominator: u64,
-    // Protocol fee percentage (0-100)
-    pub protocol_fee_percentage: u8,
-    // Accumulated fees for token A (for protocol)
-    pub protocol_fees_token_a: u64,
-    // Accumulated fees for token B (for protocol)
-    pub protocol_fees_token_b: u64,
+/// Defines the accounts required for setting the pool creation mode
+#[derive(Accounts)]
+pub struct SetPoolCreationMode<'info> {
+    // Only the admin decides who may create pools
+    #[account(
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state holding the mode
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
 }
 
-impl LiquidityPool {
-    pub const LEN: usize = 32 + 32 + 32 + 32 + 32 + 1 + 8 + 8 + 8 + 1 + 8 + 8; // token_a_mint + token_b_mint + token_a_account + token_b_account + lp_token_mint + bump +
-                                                                               // total_liquidity + fees + protocol_fee_percentage + protocol_fees
+/// Defines the accounts required for setting the permissions of a pool creator
+#[derive(Accounts)]
+#[instruction(creator: Pubkey)]
+pub struct SetCreatorPermissions<'info> {
+    // Only the admin can grant permissions, and pays for the permissions account
+    #[account(
+        mut,
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The creator's permissions, created on first use
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CreatorPermissions::LEN,
+        seeds = [
+            b"creator_permissions",
+            dex_state.key().as_ref(),
+            creator.as_ref(),
+        ],
+        bump
+    )]
+    pub creator_permissions: Account<'info, CreatorPermissions>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Defines the accounts required for listing a mint
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct SetMintStatus<'info> {
+    // Only the admin can list mints, and pays for the status account
+    #[account(
+        mut,
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The mint's status, created on first use
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + MintStatus::LEN,
+        seeds = [
+            b"mint_status",
+            dex_state.key().as_ref(),
+            mint.as_ref(),
+        ],
+        bump
+    )]
+    pub mint_status: Account<'info, MintStatus>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Defines the accounts required for requiring allowed mints in new pools
+#[derive(Accounts)]
+pub struct SetRequireAllowedMints<'info> {
+    // Only the admin decides which mints new pools accept
+    #[account(
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state holding the requirement
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
+}
+
+/// Defines the accounts required for setting the protocol fee mode of new pools
+#[derive(Accounts)]
+pub struct SetProtocolFeeMode<'info> {
+    // Only the admin decides how the protocol fee is taken
+    #[account(
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state holding the mode
+    #[account(mut)]
+    pub dex_state: Account<'info, DexState>,
 }
 
+/// Defines the accounts required for migrating a pool to the current layout
 #[derive(Accounts)]
-pub struct DepositLiquidity<'info> {
-    // Liquidity provider
+pub struct MigratePool<'info> {
+    // Anyone can migrate a pool, and pays for the extra space
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub payer: Signer<'info>,
 
-    // Target pool for deposit
-    #[account(mut)]
-    pub pool: Account<'info, LiquidityPool>,
+    /// CHECK: a pool in an earlier layout, which `Account` can't deserialize; the handler checks
+    /// its discriminator and size
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub pool: UncheckedAccount<'info>,
 
-    // Token mint definitions
-    pub token_a_mint: InterfaceAccount<'info, Mint>,
-    pub token_b_mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
 
-    // Pool's token A reserve account
+/// Defines the accounts required for migrating the DEX state to the current layout
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    // Only the admin can migrate the DEX state, and pays for the extra space
     #[account(
         mut,
-        constraint = pool_token_a.key() == pool.token_a_account,
-        constraint = token_a_mint.key() == pool.token_a_mint
+        constraint = admin.key() == crate::ADMIN_PUBKEY @ DexError::NotAdmin
     )]
-    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub admin: Signer<'info>,
 
-    // Pool's token B reserve account
+    /// CHECK: the DEX state in an earlier layout, which `Account` can't deserialize; its address
+    /// is checked by the seeds, its discriminator and size by the handler
     #[account(
         mut,
-        constraint = pool_token_b.key() == pool.token_b_account,
-        constraint = token_b_mint.key() == pool.token_b_mint
+        seeds = [
+            b"dex_state",
+            admin.key().as_ref(),
+        ],
+        bump,
+        owner = crate::ID
     )]
-    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub dex_state: UncheckedAccount<'info>,
 
-    // LP token mint to issue shares
+    pub system_program: Program<'info, System>,
+}
+
+/// Defines the accounts required for creating a farm on a pool
+#[derive(Accounts)]
+pub struct CreateFarm<'info> {
+    // Only the admin can create farms, and pays for the farm accounts
     #[account(
         mut,
-        constraint = lp_token_mint.key() == pool.lp_token_mint
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
     )]
-    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub admin: Signer<'info>,
 
-    // User's token A source account
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // Pool whose LP token is staked
+    pub pool: AccountLoader<'info, LiquidityPool>,
+
+    // LP token mint of the pool
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = token_a_mint,
-        associated_token::authority = owner,
+        constraint = lp_token_mint.key() == pool.load()?.lp_token_mint
     )]
-    pub user_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
 
-    // User's token B source account
+    // The farm account, one per pool
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = token_b_mint,
-        associated_token::authority = owner,
+        init,
+        payer = admin,
+        space = 8 + Farm::LEN,
+        seeds = [
+            b"farm",
+            pool.key().as_ref(),
+        ],
+        bump
     )]
-    pub user_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub farm: Box<Account<'info, Farm>>,
 
-    // User's account to receive LP tokens
+    // Farm-owned account holding the staked LP tokens
     #[account(
-        init_if_needed,
-        payer = owner,
+        init,
+        payer = admin,
         associated_token::mint = lp_token_mint,
-        associated_token::authority = owner,
+        associated_token::authority = farm,
     )]
-    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
+    pub lp_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
     // Required programs
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
+/// Defines the accounts required for managing the funders of a farm
 #[derive(Accounts)]
-pub struct WithdrawLiquidity<'info> {
-    // LP owner removing liquidity
+pub struct SetFarmFunder<'info> {
+    // Only the admin can manage funders
+    #[account(
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The farm whose funders change
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub farm: Box<Account<'info, Farm>>,
+}
 
-    // Pool to withdraw from
+/// Defines the accounts required for adding a reward stream to a farm
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    // Admin or authorized funder, pays for the reward vault
     #[account(mut)]
-    pub pool: Account<'info, LiquidityPool>,
+    pub funder: Signer<'info>,
 
-    // Token mint addresses
-    pub token_a_mint: InterfaceAccount<'info, Mint>,
-    pub token_b_mint: InterfaceAccount<'info, Mint>,
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
 
-    // Pool's token A reserve account
+    // The farm receiving the new stream
+    #[account(mut)]
+    pub farm: Box<Account<'info, Farm>>,
+
+    // Token paid out by the new stream
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // Farm-owned account holding the reward tokens (one stream per reward mint)
+    #[account(
+        init,
+        payer = funder,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Required programs
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Defines the accounts required for funding a reward stream
+#[derive(Accounts)]
+#[instruction(stream_index: u8)]
+pub struct FundFarm<'info> {
+    // Admin or funder of the stream
+    pub funder: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The farm being funded
+    #[account(
+        constraint = farm.reward_stream(stream_index).is_some() @ DexError::InvalidRewardStream
+    )]
+    pub farm: Box<Account<'info, Farm>>,
+
+    // Token paid out by the stream
+    #[account(
+        constraint = reward_mint.key() == farm.reward_streams[stream_index as usize].reward_mint
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    // Stream's reward vault
     #[account(
         mut,
-        constraint = pool_token_a.key() == pool.token_a_account,
-        constraint = token_a_mint.key() == pool.token_a_mint
+        constraint = reward_vault.key() == farm.reward_streams[stream_index as usize].reward_vault
     )]
-    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
 
-    // Pool's token B reserve account
+    // Funder's account the rewards come from
     #[account(
         mut,
-        constraint = pool_token_b.key() == pool.token_b_account,
-        constraint = token_b_mint.key() == pool.token_b_mint
+        token::mint = reward_mint,
+        token::authority = funder,
     )]
-    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub funder_reward_token: InterfaceAccount<'info, TokenAccount>,
 
-    // LP token mint to burn from
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Defines the accounts required for extending a reward stream
+#[derive(Accounts)]
+#[instruction(stream_index: u8)]
+pub struct ExtendFarm<'info> {
+    // Admin or funder of the stream
+    pub funder: Signer<'info>,
+
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The farm whose stream is extended
     #[account(
         mut,
-        constraint = lp_token_mint.key() == pool.lp_token_mint
+        constraint = farm.reward_stream(stream_index).is_some() @ DexError::InvalidRewardStream
     )]
-    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub farm: Box<Account<'info, Farm>>,
 
-    // User's token A account to receive funds
+    // Stream's reward vault, must cover the new schedule
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = token_a_mint,
-        associated_token::authority = owner,
+        constraint = reward_vault.key() == farm.reward_streams[stream_index as usize].reward_vault
     )]
-    pub user_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+}
 
-    // User's token B account to receive funds
+#[derive(Accounts)]
+pub struct StakeLp<'info> {
+    // LP holder staking tokens and paying for the position account
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // Farm to stake in
+    #[account(mut)]
+    pub farm: Box<Account<'info, Farm>>,
+
+    // LP token mint of the farm's pool
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = token_b_mint,
-        associated_token::authority = owner,
+        constraint = lp_token_mint.key() == farm.lp_token_mint
     )]
-    pub user_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
-    // User's LP tokens to burn
+    // Farm's LP vault
+    #[account(
+        mut,
+        constraint = lp_vault.key() == farm.lp_vault
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // User's LP tokens to stake
+    #[account(
+        mut,
+        token::mint = lp_token_mint,
+        token::authority = owner,
+    )]
+    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
+
+    // User's position in the farm, created on first stake
     #[account(
         init_if_needed,
         payer = owner,
-        associated_token::mint = lp_token_mint,
-        associated_token::authority = owner,
+        space = 8 + StakePosition::LEN,
+        seeds = [
+            b"stake_position",
+            farm.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump
     )]
-    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
+    pub stake_position: Box<Account<'info, StakePosition>>,
 
     // Required programs
     pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct Swap<'info> {
-    // User swapping tokens and paying for tx fees
-    #[account(mut)]
+pub struct UnstakeLp<'info> {
+    // Staker withdrawing LP tokens
     pub owner: Signer<'info>,
 
-    // Pool that contains the trading pair
+    // Farm to unstake from
     #[account(mut)]
-    pub pool: Account<'info, LiquidityPool>,
+    pub farm: Box<Account<'info, Farm>>,
 
-    // Token the user is swapping from
-    pub source_mint: InterfaceAccount<'info, Mint>,
+    // LP token mint of the farm's pool
+    #[account(
+        constraint = lp_token_mint.key() == farm.lp_token_mint
+    )]
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
-    // Token the user is swapping to
-    pub destination_mint: InterfaceAccount<'info, Mint>,
+    // Farm's LP vault
+    #[account(
+        mut,
+        constraint = lp_vault.key() == farm.lp_vault
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
 
-    // Pool's token A account
-    // Verifies account matches pool record and is part of the swap
+    // User's account to receive the LP tokens
     #[account(
         mut,
-        constraint = pool_token_a.key() == pool.token_a_account,
-        constraint = (source_mint.key() == pool.token_a_mint || 
-                     destination_mint.key() == pool.token_a_mint)
+        token::mint = lp_token_mint,
+        token::authority = owner,
     )]
-    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub user_lp_token: InterfaceAccount<'info, TokenAccount>,
 
-    // Pool's token B account
-    // Verifies account matches pool record and is part of the swap
+    // User's position in the farm
     #[account(
         mut,
-        constraint = pool_token_b.key() == pool.token_b_account,
-        constraint = (source_mint.key() == pool.token_b_mint || 
-                     destination_mint.key() == pool.token_b_mint)
+        seeds = [
+            b"stake_position",
+            farm.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump = stake_position.bump
     )]
-    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub stake_position: Box<Account<'info, StakePosition>>,
 
-    // User's source token account (where tokens come from)
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_index: u8)]
+pub struct ClaimRewards<'info> {
+    // Staker claiming rewards and paying for the reward account if needed
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // Farm the rewards come from
     #[account(
-        init_if_needed,
-        payer = owner,
-        associated_token::mint = source_mint,
-        associated_token::authority = owner,
+        mut,
+        constraint = farm.reward_stream(stream_index).is_some() @ DexError::InvalidRewardStream
     )]
-    pub user_source_token: InterfaceAccount<'info, TokenAccount>,
+    pub farm: Box<Account<'info, Farm>>,
 
-    // User's destination token account (where tokens go)
+    // Token paid out by the stream
+    #[account(
+        constraint = reward_mint.key() == farm.reward_streams[stream_index as usize].reward_mint
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    // Stream's reward vault
+    #[account(
+        mut,
+        constraint = reward_vault.key() == farm.reward_streams[stream_index as usize].reward_vault
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // User's account to receive the rewards
     #[account(
         init_if_needed,
         payer = owner,
-        associated_token::mint = destination_mint,
+        associated_token::mint = reward_mint,
         associated_token::authority = owner,
     )]
-    pub user_destination_token: InterfaceAccount<'info, TokenAccount>,
+    pub user_reward_token: InterfaceAccount<'info, TokenAccount>,
 
-    // Required program references
+    // User's position in the farm
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            farm.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Box<Account<'info, StakePosition>>,
+
+    // Required programs
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
-/// Defines the accounts required for collecting protocol fees
+/// Defines the accounts required for creating the mint extension allowlist
 #[derive(Accounts)]
-pub struct CollectProtocolFees<'info> {
-    // Only the admin can collect fees
+pub struct CreateExtensionAllowlist<'info> {
+    // Only the admin can create the allowlist, and pays for it
     #[account(
         mut,
         constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
     )]
     pub admin: Signer<'info>,
 
-    // DEX state to verify admin and fee collector
-    #[account(mut)]
+    // DEX state to verify admin
     pub dex_state: Account<'info, DexState>,
 
-    // The pool to collect fees from
-    #[account(mut)]
-    pub pool: Account<'info, LiquidityPool>,
-
-    // Token A mint info
-    pub token_a_mint: InterfaceAccount<'info, Mint>,
-
-    // Token B mint info
-    pub token_b_mint: InterfaceAccount<'info, Mint>,
-
-    // Pool's token A account holding reserves
+    // The allowlist account, one per DEX
     #[account(
-        mut,
-        constraint = pool_token_a.key() == pool.token_a_account,
-        constraint = token_a_mint.key() == pool.token_a_mint
+        init,
+        payer = admin,
+        space = 8 + ExtensionAllowlist::LEN,
+        seeds = [
+            b"extension_allowlist",
+            dex_state.key().as_ref(),
+        ],
+        bump
     )]
-    pub pool_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub extension_allowlist: Box<Account<'info, ExtensionAllowlist>>,
 
-    // Pool's token B account holding reserves
-    #[account(
-        mut,
-        constraint = pool_token_b.key() == pool.token_b_account,
-        constraint = token_b_mint.key() == pool.token_b_mint
-    )]
-    pub pool_token_b: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
 
-    // Fee collector's token A account
+/// Defines the accounts required for changing the mint extension allowlist
+#[derive(Accounts)]
+pub struct UpdateExtensionAllowlist<'info> {
+    // Only the admin can manage the allowlist
     #[account(
-        mut,
-        constraint = fee_collector_token_a.owner == dex_state.fee_collector,
-        constraint = fee_collector_token_a.mint == pool.token_a_mint
+        constraint = admin.key() == dex_state.admin @ DexError::NotAdmin
     )]
-    pub fee_collector_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub admin: Signer<'info>,
 
-    // Fee collector's token B account
+    // DEX state to verify admin
+    pub dex_state: Account<'info, DexState>,
+
+    // The allowlist being changed
     #[account(
-        mut, 
-        constraint = fee_collector_token_b.owner == dex_state.fee_collector,
-        constraint = fee_collector_token_b.mint == pool.token_b_mint
+        mut,
+        seeds = [
+            b"extension_allowlist",
+            dex_state.key().as_ref(),
+        ],
+        bump = extension_allowlist.bump
     )]
-    pub fee_collector_token_b: InterfaceAccount<'info, TokenAccount>,
-
-    // Required for token operations
-    pub token_program: Interface<'info, TokenInterface>,
+    pub extension_allowlist: Box<Account<'info, ExtensionAllowlist>>,
 }
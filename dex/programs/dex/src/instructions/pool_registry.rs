@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+/// Number of pools listed by each registry page
+pub const POOLS_PER_REGISTRY_PAGE: usize = 16;
+
+/// Maximum number of pools, one per fee tier, the lookup of a mint pair can point to
+/// Fee tiers are capped at the same number so that every pair can have a pool on each of them
+pub const MAX_POOLS_PER_PAIR: usize = 16;
+
+/// Index of the registry page listing the pool created as number `registry_index`
+pub fn registry_page_index(registry_index: u64) -> u64 {
+    registry_index / POOLS_PER_REGISTRY_PAGE as u64
+}
+
+/// Mints of a pair in the order used by the pair lookup seeds, so both mint orders of a pair
+/// share one lookup
+pub fn sorted_pair_mints(mint_a: Pubkey, mint_b: Pubkey) -> (Pubkey, Pubkey) {
+    if mint_a <= mint_b {
+        (mint_a, mint_b)
+    } else {
+        (mint_b, mint_a)
+    }
+}
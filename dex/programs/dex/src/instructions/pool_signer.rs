@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+use super::LiquidityPool;
+
+/// A pool account together with its PDA seeds, copied out of the zero-copy data so the pool can
+/// sign CPIs without its data being borrowed
+pub(crate) struct PoolSigner<'info> {
+    info: AccountInfo<'info>,
+    token_a_mint: Pubkey,
+    token_b_mint: Pubkey,
+    // None for pools created before fee tiers, whose address wasn't derived from one
+    fee_tier_index: Option<[u8; 2]>,
+    bump: [u8; 1],
+}
+
+impl<'info> PoolSigner<'info> {
+    pub(crate) fn new(
+        pool: &AccountLoader<'info, LiquidityPool>,
+        pool_state: &LiquidityPool,
+    ) -> Self {
+        Self {
+            info: pool.to_account_info(),
+            token_a_mint: pool_state.token_a_mint,
+            token_b_mint: pool_state.token_b_mint,
+            fee_tier_index: (pool_state.fee_tier_index != LiquidityPool::LEGACY_FEE_TIER_INDEX)
+                .then(|| pool_state.fee_tier_index.to_le_bytes()),
+            bump: [pool_state.bump],
+        }
+    }
+
+    /// Seeds to sign with as the pool PDA
+    pub(crate) fn seeds(&self) -> Vec<&[u8]> {
+        let mut seeds: Vec<&[u8]> = vec![
+            b"liquidity_pool",
+            self.token_a_mint.as_ref(),
+            self.token_b_mint.as_ref(),
+        ];
+        if let Some(fee_tier_index) = &self.fee_tier_index {
+            seeds.push(fee_tier_index);
+        }
+        seeds.push(&self.bump);
+
+        seeds
+    }
+
+    pub(crate) fn to_account_info(&self) -> AccountInfo<'info> {
+        self.info.clone()
+    }
+
+    pub(crate) fn key(&self) -> Pubkey {
+        self.info.key()
+    }
+}
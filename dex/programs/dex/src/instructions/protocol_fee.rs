@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use super::{mint_lp_tokens_to_user, DexError, PoolSigner};
+
+/// How the protocol takes its share of the swap fees of a pool
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProtocolFeeMode {
+    /// Each swap sets the protocol share aside in the input token, in the pool's protocol fee
+    /// counters, until `collect_fees` moves it to the fee collector
+    #[default]
+    Accrued,
+    /// The whole fee stays in the reserves; deposits and withdrawals mint the fee collector LP
+    /// tokens worth the protocol share of the growth of sqrt(k) since the last one (Uniswap v2)
+    MintLp,
+}
+
+/// Mints `amount` LP tokens of protocol fee to the current fee collector of the DEX, when there
+/// are any
+/// The fee collector's LP token account is only required, and only has to exist, then
+pub(crate) fn mint_protocol_fee_liquidity<'info>(
+    lp_token_program: &Interface<'info, TokenInterface>,
+    lp_token_mint: &InterfaceAccount<'info, Mint>,
+    fee_collector: &Pubkey,
+    fee_collector_lp_token: Option<&UncheckedAccount<'info>>,
+    pool: &PoolSigner<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let fee_collector_lp_token =
+        fee_collector_lp_token.ok_or(error!(DexError::FeeCollectorLpTokenMissing))?;
+
+    // An account the LP token program doesn't own, such as an associated token account not
+    // created yet, is as good as missing
+    require!(
+        fee_collector_lp_token.owner == lp_token_program.key,
+        DexError::FeeCollectorLpTokenMissing
+    );
+    let token_account =
+        TokenAccount::try_deserialize(&mut &fee_collector_lp_token.try_borrow_data()?[..])
+            .map_err(|_| error!(DexError::FeeCollectorLpTokenMissing))?;
+    require!(
+        token_account.owner == *fee_collector && token_account.mint == lp_token_mint.key(),
+        DexError::InvalidFeeCollectorLpToken
+    );
+
+    mint_lp_tokens_to_user(
+        lp_token_program,
+        lp_token_mint,
+        fee_collector_lp_token,
+        pool,
+        amount,
+    )?;
+
+    msg!("Minted {} LP tokens of protocol fee", amount);
+
+    Ok(())
+}
@@ -1,69 +1,57 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use super::{DexError, LiquidityPool};
-
-/// Calculates the output amount for a swap based on constant product formula (x*y=k)
-/// Takes into account the fee charged by the pool
-pub(crate) fn calculate_output_amount(
-    input_amount: u64,
-    input_reserve: u64,
-    output_reserve: u64,
-    fee_numerator: u64,
-    fee_denominator: u64,
-) -> Result<u64> {
-    // Ensure there are enough reserves
-    require!(
-        input_reserve > 0 && output_reserve > 0,
-        DexError::InsufficientLiquidity
-    );
-
-    // Calculate the input amount after fee
-    // fee_amount = input_amount * fee_numerator / fee_denominator
-    // input_amount_with_fee = input_amount - fee_amount
-    let fee_amount = (input_amount as u128)
-        .checked_mul(fee_numerator as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))?
-        .checked_div(fee_denominator as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))? as u64;
-
-    let input_amount_with_fee = input_amount
-        .checked_sub(fee_amount)
-        .ok_or(error!(DexError::InsufficientLiquidity))?;
-
-    // Use the constant product formula: (x + dx) * (y - dy) = x * y
-    // Solved for dy: dy = y * dx / (x + dx)
-    // Where:
-    // x = input_reserve
-    // dx = input_amount_with_fee
-    // y = output_reserve
-    // dy = output_amount
+use super::{
+    calculate_swap_prices, transfer_checked_with_hook, DexError, LiquidityPool, PoolSigner,
+    BPS_DENOMINATOR,
+};
+
+/// Resolves the fee rate of a swap: either the static pool fee or the volatility-based fee
+/// The volatility-based fee measures the price before the swap and after it moved along the
+/// curve by `curve_amounts` (source in, destination out), the fee left out
+/// Returns (fee_numerator, fee_denominator)
+pub(crate) fn resolve_swap_fee(
+    pool: &mut LiquidityPool,
+    is_source_token_a: bool,
+    (source_reserve, destination_reserve): (u64, u64),
+    (source_amount, destination_amount): (u64, u64),
+) -> Result<(u64, u64)> {
+    if !pool.dynamic_fee.is_enabled() {
+        return Ok((pool.fee_numerator, pool.fee_denominator));
+    }
+
+    let (price_before, price_after) = calculate_swap_prices(
+        is_source_token_a,
+        source_reserve,
+        destination_reserve,
+        source_amount,
+        destination_amount,
+    )
+    .ok_or(error!(DexError::InsufficientLiquidity))?;
+    let fee_bps =
+        pool.dynamic_fee
+            .update(Clock::get()?.unix_timestamp, price_before, price_after)?;
+
+    Ok((fee_bps, BPS_DENOMINATOR))
+}
 
-    // Calculate numerator (y * dx)
-    let numerator = (output_reserve as u128)
-        .checked_mul(input_amount_with_fee as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))?;
+/// Adds the protocol share of a swap fee to the pool's accumulator for the input token
+pub(crate) fn accrue_protocol_fee(
+    pool: &mut LiquidityPool,
+    is_source_token_a: bool,
+    protocol_fee: u64,
+) -> Result<()> {
+    let protocol_fees = if is_source_token_a {
+        &mut pool.protocol_fees_token_a
+    } else {
+        &mut pool.protocol_fees_token_b
+    };
 
-    // Calculate denominator (x + dx)
-    let denominator = (input_reserve as u128)
-        .checked_add(input_amount_with_fee as u128)
+    *protocol_fees = protocol_fees
+        .checked_add(protocol_fee)
         .ok_or(error!(DexError::InsufficientLiquidity))?;
 
-    // Calculate output amount (dy)
-    let output_amount = numerator
-        .checked_div(denominator)
-        .ok_or(error!(DexError::InsufficientLiquidity))? as u64;
-
-    // Ensure output amount is not zero
-    require!(output_amount > 0, DexError::InsufficientLiquidity);
-
-    // Ensure output doesn't exceed available reserves
-    require!(
-        output_amount <= output_reserve,
-        DexError::InsufficientLiquidity
-    );
-
-    Ok(output_amount)
+    Ok(())
 }
 
 /// Transfers tokens from user to pool
@@ -73,18 +61,19 @@ pub(crate) fn transfer_source_tokens_to_pool<'info>(
     user_source_token: &InterfaceAccount<'info, TokenAccount>,
     pool_source_token: &InterfaceAccount<'info, TokenAccount>,
     owner: &Signer<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
     amount: u64,
 ) -> Result<()> {
-    let cpi_accounts = anchor_spl::token_interface::TransferChecked {
-        mint: source_mint.to_account_info(),
-        from: user_source_token.to_account_info(),
-        to: pool_source_token.to_account_info(),
-        authority: owner.to_account_info(),
-    };
-
-    let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-
-    anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, source_mint.decimals)
+    transfer_checked_with_hook(
+        source_mint,
+        token_program,
+        user_source_token,
+        pool_source_token,
+        owner.to_account_info(),
+        remaining_accounts,
+        amount,
+        &[],
+    )
 }
 
 /// Transfers tokens from pool to user
@@ -93,54 +82,24 @@ pub(crate) fn transfer_destination_tokens_to_user<'info>(
     token_program: &Interface<'info, TokenInterface>,
     pool_destination_token: &InterfaceAccount<'info, TokenAccount>,
     user_destination_token: &InterfaceAccount<'info, TokenAccount>,
-    pool: &Account<'info, LiquidityPool>,
+    pool: &PoolSigner<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
     amount: u64,
 ) -> Result<()> {
     // Create the PDA signer for the transfer operation
-    let pool_seeds = &[
-        b"liquidity_pool",
-        pool.token_a_mint.as_ref(),
-        pool.token_b_mint.as_ref(),
-        &[pool.bump],
-    ];
+    let pool_seeds = pool.seeds();
     let signer = &[&pool_seeds[..]];
 
-    let cpi_accounts = anchor_spl::token_interface::TransferChecked {
-        mint: destination_mint.to_account_info(),
-        from: pool_destination_token.to_account_info(),
-        to: user_destination_token.to_account_info(),
-        authority: pool.to_account_info(),
-    };
-
-    let cpi_ctx =
-        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
-
-    anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, destination_mint.decimals)
-}
-
-/// Calculates the fee breakdown for a swap
-/// Returns (total_fee_amount, protocol_fee_amount)
-pub(crate) fn calculate_fee_breakdown(
-    input_amount: u64,
-    fee_numerator: u64,
-    fee_denominator: u64,
-    protocol_fee_percentage: u8,
-) -> Result<(u64, u64)> {
-    // Calculate total fee amount
-    let total_fee_amount = (input_amount as u128)
-        .checked_mul(fee_numerator as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))?
-        .checked_div(fee_denominator as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))? as u64;
-
-    // Calculate protocol fee portion
-    let protocol_fee_amount = (total_fee_amount as u128)
-        .checked_mul(protocol_fee_percentage as u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))?
-        .checked_div(100u128)
-        .ok_or(error!(DexError::InsufficientLiquidity))? as u64;
-
-    Ok((total_fee_amount, protocol_fee_amount))
+    transfer_checked_with_hook(
+        destination_mint,
+        token_program,
+        pool_destination_token,
+        user_destination_token,
+        pool.to_account_info(),
+        remaining_accounts,
+        amount,
+        signer,
+    )
 }
 
 /// Transfers tokens from pool to fee collector
@@ -149,32 +108,27 @@ pub(crate) fn transfer_fee_tokens_to_collector<'info>(
     token_program: &Interface<'info, TokenInterface>,
     pool_token: &InterfaceAccount<'info, TokenAccount>,
     fee_collector_token: &InterfaceAccount<'info, TokenAccount>,
-    pool: &Account<'info, LiquidityPool>,
+    pool: &PoolSigner<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
     amount: u64,
 ) -> Result<()> {
     // Create the PDA signer for the transfer operation
-    let pool_seeds = &[
-        b"liquidity_pool",
-        pool.token_a_mint.as_ref(),
-        pool.token_b_mint.as_ref(),
-        &[pool.bump],
-    ];
+    let pool_seeds = pool.seeds();
     let signer = &[&pool_seeds[..]];
 
-    let cpi_accounts = anchor_spl::token_interface::TransferChecked {
-        mint: mint.to_account_info(),
-        from: pool_token.to_account_info(),
-        to: fee_collector_token.to_account_info(),
-        authority: pool.to_account_info(),
-    };
-
-    let cpi_ctx =
-        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
-
-    anchor_spl::token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)
+    transfer_checked_with_hook(
+        mint,
+        token_program,
+        pool_token,
+        fee_collector_token,
+        pool.to_account_info(),
+        remaining_accounts,
+        amount,
+        signer,
+    )
 }
 
 /// Checks if a token mint is Token A or Token B in the pool
-pub(crate) fn is_token_a(pool: &Account<LiquidityPool>, mint: &Pubkey) -> bool {
+pub(crate) fn is_token_a(pool: &LiquidityPool, mint: &Pubkey) -> bool {
     pool.token_a_mint == *mint
 }
@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::{
+        spl_token_2022::{
+            extension::{
+                transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+            },
+            state::Mint as MintState,
+        },
+        Token2022,
+    },
+    token_2022_extensions::transfer_fee::{
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use super::DexError;
+
+/// Transfer fee configuration of a mint, None for legacy SPL mints and Token-2022 mints
+/// without the transfer-fee extension
+fn read_transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != Token2022::id() {
+        return Ok(None);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    Ok(mint_state
+        .get_extension::<TransferFeeConfig>()
+        .ok()
+        .copied())
+}
+
+/// Fee withheld by the token program when `amount` of this mint is transferred in the current epoch
+pub(crate) fn calculate_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let Some(config) = read_transfer_fee_config(mint)? else {
+        return Ok(0);
+    };
+
+    config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(error!(DexError::TransferFeeCalculationFailed))
+}
+
+/// Amount the destination account actually receives when `amount` of this mint is transferred
+pub(crate) fn calculate_amount_received(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let transfer_fee = calculate_transfer_fee(mint, amount)?;
+
+    amount
+        .checked_sub(transfer_fee)
+        .ok_or(error!(DexError::TransferFeeCalculationFailed))
+}
+
+/// Amount that has to be transferred so the destination account receives exactly `amount_received`
+pub(crate) fn calculate_amount_to_send(
+    mint: &InterfaceAccount<Mint>,
+    amount_received: u64,
+) -> Result<u64> {
+    let Some(config) = read_transfer_fee_config(mint)? else {
+        return Ok(amount_received);
+    };
+
+    let transfer_fee = config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount_received)
+        .ok_or(error!(DexError::TransferFeeCalculationFailed))?;
+
+    amount_received
+        .checked_add(transfer_fee)
+        .ok_or(error!(DexError::TransferFeeCalculationFailed))
+}
+
+/// Moves the transfer fees withheld in a token account to its mint, which Token-2022 requires
+/// before the account can be closed
+/// Harvesting is permissionless, and a no-op for mints without the transfer-fee extension
+pub(crate) fn harvest_withheld_transfer_fees<'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+) -> Result<()> {
+    if read_transfer_fee_config(mint)?.is_none() {
+        return Ok(());
+    }
+
+    harvest_withheld_tokens_to_mint(
+        CpiContext::new(
+            token_program.to_account_info(),
+            HarvestWithheldTokensToMint {
+                token_program_id: token_program.to_account_info(),
+                mint: mint.to_account_info(),
+            },
+        ),
+        vec![token_account.to_account_info()],
+    )
+}
@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::{
+        spl_token_2022::{
+            extension::{transfer_hook, StateWithExtensions},
+            onchain::invoke_transfer_checked,
+            state::Mint as MintState,
+        },
+        Token2022,
+    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use super::{DexError, LiquidityPool};
+
+/// Maximum number of transfer hook programs governance can accept in pools
+pub const MAX_HOOK_PROGRAMS: usize = 8;
+
+/// Program called by the Token-2022 transfer hook of a mint, if it has one
+pub(crate) fn get_transfer_hook_program(mint: &InterfaceAccount<Mint>) -> Result<Option<Pubkey>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != Token2022::id() {
+        return Ok(None);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    Ok(transfer_hook::get_program_id(&mint_state))
+}
+
+/// Checks that a pool mint still calls the hook program accepted when the pool was created
+/// The mint's transfer hook authority could otherwise swap in an unreviewed program
+pub(crate) fn check_pool_transfer_hook(
+    pool: &LiquidityPool,
+    mint: &InterfaceAccount<Mint>,
+) -> Result<()> {
+    let accepted_hook = if pool.token_a_mint == mint.key() {
+        pool.token_a_transfer_hook
+    } else {
+        pool.token_b_transfer_hook
+    };
+
+    let current_hook = get_transfer_hook_program(mint)?.unwrap_or_default();
+    require!(
+        current_hook == accepted_hook,
+        DexError::TransferHookNotAllowed
+    );
+
+    Ok(())
+}
+
+/// Transfers tokens with `transfer_checked`, resolving the extra accounts required by the
+/// mint's transfer hook (if any) from `remaining_accounts`
+/// Pass empty `signer_seeds` when the authority signed the transaction itself
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn transfer_checked_with_hook<'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    from_account: &InterfaceAccount<'info, TokenAccount>,
+    to_account: &InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_transfer_checked(
+        token_program.key,
+        from_account.to_account_info(),
+        mint.to_account_info(),
+        to_account.to_account_info(),
+        authority,
+        remaining_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )
+    .map_err(Into::into)
+}
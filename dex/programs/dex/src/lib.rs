@@ -15,11 +15,23 @@ use anchor_lang::prelude::*;
 pub mod instructions;
 pub use instructions::*;
 
+#[cfg(not(feature = "test-admin"))]
 declare_id!("Ge4hd4p2D7Y5D9hZCabgXCGk6zpgPbHNC7fv2gsWAZrX");
 
+// Program id of the `test-admin` builds, so that one deployed by mistake at the program address
+// fails every instruction with `DeclaredProgramIdMismatch` instead of trusting the test admin
+#[cfg(feature = "test-admin")]
+declare_id!("7kuWZbpy4Xw84XDABfNmRfrX6G4fVG7BE9p3VGKR37se");
+
 /// This key is used to authenticate administrative actions within the governance contract.
+#[cfg(not(feature = "test-admin"))]
 pub const ADMIN_PUBKEY: Pubkey = pubkey!("E88MCgENj4uksz3QX9DUYRKqM8sJfqHGxCueWDnTPDep");
 
+/// Admin of the program tests, whose keypair is derived from a public seed in the test harness
+/// Never deploy a build with the `test-admin` feature
+#[cfg(feature = "test-admin")]
+pub const ADMIN_PUBKEY: Pubkey = pubkey!("9CqQTMwcNDrcqUU32yjVyGN3A47zu4ZNr99Pz9JJ571");
+
 // This module defines the main instruction handlers for the DEX program
 // Each function corresponds to a different instruction that can be invoked
 #[program]
@@ -61,8 +73,37 @@ pub mod dex {
         )
     }
 
+    /// Changes the DEX fees and fee collector, without touching the pool counters
+    /// Pools minting their protocol fee as LP tokens mint it to the new fee collector from then on
+    /// Only callable by admin
+    pub fn set_fees(
+        ctx: Context<SetFees>,
+        fee_numerator: u64,
+        fee_denominator: u64,
+        protocol_fee_percentage: u8,
+        fee_collector: Pubkey,
+    ) -> Result<()> {
+        instructions::set_dex_fees(
+            ctx,
+            fee_numerator,
+            fee_denominator,
+            protocol_fee_percentage,
+            fee_collector,
+        )
+    }
+
+    /// Registers a fee tier pools can pick at creation, up to MAX_POOLS_PER_PAIR tiers
+    /// Only callable by admin
+    pub fn add_fee_tier(
+        ctx: Context<AddFeeTier>,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<()> {
+        instructions::register_fee_tier(ctx, fee_numerator, fee_denominator)
+    }
+
     /// Creates a new liquidity pool for a token pair
-    /// The pool is identified by the token A and token B mints
+    /// The pool is identified by the token A and token B mints and the fee tier index
     // ┌──────────────┐     ┌──────────────────┐      ┌────────────────────┐
     // │ Pool Creator │────►│ Create Liquidity │─────►│ Set Up Token A & B │
     // └──────────────┘     │  Pool Account    │      │  Reserve Accounts  │
@@ -77,7 +118,7 @@ pub mod dex {
     //                                                           ▼
     //                                                ┌───────────────────┐
     //                                                │ Copy Fee Settings │
-    //                                                │  from DEX State   │
+    //                                                │  from Fee Tier    │
     //                                                └─────────┬─────────┘
     //                                                          │
     //                                                          ▼
@@ -85,8 +126,8 @@ pub mod dex {
     //                                                 │  Increment DEX  │
     //                                                 │  Pools Counter  │
     //                                                 └─────────────────┘
-    pub fn create_pool(ctx: Context<CreatePool>) -> Result<()> {
-        instructions::create_liquidity_pool(ctx)
+    pub fn create_pool(ctx: Context<CreatePool>, fee_tier_index: u16) -> Result<()> {
+        instructions::create_liquidity_pool(ctx, fee_tier_index)
     }
 
     /// Adds liquidity to an existing pool
@@ -119,8 +160,8 @@ pub mod dex {
     //                                       │ Update Total │
     //                                       │  Liquidity   │
     //                                       └──────────────┘
-    pub fn deposit_liquidity(
-        ctx: Context<DepositLiquidity>,
+    pub fn deposit_liquidity<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositLiquidity<'info>>,
         token_a_amount: u64,
         token_b_amount: u64,
     ) -> Result<()> {
@@ -166,7 +207,10 @@ pub mod dex {
     //                              │ Decrease Total │
     //                              │   Liquidity    │
     //                              └────────────────┘
-    pub fn withdraw_liquidity(ctx: Context<WithdrawLiquidity>, lp_amount: u64) -> Result<()> {
+    pub fn withdraw_liquidity<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawLiquidity<'info>>,
+        lp_amount: u64,
+    ) -> Result<()> {
         instructions::perform_liquidity_withdrawal(ctx, lp_amount)
     }
 
@@ -194,10 +238,24 @@ pub mod dex {
     //                                │ Transfer Output Token│
     //                                │ From Pool to User    │
     //                                └──────────────────────┘
-    pub fn swap(ctx: Context<Swap>, input_amount: u64, minimum_output_amount: u64) -> Result<()> {
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        input_amount: u64,
+        minimum_output_amount: u64,
+    ) -> Result<()> {
         instructions::swap_tokens(ctx, input_amount, minimum_output_amount)
     }
 
+    /// Swaps the smallest possible input for exactly `output_amount` of the destination token
+    /// Fails if that takes more than `maximum_input_amount` of the source token
+    pub fn swap_exact_output<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        output_amount: u64,
+        maximum_input_amount: u64,
+    ) -> Result<()> {
+        instructions::swap_tokens_exact_output(ctx, output_amount, maximum_input_amount)
+    }
+
     /// Collects accumulated protocol fees and sends them to the designated collector
     /// Only callable by admin
     // ┌─────────┐     ┌───────┐     ┌───────────────┐      ┌────────────────┐
@@ -230,9 +288,196 @@ pub mod dex {
     //                          │ Transfer Token B Protocol │
     //                          │    Fees to Collector      │
     //                          └───────────────────────────┘
-    pub fn collect_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+    pub fn collect_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CollectProtocolFees<'info>>,
+    ) -> Result<()> {
         instructions::collect_protocol_fees(ctx)
     }
+
+    /// Closes an empty pool, its vaults and its LP mint, returning their rent to the creator
+    /// Callable by the pool creator or the admin once all liquidity is withdrawn and the protocol
+    /// fees are collected; leftover vault dust goes to the fee collector
+    pub fn close_pool<'info>(ctx: Context<'_, '_, 'info, 'info, ClosePool<'info>>) -> Result<()> {
+        instructions::close_liquidity_pool(ctx)
+    }
+
+    /// Upgrades a pool laid out as an earlier version to the current layout
+    /// Callable by anyone; the payer funds the extra rent
+    pub fn migrate_pool(ctx: Context<MigratePool>) -> Result<()> {
+        instructions::migrate_liquidity_pool(ctx)
+    }
+
+    /// Upgrades the DEX state laid out as an earlier version to the current layout
+    /// Only callable by admin
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        instructions::migrate_dex_state(ctx)
+    }
+
+    /// Switches pool creation between open, admin-only and allowlisted creators
+    /// Only callable by admin
+    pub fn set_pool_creation_mode(
+        ctx: Context<SetPoolCreationMode>,
+        mode: PoolCreationMode,
+    ) -> Result<()> {
+        instructions::set_dex_pool_creation_mode(ctx, mode)
+    }
+
+    /// Grants or revokes governance permissions of a pool creator, such as their own pools'
+    /// dynamic fee or, in allowlisted creation mode, creating pools
+    /// Only callable by admin
+    pub fn set_creator_permissions(
+        ctx: Context<SetCreatorPermissions>,
+        creator: Pubkey,
+        permissions: u8,
+    ) -> Result<()> {
+        instructions::set_pool_creator_permissions(ctx, creator, permissions)
+    }
+
+    /// Lists a mint as allowed, denied or unknown
+    /// Denied mints can't be paired in new pools nor traded in existing ones
+    /// Only callable by admin
+    pub fn set_mint_status(
+        ctx: Context<SetMintStatus>,
+        mint: Pubkey,
+        status: MintListing,
+    ) -> Result<()> {
+        instructions::set_dex_mint_status(ctx, mint, status)
+    }
+
+    /// Makes new pools accept only mints listed as allowed, or any mint that isn't denied
+    /// Only callable by admin
+    pub fn set_require_allowed_mints(
+        ctx: Context<SetRequireAllowedMints>,
+        required: bool,
+    ) -> Result<()> {
+        instructions::set_dex_require_allowed_mints(ctx, required)
+    }
+
+    /// Switches how pools created from now on take the protocol fee: set aside by every swap, or
+    /// minted as LP tokens to the fee collector on deposits and withdrawals
+    /// Only callable by admin
+    pub fn set_protocol_fee_mode(
+        ctx: Context<SetProtocolFeeMode>,
+        mode: ProtocolFeeMode,
+    ) -> Result<()> {
+        instructions::set_dex_protocol_fee_mode(ctx, mode)
+    }
+
+    /// Enables the volatility-based fee of a pool with the given parameters, or disables it
+    /// when `params` is `None`
+    /// Callable by admin, or by the pool creator holding PERMISSION_CONFIGURE_DYNAMIC_FEE
+    pub fn configure_dynamic_fee(
+        ctx: Context<ConfigureDynamicFee>,
+        params: Option<DynamicFeeParams>,
+    ) -> Result<()> {
+        instructions::configure_pool_dynamic_fee(ctx, params)
+    }
+
+    /// Creates a liquidity mining farm for the stakers of a pool's LP token
+    /// Only callable by admin
+    pub fn create_farm(ctx: Context<CreateFarm>) -> Result<()> {
+        instructions::initialize_farm(ctx)
+    }
+
+    /// Authorizes or revokes an account allowed to add reward streams to a farm
+    /// Only callable by admin
+    pub fn set_funder(ctx: Context<SetFarmFunder>, funder: Pubkey, authorized: bool) -> Result<()> {
+        instructions::set_farm_funder(ctx, funder, authorized)
+    }
+
+    /// Adds a reward stream with its own mint, vault and emission schedule to a farm
+    /// Callable by admin or an authorized funder
+    pub fn add_reward_stream(
+        ctx: Context<AddRewardStream>,
+        reward_per_second: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<()> {
+        instructions::add_farm_reward_stream(ctx, reward_per_second, start_time, end_time)
+    }
+
+    /// Deposits reward tokens into the vault of a reward stream
+    /// Callable by admin or the funder of the stream
+    pub fn fund_farm<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FundFarm<'info>>,
+        stream_index: u8,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::fund_farm_rewards(ctx, stream_index, amount)
+    }
+
+    /// Extends the emission window of a reward stream and sets its emission rate
+    /// The stream's vault must already hold the rewards of the new schedule
+    /// Callable by admin or the funder of the stream
+    pub fn extend_farm(
+        ctx: Context<ExtendFarm>,
+        stream_index: u8,
+        end_time: i64,
+        reward_per_second: u64,
+    ) -> Result<()> {
+        instructions::extend_farm_schedule(ctx, stream_index, end_time, reward_per_second)
+    }
+
+    /// Stakes LP tokens in a farm to earn rewards
+    pub fn stake_lp<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StakeLp<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::stake_lp_tokens(ctx, amount)
+    }
+
+    /// Withdraws staked LP tokens from a farm, earned rewards stay claimable
+    pub fn unstake_lp<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UnstakeLp<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::unstake_lp_tokens(ctx, amount)
+    }
+
+    /// Pays out the rewards earned by the caller's stake position on one reward stream
+    /// Pays only what the reward vault holds when it is underfunded, the rest stays claimable
+    pub fn claim_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimRewards<'info>>,
+        stream_index: u8,
+    ) -> Result<()> {
+        instructions::claim_farm_rewards(ctx, stream_index)
+    }
+
+    /// Creates the allowlist that relaxes the Token-2022 extension checks of pool creation
+    /// Only callable by admin
+    pub fn create_extension_allowlist(ctx: Context<CreateExtensionAllowlist>) -> Result<()> {
+        instructions::initialize_extension_allowlist(ctx)
+    }
+
+    /// Allows or disallows a Token-2022 mint extension type (by its numeric id) in new pools
+    /// Only callable by admin
+    pub fn set_allowed_extension(
+        ctx: Context<UpdateExtensionAllowlist>,
+        extension_type: u16,
+        allowed: bool,
+    ) -> Result<()> {
+        instructions::set_allowed_mint_extension(ctx, extension_type, allowed)
+    }
+
+    /// Exempts a mint from the Token-2022 extension checks of pool creation, or revokes it
+    /// Only callable by admin
+    pub fn set_trusted_mint(
+        ctx: Context<UpdateExtensionAllowlist>,
+        mint: Pubkey,
+        trusted: bool,
+    ) -> Result<()> {
+        instructions::set_extension_trusted_mint(ctx, mint, trusted)
+    }
+
+    /// Accepts or stops accepting a transfer hook program for the mints of new pools
+    /// Only callable by admin
+    pub fn set_allowed_hook_program(
+        ctx: Context<UpdateExtensionAllowlist>,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> Result<()> {
+        instructions::set_extension_hook_program(ctx, program_id, allowed)
+    }
 }
 
 /// Defines custom error codes for the DEX program.
@@ -253,4 +498,109 @@ pub enum DexError {
     // Triggered when there are no fees to collect
     #[msg("No fees to collect.")]
     NoFeesToCollect,
+    // Triggered when the dynamic fee range or decay settings are inconsistent
+    #[msg("Invalid dynamic fee parameters.")]
+    InvalidDynamicFeeParams,
+    // Triggered when a farm's emission window or reward math is invalid
+    #[msg("Invalid farm schedule.")]
+    InvalidFarmSchedule,
+    // Triggered when unstaking more LP tokens than staked
+    #[msg("Insufficient stake.")]
+    InsufficientStake,
+    // Triggered when there are no rewards to claim or the reward vault is empty
+    #[msg("Insufficient rewards.")]
+    InsufficientRewards,
+    // Triggered when a stream index does not refer to a reward stream of the farm
+    #[msg("Invalid reward stream.")]
+    InvalidRewardStream,
+    // Triggered when a farm already has the maximum number of reward streams
+    #[msg("Too many reward streams.")]
+    TooManyRewardStreams,
+    // Triggered when a farm already has the maximum number of authorized funders
+    #[msg("Too many farm funders.")]
+    TooManyFarmFunders,
+    // Triggered when an account that is not an authorized funder manages reward streams
+    #[msg("Unauthorized funder.")]
+    UnauthorizedFunder,
+    // Triggered when the transfer fee of a Token-2022 mint can't be applied to an amount
+    #[msg("Transfer fee calculation failed.")]
+    TransferFeeCalculationFailed,
+    // Triggered when a pool mint has a Token-2022 extension that governance did not allow
+    #[msg("Mint extension not allowed.")]
+    MintExtensionNotAllowed,
+    // Triggered when an allowlist entry is not a known extension type or a valid mint
+    #[msg("Invalid extension type.")]
+    InvalidExtensionType,
+    // Triggered when the extension allowlist has no free slot left
+    #[msg("Too many allowlist entries.")]
+    TooManyAllowlistEntries,
+    // Triggered when a pool mint calls a transfer hook program the pool did not accept
+    #[msg("Transfer hook not allowed.")]
+    TransferHookNotAllowed,
+    // Triggered when a pool of the same mint pair already exists on the fee tier
+    #[msg("Pool already exists.")]
+    PoolAlreadyExists,
+    // Triggered when a mint pair already has a pool on the maximum number of fee tiers
+    #[msg("Too many pools for this pair.")]
+    TooManyPoolsForPair,
+    // Triggered when a registry page has no free entry left
+    #[msg("Registry page full.")]
+    RegistryPageFull,
+    // Triggered when an account other than the pool creator or the admin closes a pool
+    #[msg("Only the pool creator or the admin can close the pool.")]
+    NotPoolCreator,
+    // Triggered when closing a pool that still has LP tokens outstanding
+    #[msg("Pool still has liquidity.")]
+    PoolNotEmpty,
+    // Triggered when closing a pool whose protocol fees were not collected
+    #[msg("Protocol fees not collected.")]
+    ProtocolFeesNotCollected,
+    // Triggered when a registry entry does not list the expected pool
+    #[msg("Invalid registry entry.")]
+    InvalidRegistryEntry,
+    // Triggered when granting a creator permission that does not exist
+    #[msg("Invalid permissions.")]
+    InvalidPermissions,
+    // Triggered when creating a pool the pool creation mode doesn't allow the signer to create
+    #[msg("Pool creation not allowed.")]
+    PoolCreationNotAllowed,
+    // Triggered when migrating an account whose discriminator or size matches no known layout
+    #[msg("Unknown account layout.")]
+    UnknownAccountLayout,
+    // Triggered when migrating an account that already has the current layout
+    #[msg("Account already migrated.")]
+    AccountAlreadyMigrated,
+    // Triggered when creating a pool with, or swapping, a mint governance denied
+    #[msg("Mint denied.")]
+    MintDenied,
+    // Triggered when creating a pool with a mint not listed as allowed while allowed mints are
+    // required
+    #[msg("Mint not allowed.")]
+    MintNotAllowed,
+    // Triggered when a deposit or withdrawal owes the fee collector protocol fee LP tokens but
+    // doesn't pass its LP token account
+    #[msg("Fee collector LP token account missing.")]
+    FeeCollectorLpTokenMissing,
+    // Triggered when registering a fee tier beyond the MAX_POOLS_PER_PAIR pools a pair lookup
+    // holds
+    #[msg("Too many fee tiers.")]
+    TooManyFeeTiers,
+    // Triggered when the LP token account passed for the protocol fee of a pool isn't one of
+    // the pool's LP mint owned by the DEX fee collector
+    #[msg("Invalid fee collector LP token account.")]
+    InvalidFeeCollectorLpToken,
+    // Triggered when a reward vault doesn't hold what its stream owes and will emit until the
+    // end of its new schedule
+    #[msg("Reward schedule not funded.")]
+    UnfundedRewardSchedule,
+}
+
+/// Reports errors of the shared math crate as their program error
+impl From<dex_math::MathError> for DexError {
+    fn from(error: dex_math::MathError) -> Self {
+        match error {
+            dex_math::MathError::InsufficientLiquidity => DexError::InsufficientLiquidity,
+            dex_math::MathError::InvalidFees => DexError::InvalidFees,
+        }
+    }
 }
@@ -2,10 +2,18 @@ import * as anchor from "@coral-xyz/anchor";
 import { ASSOCIATED_PROGRAM_ID } from "@coral-xyz/anchor/dist/cjs/utils/token";
 import {
   createAssociatedTokenAccountInstruction,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createInitializeMintInstruction,
+  createInitializePermanentDelegateInstruction,
+  createInitializeTransferHookInstruction,
+  createInitializeTransferFeeConfigInstruction,
   createMint,
   createMintToInstruction,
+  ExtensionType,
   getAssociatedTokenAddressSync,
   getMint,
+  getMintLen,
+  getTokenMetadata,
   TOKEN_2022_PROGRAM_ID,
 } from "@solana/spl-token";
 import {
@@ -21,6 +29,11 @@ const provider = anchor.AnchorProvider.env();
 anchor.setProvider(provider);
 const program = anchor.workspace.Dex as Program<Dex>;
 const DEFAULT_AIRDROP_SOL = 2;
+const POOLS_PER_REGISTRY_PAGE = 16;
+const PERMISSION_CONFIGURE_DYNAMIC_FEE = 1;
+const PERMISSION_CREATE_POOL = 2;
+const DEX_STATE_VERSION = 1;
+const LIQUIDITY_POOL_VERSION = 3;
 
 describe("DEX tests", () => {
   // Test accounts
@@ -42,11 +55,14 @@ describe("DEX tests", () => {
   let lpTokenMint: PublicKey;
   let feeCollectorTokenA: PublicKey;
   let feeCollectorTokenB: PublicKey;
+  let feeTierPda: PublicKey;
 
   // Fee configuration
   const feeNumerator = 10;
   const feeDenominator = 1000;
   const protocolFeePercentage = 30; // 30% of the total fee goes to protocol fee collector
+  const feeTierIndex = 0; // Tier registered with the fee configuration above
+  const feeTierSeed = Buffer.from(new Uint16Array([feeTierIndex]).buffer);
 
   before(async () => {
     // Setup test accounts
@@ -130,6 +146,64 @@ describe("DEX tests", () => {
     expect(dexState.feeCollector.toBase58()).to.equal(feeCollector.publicKey.toBase58());
   });
 
+  // NOTE: Registering fee tiers
+  it("Registering fee tiers", async () => {
+    const tierPda = (index: number) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("fee_tier"),
+          dexStatePda.toBuffer(),
+          Buffer.from(new Uint16Array([index]).buffer),
+        ],
+        program.programId
+      )[0];
+
+    feeTierPda = tierPda(feeTierIndex);
+
+    // Only the admin can register tiers
+    try {
+      await program.methods
+        .addFeeTier(new anchor.BN(feeNumerator), new anchor.BN(feeDenominator))
+        .accounts({
+          admin: unauthorizedAttacker.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([unauthorizedAttacker])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    // Tier 0 mirrors the global fee, tier 1 is a cheaper 5 bps tier
+    const tiers = [
+      [feeNumerator, feeDenominator],
+      [5, 10000],
+    ];
+    for (let index = 0; index < tiers.length; index++) {
+      await program.methods
+        .addFeeTier(new anchor.BN(tiers[index][0]), new anchor.BN(tiers[index][1]))
+        .accounts({
+          admin: adminWallet.publicKey,
+          dexState: dexStatePda,
+          feeTier: tierPda(index),
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const feeTier = await program.account.feeTier.fetch(tierPda(index));
+      expect(feeTier.index).to.equal(index);
+      expect(feeTier.feeNumerator.toNumber()).to.equal(tiers[index][0]);
+      expect(feeTier.feeDenominator.toNumber()).to.equal(tiers[index][1]);
+    }
+
+    const dexState = await program.account.dexState.fetch(dexStatePda);
+    expect(dexState.feeTiersCount).to.equal(tiers.length);
+  });
+
   // NOTE: Creating a liquidity pool
   it("Creating a liquidity pool", async () => {
     // Create token mints
@@ -167,6 +241,7 @@ describe("DEX tests", () => {
         Buffer.from("liquidity_pool"),
         tokenAMint.toBuffer(),
         tokenBMint.toBuffer(),
+        feeTierSeed,
       ],
       program.programId
     );
@@ -225,14 +300,17 @@ describe("DEX tests", () => {
     const createPoolAccounts = {
       owner: poolOwner.publicKey,
       dexState: dexStatePda,
+      feeTier: feeTierPda,
       tokenAMint,
       tokenBMint,
+      ...(await poolRegistryAccounts(tokenAMint, tokenBMint)),
       pool: poolPda,
       poolTokenA,
       poolTokenB,
       lpTokenMint,
       ownerLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -240,7 +318,7 @@ describe("DEX tests", () => {
 
     // Create the pool
     await program.methods
-      .createPool()
+      .createPool(feeTierIndex)
       .accounts(createPoolAccounts)
       .signers([poolOwner, lpTokenMintKeypair])
       .rpc();
@@ -267,6 +345,19 @@ describe("DEX tests", () => {
     expect(pool.protocolFeesTokenB.toNumber()).to.equal(0);
     expect(dexState.poolsCount.toNumber()).to.equal(1);
     expect(lpMintInfo.decimals).to.equal(6);
+
+    // The test mints have no metadata, so the LP token is named after their abbreviated addresses
+    const lpMetadata = await getTokenMetadata(
+      provider.connection,
+      lpTokenMint,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const labelA = tokenAMint.toBase58().slice(0, 4);
+    const labelB = tokenBMint.toBase58().slice(0, 4);
+    expect(lpMetadata.name).to.equal(`DEX LP ${labelA}-${labelB}`);
+    expect(lpMetadata.symbol).to.equal(`${labelA}-${labelB}-LP`);
+    expect(lpMetadata.updateAuthority.toBase58()).to.equal(poolPda.toBase58());
   });
 
   // NOTE: Depositing and withdrawing liquidity
@@ -348,6 +439,7 @@ describe("DEX tests", () => {
     const depositAccounts = {
       owner: poolOwner.publicKey,
       pool: poolPda,
+      dexState: dexStatePda,
       tokenAMint,
       tokenBMint,
       poolTokenA,
@@ -357,6 +449,7 @@ describe("DEX tests", () => {
       userTokenB: ownerTokenB,
       userLpToken: ownerLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -380,6 +473,7 @@ describe("DEX tests", () => {
     const withdrawAccounts = {
       owner: poolOwner.publicKey,
       pool: poolPda,
+      dexState: dexStatePda,
       tokenAMint,
       tokenBMint,
       poolTokenA,
@@ -389,6 +483,7 @@ describe("DEX tests", () => {
       userTokenB: ownerTokenB,
       userLpToken: ownerLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -504,6 +599,7 @@ describe("DEX tests", () => {
       const additionalDepositAccounts = {
         owner: poolOwner.publicKey,
         pool: poolPda,
+        dexState: dexStatePda,
         tokenAMint,
         tokenBMint,
         poolTokenA,
@@ -513,6 +609,7 @@ describe("DEX tests", () => {
         userTokenB: ownerTokenB,
         userLpToken: ownerLpToken,
         tokenProgram: TOKEN_2022_PROGRAM_ID,
+        lpTokenProgram: TOKEN_2022_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
         rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -543,7 +640,7 @@ describe("DEX tests", () => {
     const feeDenominator = poolBeforeSwap.feeDenominator.toNumber();
 
     // Calculate total fee
-    const totalFeeAmount = Math.floor(inputAmount * feeNumerator / feeDenominator);
+    const totalFeeAmount = Math.ceil(inputAmount * feeNumerator / feeDenominator);
 
     // Calculate protocol fee portion
     const protocolFeeAmount = Math.floor(totalFeeAmount * protocolFeePercentage / 100);
@@ -559,6 +656,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenAMint,
       destinationMint: tokenBMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenA,
@@ -621,7 +719,7 @@ describe("DEX tests", () => {
     const reverseReserveB = Number(poolTokenBBalanceAfter.value.amount);
 
     // Calculate total fee for reverse swap
-    const reverseTotalFeeAmount = Math.floor(reverseInputAmount * feeNumerator / feeDenominator);
+    const reverseTotalFeeAmount = Math.ceil(reverseInputAmount * feeNumerator / feeDenominator);
 
     // Calculate protocol fee portion for reverse swap
     const reverseProtocolFeeAmount = Math.floor(reverseTotalFeeAmount * protocolFeePercentage / 100);
@@ -640,6 +738,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenBMint,
       destinationMint: tokenAMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenB,
@@ -851,6 +950,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenAMint,
       destinationMint: tokenBMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenA,
@@ -929,6 +1029,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenAMint,
       destinationMint: tokenBMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenA,
@@ -998,6 +1099,7 @@ describe("DEX tests", () => {
         Buffer.from("liquidity_pool"),
         newTokenAMint.toBuffer(),
         newTokenBMint.toBuffer(),
+        feeTierSeed,
       ],
       program.programId
     );
@@ -1112,21 +1214,24 @@ describe("DEX tests", () => {
     const createPoolAccounts = {
       owner: manipulatorKeypair.publicKey,
       dexState: dexStatePda,
+      feeTier: feeTierPda,
       tokenAMint: newTokenAMint,
       tokenBMint: newTokenBMint,
+      ...(await poolRegistryAccounts(newTokenAMint, newTokenBMint)),
       pool: newPoolPda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       lpTokenMint: newLpTokenMint,
       ownerLpToken: manipulatorLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
     };
 
     await program.methods
-      .createPool()
+      .createPool(feeTierIndex)
       .accounts(createPoolAccounts)
       .signers([manipulatorKeypair, newLpTokenMintKeypair])
       .rpc();
@@ -1137,6 +1242,7 @@ describe("DEX tests", () => {
     const depositAccounts = {
       owner: manipulatorKeypair.publicKey,
       pool: newPoolPda,
+      dexState: dexStatePda,
       tokenAMint: newTokenAMint,
       tokenBMint: newTokenBMint,
       poolTokenA: newPoolTokenA,
@@ -1146,6 +1252,7 @@ describe("DEX tests", () => {
       userTokenB: manipulatorTokenB,
       userLpToken: manipulatorLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -1168,6 +1275,7 @@ describe("DEX tests", () => {
       pool: newPoolPda,
       sourceMint: newTokenAMint,
       destinationMint: newTokenBMint,
+      dexState: dexStatePda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       userSourceToken: manipulatorTokenA,
@@ -1219,6 +1327,7 @@ describe("DEX tests", () => {
       pool: newPoolPda,
       sourceMint: newTokenBMint,
       destinationMint: newTokenAMint,
+      dexState: dexStatePda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       userSourceToken: manipulatorTokenB,
@@ -1308,6 +1417,7 @@ describe("DEX tests", () => {
         Buffer.from("liquidity_pool"),
         testTokenAMint.toBuffer(),
         testTokenBMint.toBuffer(),
+        feeTierSeed,
       ],
       program.programId
     );
@@ -1422,21 +1532,24 @@ describe("DEX tests", () => {
     const createPoolAccounts = {
       owner: testUserKeypair.publicKey,
       dexState: dexStatePda,
+      feeTier: feeTierPda,
       tokenAMint: testTokenAMint,
       tokenBMint: testTokenBMint,
+      ...(await poolRegistryAccounts(testTokenAMint, testTokenBMint)),
       pool: testPoolPda,
       poolTokenA: testPoolTokenA,
       poolTokenB: testPoolTokenB,
       lpTokenMint: testLpTokenMint,
       ownerLpToken: testUserLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
     };
 
     await program.methods
-      .createPool()
+      .createPool(feeTierIndex)
       .accounts(createPoolAccounts)
       .signers([testUserKeypair, testLpTokenMintKeypair])
       .rpc();
@@ -1447,6 +1560,7 @@ describe("DEX tests", () => {
     const depositAccounts = {
       owner: testUserKeypair.publicKey,
       pool: testPoolPda,
+      dexState: dexStatePda,
       tokenAMint: testTokenAMint,
       tokenBMint: testTokenBMint,
       poolTokenA: testPoolTokenA,
@@ -1456,6 +1570,7 @@ describe("DEX tests", () => {
       userTokenB: testUserTokenB,
       userLpToken: testUserLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -1551,6 +1666,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenAMint,
       destinationMint: tokenBMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenA,
@@ -1629,6 +1745,7 @@ describe("DEX tests", () => {
       pool: poolPda,
       sourceMint: tokenAMint,
       destinationMint: tokenBMint,
+      dexState: dexStatePda,
       poolTokenA,
       poolTokenB,
       userSourceToken: ownerTokenA,
@@ -1698,6 +1815,7 @@ describe("DEX tests", () => {
         Buffer.from("liquidity_pool"),
         newTokenAMint.toBuffer(),
         newTokenBMint.toBuffer(),
+        feeTierSeed,
       ],
       program.programId
     );
@@ -1812,21 +1930,24 @@ describe("DEX tests", () => {
     const createPoolAccounts = {
       owner: manipulatorKeypair.publicKey,
       dexState: dexStatePda,
+      feeTier: feeTierPda,
       tokenAMint: newTokenAMint,
       tokenBMint: newTokenBMint,
+      ...(await poolRegistryAccounts(newTokenAMint, newTokenBMint)),
       pool: newPoolPda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       lpTokenMint: newLpTokenMint,
       ownerLpToken: manipulatorLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
     };
 
     await program.methods
-      .createPool()
+      .createPool(feeTierIndex)
       .accounts(createPoolAccounts)
       .signers([manipulatorKeypair, newLpTokenMintKeypair])
       .rpc();
@@ -1837,6 +1958,7 @@ describe("DEX tests", () => {
     const depositAccounts = {
       owner: manipulatorKeypair.publicKey,
       pool: newPoolPda,
+      dexState: dexStatePda,
       tokenAMint: newTokenAMint,
       tokenBMint: newTokenBMint,
       poolTokenA: newPoolTokenA,
@@ -1846,6 +1968,7 @@ describe("DEX tests", () => {
       userTokenB: manipulatorTokenB,
       userLpToken: manipulatorLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -1868,6 +1991,7 @@ describe("DEX tests", () => {
       pool: newPoolPda,
       sourceMint: newTokenAMint,
       destinationMint: newTokenBMint,
+      dexState: dexStatePda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       userSourceToken: manipulatorTokenA,
@@ -1919,6 +2043,7 @@ describe("DEX tests", () => {
       pool: newPoolPda,
       sourceMint: newTokenBMint,
       destinationMint: newTokenAMint,
+      dexState: dexStatePda,
       poolTokenA: newPoolTokenA,
       poolTokenB: newPoolTokenB,
       userSourceToken: manipulatorTokenB,
@@ -2008,6 +2133,7 @@ describe("DEX tests", () => {
         Buffer.from("liquidity_pool"),
         testTokenAMint.toBuffer(),
         testTokenBMint.toBuffer(),
+        feeTierSeed,
       ],
       program.programId
     );
@@ -2122,21 +2248,24 @@ describe("DEX tests", () => {
     const createPoolAccounts = {
       owner: testUserKeypair.publicKey,
       dexState: dexStatePda,
+      feeTier: feeTierPda,
       tokenAMint: testTokenAMint,
       tokenBMint: testTokenBMint,
+      ...(await poolRegistryAccounts(testTokenAMint, testTokenBMint)),
       pool: testPoolPda,
       poolTokenA: testPoolTokenA,
       poolTokenB: testPoolTokenB,
       lpTokenMint: testLpTokenMint,
       ownerLpToken: testUserLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
     };
 
     await program.methods
-      .createPool()
+      .createPool(feeTierIndex)
       .accounts(createPoolAccounts)
       .signers([testUserKeypair, testLpTokenMintKeypair])
       .rpc();
@@ -2147,6 +2276,7 @@ describe("DEX tests", () => {
     const depositAccounts = {
       owner: testUserKeypair.publicKey,
       pool: testPoolPda,
+      dexState: dexStatePda,
       tokenAMint: testTokenAMint,
       tokenBMint: testTokenBMint,
       poolTokenA: testPoolTokenA,
@@ -2156,6 +2286,7 @@ describe("DEX tests", () => {
       userTokenB: testUserTokenB,
       userLpToken: testUserLpToken,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
       associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
       systemProgram: anchor.web3.SystemProgram.programId,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -2208,7 +2339,1947 @@ describe("DEX tests", () => {
     // Verify LP amount is close to expected (within 1% margin due to rounding)
     expect(imbalancedLpAmount).to.be.approximately(expectedImbalancedLpAmount, expectedImbalancedLpAmount * 0.01);
   });
-});
+
+  // NOTE: Dynamic volatility-based fees
+  it("Dynamic fee stays within the configured floor and cap", async () => {
+    const ownerTokenA = getAssociatedTokenAddressSync(
+      tokenAMint,
+      poolOwner.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+
+    const ownerTokenB = getAssociatedTokenAddressSync(
+      tokenBMint,
+      poolOwner.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+
+    const dynamicFeeParams = {
+      minFeeBps: 5,
+      maxFeeBps: 200,
+      filterPeriod: 30,
+      decayPeriod: 600,
+      reductionFactor: 5000,
+      variableFeeControl: 40000,
+      maxVolatilityAccumulator: 100000,
+    };
+
+    const configureAccounts = {
+      authority: admin.publicKey,
+      dexState: dexStatePda,
+      pool: poolPda,
+      creatorPermissions: null,
+    };
+
+    // Inconsistent parameters are rejected
+    try {
+      await program.methods
+        .configureDynamicFee({ ...dynamicFeeParams, minFeeBps: 300 })
+        .accounts(configureAccounts)
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("InvalidDynamicFeeParams");
+    }
+
+    // Only the admin can switch the fee mode
+    try {
+      await program.methods
+        .configureDynamicFee(dynamicFeeParams)
+        .accounts({ ...configureAccounts, authority: unauthorizedAttacker.publicKey })
+        .signers([unauthorizedAttacker])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    await program.methods
+      .configureDynamicFee(dynamicFeeParams)
+      .accounts(configureAccounts)
+      .rpc();
+
+    const swapAccounts = {
+      owner: poolOwner.publicKey,
+      pool: poolPda,
+      sourceMint: tokenAMint,
+      destinationMint: tokenBMint,
+      dexState: dexStatePda,
+      poolTokenA,
+      poolTokenB,
+      userSourceToken: ownerTokenA,
+      userDestinationToken: ownerTokenB,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+    };
+
+    // Two swaps in a row: the second one sees the price move of the first
+    const inputAmount = 20000;
+    for (let i = 0; i < 2; i++) {
+      const poolBefore = await program.account.liquidityPool.fetch(poolPda);
+
+      await program.methods
+        .swap(new anchor.BN(inputAmount), new anchor.BN(1))
+        .accounts(swapAccounts)
+        .signers([poolOwner])
+        .rpc();
+
+      const poolAfter = await program.account.liquidityPool.fetch(poolPda);
+      const protocolFee =
+        poolAfter.protocolFeesTokenA.toNumber() - poolBefore.protocolFeesTokenA.toNumber();
+
+      // Protocol fee is a share of a total fee bounded by the floor and the cap
+      const minProtocolFee = Math.floor(
+        (Math.floor((inputAmount * dynamicFeeParams.minFeeBps) / 10000) * protocolFeePercentage) / 100
+      );
+      const maxProtocolFee = Math.floor(
+        (Math.floor((inputAmount * dynamicFeeParams.maxFeeBps) / 10000) * protocolFeePercentage) / 100
+      );
+      expect(protocolFee).to.be.at.least(minProtocolFee);
+      expect(protocolFee).to.be.at.most(maxProtocolFee);
+    }
+
+    const poolWithDynamicFee = await program.account.liquidityPool.fetch(poolPda);
+    expect(poolWithDynamicFee.dynamicFee.enabled).to.equal(1);
+    expect(poolWithDynamicFee.dynamicFee.volatilityAccumulator).to.be.greaterThan(0);
+    expect(poolWithDynamicFee.dynamicFee.lastUpdateTimestamp.toNumber()).to.be.greaterThan(0);
+
+    // Switch back to the static fee for the other tests
+    await program.methods
+      .configureDynamicFee(null)
+      .accounts(configureAccounts)
+      .rpc();
+
+    const poolWithStaticFee = await program.account.liquidityPool.fetch(poolPda);
+    expect(poolWithStaticFee.dynamicFee.enabled).to.equal(0);
+  });
+
+  const validDynamicFeeParams = {
+    minFeeBps: 5,
+    maxFeeBps: 200,
+    filterPeriod: 30,
+    decayPeriod: 600,
+    reductionFactor: 5000,
+    variableFeeControl: 40000,
+    maxVolatilityAccumulator: 100000,
+  };
+
+  const expectInvalidDynamicFeeParams = async (params: typeof validDynamicFeeParams) => {
+    try {
+      await program.methods
+        .configureDynamicFee(params)
+        .accounts({
+          authority: admin.publicKey,
+          dexState: dexStatePda,
+          pool: poolPda,
+          creatorPermissions: null,
+        })
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("InvalidDynamicFeeParams");
+    }
+  };
+
+  it("Rejects a dynamic fee without a minimum fee", async () => {
+    await expectInvalidDynamicFeeParams({ ...validDynamicFeeParams, minFeeBps: 0 });
+  });
+
+  it("Rejects a dynamic fee whose volatility accumulator is capped at zero", async () => {
+    await expectInvalidDynamicFeeParams({ ...validDynamicFeeParams, maxVolatilityAccumulator: 0 });
+  });
+
+  it("Rejects a dynamic fee whose volatility accumulator cap overflows", async () => {
+    // One above MAX_VOLATILITY_ACCUMULATOR, 2^20 - 1
+    await expectInvalidDynamicFeeParams({
+      ...validDynamicFeeParams,
+      maxVolatilityAccumulator: 1 << 20,
+    });
+  });
+
+  // NOTE: One pool per fee tier for the same pair
+  it("Creates a second pool for the same pair on another fee tier", async () => {
+    const cheapTierIndex = 1;
+    const cheapTierSeed = Buffer.from(new Uint16Array([cheapTierIndex]).buffer);
+    const [cheapTierPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_tier"), dexStatePda.toBuffer(), cheapTierSeed],
+      program.programId
+    );
+
+    const [cheapPoolPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("liquidity_pool"),
+        tokenAMint.toBuffer(),
+        tokenBMint.toBuffer(),
+        cheapTierSeed,
+      ],
+      program.programId
+    );
+    expect(cheapPoolPda.toBase58()).to.not.equal(poolPda.toBase58());
+
+    const cheapLpTokenMintKeypair = Keypair.generate();
+    const cheapPoolTokenA = getAssociatedTokenAddressSync(
+      tokenAMint,
+      cheapPoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+    const cheapPoolTokenB = getAssociatedTokenAddressSync(
+      tokenBMint,
+      cheapPoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          cheapPoolTokenA,
+          cheapPoolPda,
+          tokenAMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          cheapPoolTokenB,
+          cheapPoolPda,
+          tokenBMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      ),
+      [poolOwner]
+    );
+
+    const dexStateBefore = await program.account.dexState.fetch(dexStatePda);
+
+    await program.methods
+      .createPool(cheapTierIndex)
+      .accounts({
+        owner: poolOwner.publicKey,
+        dexState: dexStatePda,
+        feeTier: cheapTierPda,
+        tokenAMint,
+        tokenBMint,
+        ...(await poolRegistryAccounts(tokenAMint, tokenBMint)),
+        pool: cheapPoolPda,
+        poolTokenA: cheapPoolTokenA,
+        poolTokenB: cheapPoolTokenB,
+        lpTokenMint: cheapLpTokenMintKeypair.publicKey,
+        ownerLpToken: getAssociatedTokenAddressSync(
+          cheapLpTokenMintKeypair.publicKey,
+          poolOwner.publicKey,
+          false,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([poolOwner, cheapLpTokenMintKeypair])
+      .rpc();
+
+    // The new pool charges the tier fee, not the global one
+    const cheapPool = await program.account.liquidityPool.fetch(cheapPoolPda);
+    const dexStateAfter = await program.account.dexState.fetch(dexStatePda);
+    expect(cheapPool.feeTierIndex).to.equal(cheapTierIndex);
+    expect(cheapPool.feeNumerator.toNumber()).to.equal(5);
+    expect(cheapPool.feeDenominator.toNumber()).to.equal(10000);
+    expect(dexStateAfter.poolsCount.toNumber()).to.equal(dexStateBefore.poolsCount.toNumber() + 1);
+  });
+
+  // NOTE: Liquidity mining
+  it("Stakes LP tokens in a farm with two reward streams and claims rewards", async () => {
+    // Partner co-incentivizing the pool with its own token
+    const partner = Keypair.generate();
+    await airdropIfNeeded(provider.connection, partner.publicKey);
+
+    // Reward tokens: one minted by the admin, one by the partner
+    const createRewardMint = async (authority: Keypair) => {
+      const mintKeypair = Keypair.generate();
+      await createMint(
+        provider.connection,
+        authority,
+        authority.publicKey,
+        null,
+        6,
+        mintKeypair,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return mintKeypair.publicKey;
+    };
+    const adminRewardMint = await createRewardMint(admin);
+    const partnerRewardMint = await createRewardMint(partner);
+
+    const [farmPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("farm"), poolPda.toBuffer()],
+      program.programId
+    );
+    const farmVault = (mint: PublicKey) =>
+      getAssociatedTokenAddressSync(mint, farmPda, true, TOKEN_2022_PROGRAM_ID, ASSOCIATED_PROGRAM_ID);
+    const userAccount = (mint: PublicKey, owner: PublicKey) =>
+      getAssociatedTokenAddressSync(mint, owner, false, TOKEN_2022_PROGRAM_ID, ASSOCIATED_PROGRAM_ID);
+    const lpVault = farmVault(lpTokenMint);
+
+    await program.methods
+      .createFarm()
+      .accounts({
+        admin: admin.publicKey,
+        dexState: dexStatePda,
+        pool: poolPda,
+        lpTokenMint,
+        farm: farmPda,
+        lpVault,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    // Emission windows start shortly after the current cluster time
+    const slot = await provider.connection.getSlot();
+    const now = await provider.connection.getBlockTime(slot);
+    const startTime = now + 2;
+    const endTime = startTime + 3600;
+    const rewardRates = [1000, 250];
+
+    const addStreamAccounts = (funder: Keypair, rewardMint: PublicKey) => ({
+      funder: funder.publicKey,
+      dexState: dexStatePda,
+      farm: farmPda,
+      rewardMint,
+      rewardVault: farmVault(rewardMint),
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    });
+
+    // The partner cannot add a stream before being authorized by the admin
+    try {
+      await program.methods
+        .addRewardStream(new anchor.BN(rewardRates[1]), new anchor.BN(startTime), new anchor.BN(endTime))
+        .accounts(addStreamAccounts(partner, partnerRewardMint))
+        .signers([partner])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("UnauthorizedFunder");
+    }
+
+    await program.methods
+      .setFunder(partner.publicKey, true)
+      .accounts({ admin: admin.publicKey, dexState: dexStatePda, farm: farmPda })
+      .rpc();
+
+    // Stream 0 from the admin, stream 1 from the partner
+    await program.methods
+      .addRewardStream(new anchor.BN(rewardRates[0]), new anchor.BN(startTime), new anchor.BN(endTime))
+      .accounts(addStreamAccounts(admin, adminRewardMint))
+      .rpc();
+    await program.methods
+      .addRewardStream(new anchor.BN(rewardRates[1]), new anchor.BN(startTime), new anchor.BN(endTime))
+      .accounts(addStreamAccounts(partner, partnerRewardMint))
+      .signers([partner])
+      .rpc();
+
+    // Each funder funds its own stream for the whole window
+    const streams: [Keypair, PublicKey][] = [
+      [admin, adminRewardMint],
+      [partner, partnerRewardMint],
+    ];
+    for (let index = 0; index < streams.length; index++) {
+      const [funder, rewardMint] = streams[index];
+      const funderRewardToken = userAccount(rewardMint, funder.publicKey);
+      const fundingAmount = rewardRates[index] * 3600;
+
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createAssociatedTokenAccountInstruction(
+            funder.publicKey,
+            funderRewardToken,
+            funder.publicKey,
+            rewardMint,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          createMintToInstruction(
+            rewardMint,
+            funderRewardToken,
+            funder.publicKey,
+            fundingAmount,
+            [],
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [funder]
+      );
+
+      await program.methods
+        .fundFarm(index, new anchor.BN(fundingAmount))
+        .accounts({
+          funder: funder.publicKey,
+          dexState: dexStatePda,
+          farm: farmPda,
+          rewardMint,
+          rewardVault: farmVault(rewardMint),
+          funderRewardToken,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([funder])
+        .rpc();
+    }
+
+    const farm = await program.account.farm.fetch(farmPda);
+    expect(farm.rewardStreamCount).to.equal(2);
+    expect(farm.rewardStreams[1].funder.toBase58()).to.equal(partner.publicKey.toBase58());
+
+    // Stake half of the pool owner's LP tokens
+    const ownerLpToken = userAccount(lpTokenMint, poolOwner.publicKey);
+    const ownerLpBalance = await provider.connection.getTokenAccountBalance(ownerLpToken);
+    const stakeAmount = Math.floor(Number(ownerLpBalance.value.amount) / 2);
+    const [stakePositionPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stake_position"), farmPda.toBuffer(), poolOwner.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .stakeLp(new anchor.BN(stakeAmount))
+      .accounts({
+        owner: poolOwner.publicKey,
+        farm: farmPda,
+        lpTokenMint,
+        lpVault,
+        userLpToken: ownerLpToken,
+        stakePosition: stakePositionPda,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([poolOwner])
+      .rpc();
+
+    const farmAfterStake = await program.account.farm.fetch(farmPda);
+    expect(farmAfterStake.totalStaked.toNumber()).to.equal(stakeAmount);
+
+    // Let some rewards accrue, then claim both streams
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+
+    const claimAccounts = (rewardMint: PublicKey) => ({
+      owner: poolOwner.publicKey,
+      farm: farmPda,
+      rewardMint,
+      rewardVault: farmVault(rewardMint),
+      userRewardToken: userAccount(rewardMint, poolOwner.publicKey),
+      stakePosition: stakePositionPda,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    });
+
+    for (let index = 0; index < streams.length; index++) {
+      const rewardMint = streams[index][1];
+      const ownerRewardToken = userAccount(rewardMint, poolOwner.publicKey);
+
+      await program.methods
+        .claimRewards(index)
+        .accounts(claimAccounts(rewardMint))
+        .signers([poolOwner])
+        .rpc();
+
+      // The only staker receives everything the stream emitted since the start, minus rounding,
+      // including what was emitted before the stake and carried forward
+      const farmAfterClaim = await program.account.farm.fetch(farmPda);
+      const claimed = Number(
+        (await provider.connection.getTokenAccountBalance(ownerRewardToken)).value.amount
+      );
+      const emitted =
+        (farmAfterClaim.rewardStreams[index].lastUpdateTime.toNumber() - startTime) *
+        rewardRates[index];
+      expect(claimed).to.be.greaterThan(0);
+      expect(claimed).to.be.at.most(emitted);
+      expect(claimed).to.be.approximately(emitted, 1);
+    }
+
+    // A third stream funded for a single second of its window
+    const underfundedIndex = streams.length;
+    const underfundedRewardMint = await createRewardMint(admin);
+    const underfundedRate = 1000;
+    const underfundedStart =
+      (await provider.connection.getBlockTime(await provider.connection.getSlot())) + 2;
+    await program.methods
+      .addRewardStream(
+        new anchor.BN(underfundedRate),
+        new anchor.BN(underfundedStart),
+        new anchor.BN(underfundedStart + 3600)
+      )
+      .accounts(addStreamAccounts(admin, underfundedRewardMint))
+      .rpc();
+
+    const adminUnderfundedToken = userAccount(underfundedRewardMint, admin.publicKey);
+    const fundUnderfundedStream = async (amount: number) => {
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createAssociatedTokenAccountIdempotentInstruction(
+            admin.publicKey,
+            adminUnderfundedToken,
+            admin.publicKey,
+            underfundedRewardMint,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          createMintToInstruction(
+            underfundedRewardMint,
+            adminUnderfundedToken,
+            admin.publicKey,
+            amount,
+            [],
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [admin]
+      );
+      await program.methods
+        .fundFarm(underfundedIndex, new anchor.BN(amount))
+        .accounts({
+          funder: admin.publicKey,
+          dexState: dexStatePda,
+          farm: farmPda,
+          rewardMint: underfundedRewardMint,
+          rewardVault: farmVault(underfundedRewardMint),
+          funderRewardToken: adminUnderfundedToken,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .rpc();
+    };
+    await fundUnderfundedStream(underfundedRate);
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+
+    // The claim pays the whole vault and leaves the rest pending
+    const ownerUnderfundedToken = userAccount(underfundedRewardMint, poolOwner.publicKey);
+    await program.methods
+      .claimRewards(underfundedIndex)
+      .accounts(claimAccounts(underfundedRewardMint))
+      .signers([poolOwner])
+      .rpc();
+
+    const partialClaim = await provider.connection.getTokenAccountBalance(ownerUnderfundedToken);
+    const emptiedVault = await provider.connection.getTokenAccountBalance(
+      farmVault(underfundedRewardMint)
+    );
+    const positionAfterPartialClaim = await program.account.stakePosition.fetch(stakePositionPda);
+    const stillPending =
+      positionAfterPartialClaim.rewards[underfundedIndex].pendingRewards.toNumber();
+    expect(Number(partialClaim.value.amount)).to.equal(underfundedRate);
+    expect(Number(emptiedVault.value.amount)).to.equal(0);
+    expect(stillPending).to.be.greaterThan(0);
+
+    // Once funded again, the pending rewards are paid on top of the new ones
+    await fundUnderfundedStream(underfundedRate * 3600);
+    await program.methods
+      .claimRewards(underfundedIndex)
+      .accounts(claimAccounts(underfundedRewardMint))
+      .signers([poolOwner])
+      .rpc();
+
+    const fullClaim = await provider.connection.getTokenAccountBalance(ownerUnderfundedToken);
+    const positionAfterFullClaim = await program.account.stakePosition.fetch(stakePositionPda);
+    expect(Number(fullClaim.value.amount)).to.be.at.least(underfundedRate + stillPending);
+    expect(positionAfterFullClaim.rewards[underfundedIndex].pendingRewards.toNumber()).to.equal(0);
+
+    // Extending a stream needs a vault holding what it owes and all it will emit until the new end
+    const extendedEnd = underfundedStart + 7200;
+    const extendedRate = underfundedRate * 2;
+    const extendAccounts = {
+      funder: admin.publicKey,
+      dexState: dexStatePda,
+      farm: farmPda,
+      rewardVault: farmVault(underfundedRewardMint),
+    };
+    try {
+      await program.methods
+        .extendFarm(underfundedIndex, new anchor.BN(extendedEnd), new anchor.BN(extendedRate))
+        .accounts(extendAccounts)
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("UnfundedRewardSchedule");
+    }
+
+    await fundUnderfundedStream(extendedRate * 7200);
+    await program.methods
+      .extendFarm(underfundedIndex, new anchor.BN(extendedEnd), new anchor.BN(extendedRate))
+      .accounts(extendAccounts)
+      .rpc();
+
+    const extendedStream = (await program.account.farm.fetch(farmPda)).rewardStreams[underfundedIndex];
+    expect(extendedStream.endTime.toNumber()).to.equal(extendedEnd);
+    expect(extendedStream.rewardPerSecond.toNumber()).to.equal(extendedRate);
+
+    // Unstaking more than staked fails, unstaking everything succeeds
+    const unstakeAccounts = {
+      owner: poolOwner.publicKey,
+      farm: farmPda,
+      lpTokenMint,
+      lpVault,
+      userLpToken: ownerLpToken,
+      stakePosition: stakePositionPda,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .unstakeLp(new anchor.BN(stakeAmount + 1))
+        .accounts(unstakeAccounts)
+        .signers([poolOwner])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("InsufficientStake");
+    }
+
+    await program.methods
+      .unstakeLp(new anchor.BN(stakeAmount))
+      .accounts(unstakeAccounts)
+      .signers([poolOwner])
+      .rpc();
+
+    const ownerLpBalanceAfter = await provider.connection.getTokenAccountBalance(ownerLpToken);
+    expect(ownerLpBalanceAfter.value.amount).to.equal(ownerLpBalance.value.amount);
+  });
+
+  // NOTE: Token-2022 transfer-fee mints
+  it("Prices swaps on the net amount of transfer-fee mints", async () => {
+    const transferFeeBps = 100; // 1% withheld on every transfer
+    const userAccount = (mint: PublicKey, owner: PublicKey, allowOwnerOffCurve = false) =>
+      getAssociatedTokenAddressSync(
+        mint,
+        owner,
+        allowOwnerOffCurve,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+
+    // Mint with the transfer-fee extension, paired with a plain mint
+    const feeMintKeypair = Keypair.generate();
+    const feeMint = feeMintKeypair.publicKey;
+    const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: poolOwner.publicKey,
+          newAccountPubkey: feeMint,
+          space: mintLen,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(mintLen),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferFeeConfigInstruction(
+          feeMint,
+          poolOwner.publicKey,
+          poolOwner.publicKey,
+          transferFeeBps,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(feeMint, 6, poolOwner.publicKey, null, TOKEN_2022_PROGRAM_ID)
+      ),
+      [poolOwner, feeMintKeypair]
+    );
+
+    const plainMintKeypair = Keypair.generate();
+    const plainMint = plainMintKeypair.publicKey;
+    await createMint(
+      provider.connection,
+      poolOwner,
+      poolOwner.publicKey,
+      null,
+      6,
+      plainMintKeypair,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const [feePoolPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("liquidity_pool"), feeMint.toBuffer(), plainMint.toBuffer(), feeTierSeed],
+      program.programId
+    );
+    const feePoolTokenA = userAccount(feeMint, feePoolPda, true);
+    const feePoolTokenB = userAccount(plainMint, feePoolPda, true);
+    const ownerFeeToken = userAccount(feeMint, poolOwner.publicKey);
+    const ownerPlainToken = userAccount(plainMint, poolOwner.publicKey);
+    const feeLpTokenMintKeypair = Keypair.generate();
+    const feeLpTokenMint = feeLpTokenMintKeypair.publicKey;
+    const ownerFeeLpToken = userAccount(feeLpTokenMint, poolOwner.publicKey);
+
+    const setupTx = new Transaction();
+    for (const [account, owner, mint] of [
+      [feePoolTokenA, feePoolPda, feeMint],
+      [feePoolTokenB, feePoolPda, plainMint],
+      [ownerFeeToken, poolOwner.publicKey, feeMint],
+      [ownerPlainToken, poolOwner.publicKey, plainMint],
+    ]) {
+      setupTx.add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          account,
+          owner,
+          mint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      );
+    }
+    setupTx.add(
+      createMintToInstruction(feeMint, ownerFeeToken, poolOwner.publicKey, 10_000_000, [], TOKEN_2022_PROGRAM_ID),
+      createMintToInstruction(plainMint, ownerPlainToken, poolOwner.publicKey, 10_000_000, [], TOKEN_2022_PROGRAM_ID)
+    );
+    await provider.sendAndConfirm(setupTx, [poolOwner]);
+
+    await program.methods
+      .createPool(feeTierIndex)
+      .accounts({
+        owner: poolOwner.publicKey,
+        dexState: dexStatePda,
+        feeTier: feeTierPda,
+        tokenAMint: feeMint,
+        tokenBMint: plainMint,
+        ...(await poolRegistryAccounts(feeMint, plainMint)),
+        pool: feePoolPda,
+        poolTokenA: feePoolTokenA,
+        poolTokenB: feePoolTokenB,
+        lpTokenMint: feeLpTokenMint,
+        ownerLpToken: ownerFeeLpToken,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([poolOwner, feeLpTokenMintKeypair])
+      .rpc();
+
+    // The deposit is valued on what reaches the pool: 1% of the fee token is withheld
+    await program.methods
+      .depositLiquidity(new anchor.BN(1_000_000), new anchor.BN(1_000_000))
+      .accounts({
+        owner: poolOwner.publicKey,
+        pool: feePoolPda,
+        dexState: dexStatePda,
+        tokenAMint: feeMint,
+        tokenBMint: plainMint,
+        poolTokenA: feePoolTokenA,
+        poolTokenB: feePoolTokenB,
+        lpTokenMint: feeLpTokenMint,
+        userTokenA: ownerFeeToken,
+        userTokenB: ownerPlainToken,
+        userLpToken: ownerFeeLpToken,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([poolOwner])
+      .rpc();
+
+    const balance = async (account: PublicKey) =>
+      Number((await provider.connection.getTokenAccountBalance(account)).value.amount);
+    expect(await balance(feePoolTokenA)).to.equal(990_000);
+    expect(await balance(ownerFeeLpToken)).to.equal(Math.floor(Math.sqrt(990_000 * 1_000_000)));
+
+    const swapAccounts = (sourceMint: PublicKey, destinationMint: PublicKey) => ({
+      owner: poolOwner.publicKey,
+      pool: feePoolPda,
+      sourceMint,
+      destinationMint,
+      dexState: dexStatePda,
+      poolTokenA: feePoolTokenA,
+      poolTokenB: feePoolTokenB,
+      userSourceToken: userAccount(sourceMint, poolOwner.publicKey),
+      userDestinationToken: userAccount(destinationMint, poolOwner.publicKey),
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+    });
+
+    // Exact input: the output is priced on the 99_000 the pool receives, not the 100_000 sent
+    const reserveA = await balance(feePoolTokenA);
+    const reserveB = await balance(feePoolTokenB);
+    const netInput = 99_000;
+    const inputWithFee = netInput - Math.ceil((netInput * feeNumerator) / feeDenominator);
+    const expectedOutput = Math.floor((reserveB * inputWithFee) / (reserveA + inputWithFee));
+    const plainBefore = await balance(ownerPlainToken);
+
+    await program.methods
+      .swap(new anchor.BN(100_000), new anchor.BN(expectedOutput))
+      .accounts(swapAccounts(feeMint, plainMint))
+      .signers([poolOwner])
+      .rpc();
+
+    expect((await balance(feePoolTokenA)) - reserveA).to.equal(netInput);
+    expect((await balance(ownerPlainToken)) - plainBefore).to.equal(expectedOutput);
+
+    // Exact output: the pool grosses up its transfer so the user is credited exactly 50_000
+    const exactOutput = 50_000;
+    const feeTokenBefore = await balance(ownerFeeToken);
+
+    await program.methods
+      .swapExactOutput(new anchor.BN(exactOutput), new anchor.BN(1_000_000))
+      .accounts(swapAccounts(plainMint, feeMint))
+      .signers([poolOwner])
+      .rpc();
+
+    expect((await balance(ownerFeeToken)) - feeTokenBefore).to.equal(exactOutput);
+
+    // Asking for the same output with too small an input budget fails
+    try {
+      await program.methods
+        .swapExactOutput(new anchor.BN(exactOutput), new anchor.BN(1_000))
+        .accounts(swapAccounts(plainMint, feeMint))
+        .signers([poolOwner])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("SlippageExceeded");
+    }
+  });
+
+  // NOTE: Token-2022 extension allowlist
+  it("Rejects dangerous mint extensions unless governance allows them", async () => {
+    const [extensionAllowlistPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("extension_allowlist"), dexStatePda.toBuffer()],
+      program.programId
+    );
+    const userAccount = (mint: PublicKey, owner: PublicKey, allowOwnerOffCurve = false) =>
+      getAssociatedTokenAddressSync(
+        mint,
+        owner,
+        allowOwnerOffCurve,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+
+    // Mint with a permanent delegate, which could pull tokens out of the pool vault
+    const delegateMintKeypair = Keypair.generate();
+    const delegateMint = delegateMintKeypair.publicKey;
+    const mintLen = getMintLen([ExtensionType.PermanentDelegate]);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: poolOwner.publicKey,
+          newAccountPubkey: delegateMint,
+          space: mintLen,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(mintLen),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializePermanentDelegateInstruction(
+          delegateMint,
+          poolOwner.publicKey,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(delegateMint, 6, poolOwner.publicKey, null, TOKEN_2022_PROGRAM_ID)
+      ),
+      [poolOwner, delegateMintKeypair]
+    );
+
+    const [delegatePoolPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("liquidity_pool"), delegateMint.toBuffer(), tokenBMint.toBuffer(), feeTierSeed],
+      program.programId
+    );
+    const delegatePoolTokenA = userAccount(delegateMint, delegatePoolPda, true);
+    const delegatePoolTokenB = userAccount(tokenBMint, delegatePoolPda, true);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          delegatePoolTokenA,
+          delegatePoolPda,
+          delegateMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          delegatePoolTokenB,
+          delegatePoolPda,
+          tokenBMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      ),
+      [poolOwner]
+    );
+
+    const createDelegatePool = async (extensionAllowlist: PublicKey | null) => {
+      const delegateLpTokenMintKeypair = Keypair.generate();
+      await program.methods
+        .createPool(feeTierIndex)
+        .accounts({
+          owner: poolOwner.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          extensionAllowlist,
+          tokenAMint: delegateMint,
+          tokenBMint,
+          ...(await poolRegistryAccounts(delegateMint, tokenBMint)),
+          pool: delegatePoolPda,
+          poolTokenA: delegatePoolTokenA,
+          poolTokenB: delegatePoolTokenB,
+          lpTokenMint: delegateLpTokenMintKeypair.publicKey,
+          ownerLpToken: userAccount(delegateLpTokenMintKeypair.publicKey, poolOwner.publicKey),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner, delegateLpTokenMintKeypair])
+        .rpc();
+    };
+
+    // Rejected by default
+    try {
+      await createDelegatePool(null);
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintExtensionNotAllowed");
+    }
+
+    await program.methods
+      .createExtensionAllowlist()
+      .accounts({
+        admin: adminWallet.publicKey,
+        dexState: dexStatePda,
+        extensionAllowlist: extensionAllowlistPda,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    const updateAccounts = (admin: PublicKey) => ({
+      admin,
+      dexState: dexStatePda,
+      extensionAllowlist: extensionAllowlistPda,
+    });
+
+    // Only the admin manages the allowlist
+    try {
+      await program.methods
+        .setAllowedExtension(ExtensionType.PermanentDelegate, true)
+        .accounts(updateAccounts(unauthorizedAttacker.publicKey))
+        .signers([unauthorizedAttacker])
+        .rpc();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    // An empty allowlist changes nothing
+    try {
+      await createDelegatePool(extensionAllowlistPda);
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintExtensionNotAllowed");
+    }
+
+    // Trusting the mint lets the pool through
+    await program.methods
+      .setTrustedMint(delegateMint, true)
+      .accounts(updateAccounts(adminWallet.publicKey))
+      .rpc();
+
+    const extensionAllowlist = await program.account.extensionAllowlist.fetch(extensionAllowlistPda);
+    expect(extensionAllowlist.trustedMints[0].toBase58()).to.equal(delegateMint.toBase58());
+
+    await createDelegatePool(extensionAllowlistPda);
+    const delegatePool = await program.account.liquidityPool.fetch(delegatePoolPda);
+    expect(delegatePool.tokenAMint.toBase58()).to.equal(delegateMint.toBase58());
+
+    // Allowing the extension type works the same for every mint; revoke the mint exemption
+    await program.methods
+      .setAllowedExtension(ExtensionType.PermanentDelegate, true)
+      .accounts(updateAccounts(adminWallet.publicKey))
+      .rpc();
+    await program.methods
+      .setTrustedMint(delegateMint, false)
+      .accounts(updateAccounts(adminWallet.publicKey))
+      .rpc();
+
+    const allowlistAfter = await program.account.extensionAllowlist.fetch(extensionAllowlistPda);
+    expect(allowlistAfter.allowedExtensions[0]).to.equal(ExtensionType.PermanentDelegate);
+    expect(allowlistAfter.trustedMints[0].toBase58()).to.equal(PublicKey.default.toBase58());
+  });
+
+  // NOTE: Token-2022 transfer hooks
+  it("Accepts transfer hook mints only for governance-approved hook programs", async () => {
+    const [extensionAllowlistPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("extension_allowlist"), dexStatePda.toBuffer()],
+      program.programId
+    );
+    const userAccount = (mint: PublicKey, owner: PublicKey, allowOwnerOffCurve = false) =>
+      getAssociatedTokenAddressSync(
+        mint,
+        owner,
+        allowOwnerOffCurve,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+    const updateAccounts = {
+      admin: adminWallet.publicKey,
+      dexState: dexStatePda,
+      extensionAllowlist: extensionAllowlistPda,
+    };
+
+    // Stand-in hook program: pool creation never transfers, so it doesn't need to exist
+    const hookProgramId = Keypair.generate().publicKey;
+    const hookMintKeypair = Keypair.generate();
+    const hookMint = hookMintKeypair.publicKey;
+    const mintLen = getMintLen([ExtensionType.TransferHook]);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: poolOwner.publicKey,
+          newAccountPubkey: hookMint,
+          space: mintLen,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(mintLen),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferHookInstruction(
+          hookMint,
+          poolOwner.publicKey,
+          hookProgramId,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(hookMint, 6, poolOwner.publicKey, null, TOKEN_2022_PROGRAM_ID)
+      ),
+      [poolOwner, hookMintKeypair]
+    );
+
+    const [hookPoolPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("liquidity_pool"), hookMint.toBuffer(), tokenBMint.toBuffer(), feeTierSeed],
+      program.programId
+    );
+    const hookPoolTokenA = userAccount(hookMint, hookPoolPda, true);
+    const hookPoolTokenB = userAccount(tokenBMint, hookPoolPda, true);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          hookPoolTokenA,
+          hookPoolPda,
+          hookMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          hookPoolTokenB,
+          hookPoolPda,
+          tokenBMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      ),
+      [poolOwner]
+    );
+
+    const createHookPool = async () => {
+      const hookLpTokenMintKeypair = Keypair.generate();
+      await program.methods
+        .createPool(feeTierIndex)
+        .accounts({
+          owner: poolOwner.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          extensionAllowlist: extensionAllowlistPda,
+          tokenAMint: hookMint,
+          tokenBMint,
+          ...(await poolRegistryAccounts(hookMint, tokenBMint)),
+          pool: hookPoolPda,
+          poolTokenA: hookPoolTokenA,
+          poolTokenB: hookPoolTokenB,
+          lpTokenMint: hookLpTokenMintKeypair.publicKey,
+          ownerLpToken: userAccount(hookLpTokenMintKeypair.publicKey, poolOwner.publicKey),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner, hookLpTokenMintKeypair])
+        .rpc();
+    };
+
+    // Unknown hook programs are rejected
+    try {
+      await createHookPool();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintExtensionNotAllowed");
+    }
+
+    // Hooks can't be allowed wholesale through the extension type
+    try {
+      await program.methods
+        .setAllowedExtension(ExtensionType.TransferHook, true)
+        .accounts(updateAccounts)
+        .rpc();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("InvalidExtensionType");
+    }
+
+    await program.methods
+      .setAllowedHookProgram(hookProgramId, true)
+      .accounts(updateAccounts)
+      .rpc();
+
+    // The accepted hook program is pinned on the pool
+    await createHookPool();
+    const hookPool = await program.account.liquidityPool.fetch(hookPoolPda);
+    expect(hookPool.tokenATransferHook.toBase58()).to.equal(hookProgramId.toBase58());
+    expect(hookPool.tokenBTransferHook.toBase58()).to.equal(PublicKey.default.toBase58());
+  });
+
+  // NOTE: Pool registry
+  it("Lists every pool in the registry and rejects a reversed pair on the same fee tier", async () => {
+    const dexState = await program.account.dexState.fetch(dexStatePda);
+    const poolsCount = dexState.poolsCount.toNumber();
+
+    // Walk the registry pages: pool n is entry n % 16 of page n / 16
+    const entries = [];
+    for (let pageIndex = 0; pageIndex * POOLS_PER_REGISTRY_PAGE < poolsCount; pageIndex++) {
+      const [pagePda] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool_registry"),
+          dexStatePda.toBuffer(),
+          new anchor.BN(pageIndex).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+      const page = await program.account.poolRegistryPage.fetch(pagePda);
+      expect(page.pageIndex.toNumber()).to.equal(pageIndex);
+      entries.push(...page.entries.slice(0, page.entriesCount));
+    }
+    expect(entries.length).to.equal(poolsCount);
+
+    // The first pool is listed with its mints and knows its position
+    const pool = await program.account.liquidityPool.fetch(poolPda);
+    const entry = entries[pool.registryIndex.toNumber()];
+    expect(entry.pool.toString()).to.equal(poolPda.toString());
+    expect(entry.tokenAMint.toString()).to.equal(tokenAMint.toString());
+    expect(entry.tokenBMint.toString()).to.equal(tokenBMint.toString());
+    expect(entry.createdSlot.toNumber()).to.be.greaterThan(0);
+
+    // The pair lookup finds the pool on its fee tier
+    const { poolPair } = await poolRegistryAccounts(tokenBMint, tokenAMint);
+    const pair = await program.account.poolPair.fetch(poolPair);
+    const position = pair.feeTierIndexes
+      .slice(0, pair.poolsCount)
+      .indexOf(feeTierIndex);
+    expect(pair.pools[position].toString()).to.equal(poolPda.toString());
+
+    // The same pair with the mints swapped can't get a second pool on the fee tier
+    const [reversedPoolPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("liquidity_pool"),
+        tokenBMint.toBuffer(),
+        tokenAMint.toBuffer(),
+        feeTierSeed,
+      ],
+      program.programId
+    );
+    const reversedPoolTokenA = getAssociatedTokenAddressSync(
+      tokenBMint,
+      reversedPoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+    const reversedPoolTokenB = getAssociatedTokenAddressSync(
+      tokenAMint,
+      reversedPoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          reversedPoolTokenA,
+          reversedPoolPda,
+          tokenBMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          reversedPoolTokenB,
+          reversedPoolPda,
+          tokenAMint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      ),
+      [poolOwner]
+    );
+
+    const reversedLpTokenMintKeypair = Keypair.generate();
+    try {
+      await program.methods
+        .createPool(feeTierIndex)
+        .accounts({
+          owner: poolOwner.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          tokenAMint: tokenBMint,
+          tokenBMint: tokenAMint,
+          ...(await poolRegistryAccounts(tokenBMint, tokenAMint)),
+          pool: reversedPoolPda,
+          poolTokenA: reversedPoolTokenA,
+          poolTokenB: reversedPoolTokenB,
+          lpTokenMint: reversedLpTokenMintKeypair.publicKey,
+          ownerLpToken: getAssociatedTokenAddressSync(
+            reversedLpTokenMintKeypair.publicKey,
+            poolOwner.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner, reversedLpTokenMintKeypair])
+        .rpc();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("PoolAlreadyExists");
+    }
+  });
+
+  // NOTE: Closing pools
+  it("Closes an empty pool and returns the rent to its creator", async () => {
+    const userAccount = (mint: PublicKey, owner: PublicKey, allowOwnerOffCurve = false) =>
+      getAssociatedTokenAddressSync(
+        mint,
+        owner,
+        allowOwnerOffCurve,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+    const createPairMint = async () => {
+      const mintKeypair = Keypair.generate();
+      await createMint(
+        provider.connection,
+        poolOwner,
+        poolOwner.publicKey,
+        null,
+        6,
+        mintKeypair,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return mintKeypair.publicKey;
+    };
+    const closeMintA = await createPairMint();
+    const closeMintB = await createPairMint();
+
+    const [closePoolPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("liquidity_pool"),
+        closeMintA.toBuffer(),
+        closeMintB.toBuffer(),
+        feeTierSeed,
+      ],
+      program.programId
+    );
+    const closePoolTokenA = userAccount(closeMintA, closePoolPda, true);
+    const closePoolTokenB = userAccount(closeMintB, closePoolPda, true);
+    const ownerTokenA = userAccount(closeMintA, poolOwner.publicKey);
+    const ownerTokenB = userAccount(closeMintB, poolOwner.publicKey);
+    const collectorTokenA = userAccount(closeMintA, feeCollector.publicKey);
+    const collectorTokenB = userAccount(closeMintB, feeCollector.publicKey);
+    const closeLpTokenMintKeypair = Keypair.generate();
+    const closeLpTokenMint = closeLpTokenMintKeypair.publicKey;
+    const ownerLpToken = userAccount(closeLpTokenMint, poolOwner.publicKey);
+
+    const setupTx = new Transaction();
+    for (const [account, owner, mint] of [
+      [closePoolTokenA, closePoolPda, closeMintA],
+      [closePoolTokenB, closePoolPda, closeMintB],
+      [ownerTokenA, poolOwner.publicKey, closeMintA],
+      [ownerTokenB, poolOwner.publicKey, closeMintB],
+      [collectorTokenA, feeCollector.publicKey, closeMintA],
+      [collectorTokenB, feeCollector.publicKey, closeMintB],
+    ]) {
+      setupTx.add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          account,
+          owner,
+          mint,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      );
+    }
+    setupTx.add(
+      createMintToInstruction(closeMintA, ownerTokenA, poolOwner.publicKey, 1_000_000, [], TOKEN_2022_PROGRAM_ID),
+      createMintToInstruction(closeMintB, ownerTokenB, poolOwner.publicKey, 1_000_000, [], TOKEN_2022_PROGRAM_ID)
+    );
+    await provider.sendAndConfirm(setupTx, [poolOwner]);
+
+    const { registryPage, poolPair } = await poolRegistryAccounts(closeMintA, closeMintB);
+    await program.methods
+      .createPool(feeTierIndex)
+      .accounts({
+        owner: poolOwner.publicKey,
+        dexState: dexStatePda,
+        feeTier: feeTierPda,
+        tokenAMint: closeMintA,
+        tokenBMint: closeMintB,
+        registryPage,
+        poolPair,
+        pool: closePoolPda,
+        poolTokenA: closePoolTokenA,
+        poolTokenB: closePoolTokenB,
+        lpTokenMint: closeLpTokenMint,
+        ownerLpToken,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .signers([poolOwner, closeLpTokenMintKeypair])
+      .rpc();
+
+    const liquidityAccounts = {
+      owner: poolOwner.publicKey,
+      pool: closePoolPda,
+      dexState: dexStatePda,
+      tokenAMint: closeMintA,
+      tokenBMint: closeMintB,
+      poolTokenA: closePoolTokenA,
+      poolTokenB: closePoolTokenB,
+      lpTokenMint: closeLpTokenMint,
+      userTokenA: ownerTokenA,
+      userTokenB: ownerTokenB,
+      userLpToken: ownerLpToken,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+    };
+    await program.methods
+      .depositLiquidity(new anchor.BN(500_000), new anchor.BN(500_000))
+      .accounts(liquidityAccounts)
+      .signers([poolOwner])
+      .rpc();
+
+    const closeAccounts = (authority: PublicKey) => ({
+      authority,
+      creator: poolOwner.publicKey,
+      dexState: dexStatePda,
+      pool: closePoolPda,
+      registryPage,
+      poolPair,
+      tokenAMint: closeMintA,
+      tokenBMint: closeMintB,
+      poolTokenA: closePoolTokenA,
+      poolTokenB: closePoolTokenB,
+      lpTokenMint: closeLpTokenMint,
+      feeCollectorTokenA: collectorTokenA,
+      feeCollectorTokenB: collectorTokenB,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+    });
+
+    // A pool with liquidity can't be closed
+    try {
+      await program.methods
+        .closePool()
+        .accounts(closeAccounts(poolOwner.publicKey))
+        .signers([poolOwner])
+        .rpc();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("PoolNotEmpty");
+    }
+
+    const pool = await program.account.liquidityPool.fetch(closePoolPda);
+    expect(pool.creator.toString()).to.equal(poolOwner.publicKey.toString());
+    await program.methods
+      .withdrawLiquidity(pool.totalLiquidity)
+      .accounts(liquidityAccounts)
+      .signers([poolOwner])
+      .rpc();
+
+    // Only the creator or the admin can close it
+    try {
+      await program.methods
+        .closePool()
+        .accounts(closeAccounts(unauthorizedAttacker.publicKey))
+        .signers([unauthorizedAttacker])
+        .rpc();
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotPoolCreator");
+    }
+
+    const closedPoolsBefore = (await program.account.dexState.fetch(dexStatePda)).closedPoolsCount;
+    const creatorBalanceBefore = await provider.connection.getBalance(poolOwner.publicKey);
+    await program.methods
+      .closePool()
+      .accounts(closeAccounts(admin.publicKey))
+      .rpc();
+
+    // Pool, vaults and LP mint are gone and their rent went to the creator
+    for (const account of [closePoolPda, closePoolTokenA, closePoolTokenB, closeLpTokenMint]) {
+      expect(await provider.connection.getAccountInfo(account)).to.be.null;
+    }
+    const creatorBalanceAfter = await provider.connection.getBalance(poolOwner.publicKey);
+    expect(creatorBalanceAfter).to.be.greaterThan(creatorBalanceBefore);
+
+    // The registry keeps the entry flagged as closed, the pair lookup forgets the pool
+    const page = await program.account.poolRegistryPage.fetch(registryPage);
+    const entry = page.entries[pool.registryIndex.toNumber() % POOLS_PER_REGISTRY_PAGE];
+    expect(entry.pool.toString()).to.equal(closePoolPda.toString());
+    expect(entry.closed).to.be.true;
+    const pair = await program.account.poolPair.fetch(poolPair);
+    expect(pair.poolsCount).to.equal(0);
+    const dexState = await program.account.dexState.fetch(dexStatePda);
+    expect(dexState.closedPoolsCount.toNumber()).to.equal(closedPoolsBefore.toNumber() + 1);
+  });
+
+  // NOTE: Accounts are versioned, older layouts are upgraded in place by the migrate instructions
+  it("Creates accounts with the current layout version and refuses to migrate them again", async () => {
+    const dexState = await program.account.dexState.fetch(dexStatePda);
+    expect(dexState.version).to.equal(DEX_STATE_VERSION);
+
+    // Only the admin can migrate the DEX state
+    try {
+      await program.methods
+        .migrateState()
+        .accounts({ admin: unauthorizedAttacker.publicKey })
+        .signers([unauthorizedAttacker])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    try {
+      await program.methods
+        .migrateState()
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("AccountAlreadyMigrated");
+    }
+  });
+
+  // NOTE: Creation metadata and the permissions governance grants to pool creators
+  it("Records pool creation metadata and lets permitted creators configure their pools", async () => {
+    const pool = await program.account.liquidityPool.fetch(poolPda);
+    expect(pool.creator.toBase58()).to.equal(poolOwner.publicKey.toBase58());
+    expect(pool.createdAt.toNumber()).to.be.greaterThan(0);
+    expect(pool.createdSlot.toNumber()).to.be.greaterThan(0);
+    expect(pool.version).to.equal(LIQUIDITY_POOL_VERSION);
+
+    // Pools created by this program version already have the current layout
+    try {
+      await program.methods
+        .migratePool()
+        .accounts({ payer: admin.publicKey, pool: poolPda })
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("AccountAlreadyMigrated");
+    }
+
+    const [creatorPermissionsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("creator_permissions"), dexStatePda.toBuffer(), poolOwner.publicKey.toBuffer()],
+      program.programId
+    );
+    const setPermissions = (permissions: number) =>
+      program.methods
+        .setCreatorPermissions(poolOwner.publicKey, permissions)
+        .accounts({
+          admin: admin.publicKey,
+          dexState: dexStatePda,
+          creatorPermissions: creatorPermissionsPda,
+        })
+        .rpc();
+    const configureAsCreator = (creatorPermissions: PublicKey | null) =>
+      program.methods
+        .configureDynamicFee(null)
+        .accounts({
+          authority: poolOwner.publicKey,
+          dexState: dexStatePda,
+          pool: poolPda,
+          creatorPermissions,
+        })
+        .signers([poolOwner])
+        .rpc();
+
+    // Without a grant the creator can't change the fee mode
+    try {
+      await configureAsCreator(null);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    // Unknown permission bits are rejected
+    try {
+      await setPermissions(0x80);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("InvalidPermissions");
+    }
+
+    await setPermissions(PERMISSION_CONFIGURE_DYNAMIC_FEE);
+    await configureAsCreator(creatorPermissionsPda);
+
+    // Revoking the grant takes the right back
+    await setPermissions(0);
+    try {
+      await configureAsCreator(creatorPermissionsPda);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+  });
+
+  // NOTE: Governance can restrict pool creation to the admin or to allowlisted creators
+  it("Restricts pool creation according to the pool creation mode", async () => {
+    const createPairMint = async () => {
+      const mintKeypair = Keypair.generate();
+      await createMint(
+        provider.connection,
+        poolOwner,
+        poolOwner.publicKey,
+        null,
+        6,
+        mintKeypair,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return mintKeypair.publicKey;
+    };
+    const modeMintA = await createPairMint();
+    const modeMintB = await createPairMint();
+    const [modePoolPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("liquidity_pool"), modeMintA.toBuffer(), modeMintB.toBuffer(), feeTierSeed],
+      program.programId
+    );
+    const vaultA = getAssociatedTokenAddressSync(
+      modeMintA,
+      modePoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+    const vaultB = getAssociatedTokenAddressSync(
+      modeMintB,
+      modePoolPda,
+      true,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID
+    );
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          vaultA,
+          modePoolPda,
+          modeMintA,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        ),
+        createAssociatedTokenAccountInstruction(
+          poolOwner.publicKey,
+          vaultB,
+          modePoolPda,
+          modeMintB,
+          TOKEN_2022_PROGRAM_ID,
+          ASSOCIATED_PROGRAM_ID
+        )
+      ),
+      [poolOwner]
+    );
+
+    const [creatorPermissionsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("creator_permissions"), dexStatePda.toBuffer(), poolOwner.publicKey.toBuffer()],
+      program.programId
+    );
+    const setMode = (mode: object) =>
+      program.methods
+        .setPoolCreationMode(mode as any)
+        .accounts({ admin: admin.publicKey, dexState: dexStatePda })
+        .rpc();
+    const createModePool = async (creatorPermissions: PublicKey | null) => {
+      const lpTokenMintKeypair = Keypair.generate();
+      const { registryPage, poolPair } = await poolRegistryAccounts(modeMintA, modeMintB);
+      await program.methods
+        .createPool(feeTierIndex)
+        .accounts({
+          owner: poolOwner.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          creatorPermissions,
+          tokenAMint: modeMintA,
+          tokenBMint: modeMintB,
+          registryPage,
+          poolPair,
+          pool: modePoolPda,
+          poolTokenA: vaultA,
+          poolTokenB: vaultB,
+          lpTokenMint: lpTokenMintKeypair.publicKey,
+          ownerLpToken: getAssociatedTokenAddressSync(
+            lpTokenMintKeypair.publicKey,
+            poolOwner.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner, lpTokenMintKeypair])
+        .rpc();
+    };
+
+    // Only the admin can change the mode
+    try {
+      await program.methods
+        .setPoolCreationMode({ adminOnly: {} })
+        .accounts({ admin: unauthorizedAttacker.publicKey, dexState: dexStatePda })
+        .signers([unauthorizedAttacker])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    await setMode({ adminOnly: {} });
+    const dexState = await program.account.dexState.fetch(dexStatePda);
+    expect(dexState.poolCreationMode).to.deep.equal({ adminOnly: {} });
+
+    try {
+      await createModePool(null);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("PoolCreationNotAllowed");
+    }
+
+    // Allowlisted creators need the create pool permission
+    await setMode({ allowlisted: {} });
+    await program.methods
+      .setCreatorPermissions(poolOwner.publicKey, 0)
+      .accounts({ admin: admin.publicKey, dexState: dexStatePda, creatorPermissions: creatorPermissionsPda })
+      .rpc();
+    try {
+      await createModePool(creatorPermissionsPda);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("PoolCreationNotAllowed");
+    }
+
+    await program.methods
+      .setCreatorPermissions(poolOwner.publicKey, PERMISSION_CREATE_POOL)
+      .accounts({ admin: admin.publicKey, dexState: dexStatePda, creatorPermissions: creatorPermissionsPda })
+      .rpc();
+    await createModePool(creatorPermissionsPda);
+
+    const pool = await program.account.liquidityPool.fetch(modePoolPda);
+    expect(pool.creator.toBase58()).to.equal(poolOwner.publicKey.toBase58());
+
+    // Back to open creation for the other tests
+    await setMode({ open: {} });
+  });
+  // NOTE: Governance can deny a mint, blocking new pools and swaps of it, or only accept allowed mints
+  it("Blocks denied mints from new pools and from trading", async () => {
+    const [tokenAMintStatusPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_status"), dexStatePda.toBuffer(), tokenAMint.toBuffer()],
+      program.programId
+    );
+    const setMintStatus = (mint: PublicKey, mintStatus: PublicKey, status: object) =>
+      program.methods
+        .setMintStatus(mint, status as any)
+        .accounts({ admin: admin.publicKey, dexState: dexStatePda, mintStatus })
+        .rpc();
+    const swapTokenA = () =>
+      program.methods
+        .swap(new anchor.BN(1_000), new anchor.BN(0))
+        .accounts({
+          owner: poolOwner.publicKey,
+          pool: poolPda,
+          sourceMint: tokenAMint,
+          destinationMint: tokenBMint,
+          dexState: dexStatePda,
+          poolTokenA,
+          poolTokenB,
+          userSourceToken: getAssociatedTokenAddressSync(
+            tokenAMint,
+            poolOwner.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          userDestinationToken: getAssociatedTokenAddressSync(
+            tokenBMint,
+            poolOwner.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner])
+        .rpc();
+
+    // Only the admin can list mints
+    try {
+      await program.methods
+        .setMintStatus(tokenAMint, { denied: {} } as any)
+        .accounts({
+          admin: unauthorizedAttacker.publicKey,
+          dexState: dexStatePda,
+          mintStatus: tokenAMintStatusPda,
+        })
+        .signers([unauthorizedAttacker])
+        .rpc();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("NotAdmin");
+    }
+
+    // A denied mint can't be traded in existing pools
+    await setMintStatus(tokenAMint, tokenAMintStatusPda, { denied: {} });
+    const mintStatus = await program.account.mintStatus.fetch(tokenAMintStatusPda);
+    expect(mintStatus.status).to.deep.equal({ denied: {} });
+    try {
+      await swapTokenA();
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintDenied");
+    }
+
+    // Nor paired in new pools
+    const createPairMint = async () => {
+      const mintKeypair = Keypair.generate();
+      await createMint(
+        provider.connection,
+        poolOwner,
+        poolOwner.publicKey,
+        null,
+        6,
+        mintKeypair,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return mintKeypair.publicKey;
+    };
+    const createListedPool = async (mintA: PublicKey, mintB: PublicKey) => {
+      const [listedPoolPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("liquidity_pool"), mintA.toBuffer(), mintB.toBuffer(), feeTierSeed],
+        program.programId
+      );
+      const vaultA = getAssociatedTokenAddressSync(
+        mintA,
+        listedPoolPda,
+        true,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+      const vaultB = getAssociatedTokenAddressSync(
+        mintB,
+        listedPoolPda,
+        true,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_PROGRAM_ID
+      );
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createAssociatedTokenAccountInstruction(
+            poolOwner.publicKey,
+            vaultA,
+            listedPoolPda,
+            mintA,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          createAssociatedTokenAccountInstruction(
+            poolOwner.publicKey,
+            vaultB,
+            listedPoolPda,
+            mintB,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          )
+        ),
+        [poolOwner]
+      );
+
+      const lpTokenMintKeypair = Keypair.generate();
+      const { registryPage, poolPair } = await poolRegistryAccounts(mintA, mintB);
+      await program.methods
+        .createPool(feeTierIndex)
+        .accounts({
+          owner: poolOwner.publicKey,
+          dexState: dexStatePda,
+          feeTier: feeTierPda,
+          creatorPermissions: null,
+          tokenAMint: mintA,
+          tokenBMint: mintB,
+          registryPage,
+          poolPair,
+          pool: listedPoolPda,
+          poolTokenA: vaultA,
+          poolTokenB: vaultB,
+          lpTokenMint: lpTokenMintKeypair.publicKey,
+          ownerLpToken: getAssociatedTokenAddressSync(
+            lpTokenMintKeypair.publicKey,
+            poolOwner.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID,
+            ASSOCIATED_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          lpTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([poolOwner, lpTokenMintKeypair])
+        .rpc();
+    };
+    const pairMint = await createPairMint();
+    try {
+      await createListedPool(tokenAMint, pairMint);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintDenied");
+    }
+
+    // Lifting the denial restores trading without touching the pool
+    await setMintStatus(tokenAMint, tokenAMintStatusPda, { unknown: {} });
+    await swapTokenA();
+
+    // Once allowed mints are required, unlisted mints can't be paired
+    const setRequireAllowedMints = (required: boolean) =>
+      program.methods
+        .setRequireAllowedMints(required)
+        .accounts({ admin: admin.publicKey, dexState: dexStatePda })
+        .rpc();
+    await setRequireAllowedMints(true);
+    const otherPairMint = await createPairMint();
+    try {
+      await createListedPool(pairMint, otherPairMint);
+
+      throw new Error("Expected transaction to fail, but it succeeded");
+    } catch (err: any) {
+      expect(err.error.errorCode.code).to.equal("MintNotAllowed");
+    }
+
+    for (const mint of [pairMint, otherPairMint]) {
+      const [mintStatusPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("mint_status"), dexStatePda.toBuffer(), mint.toBuffer()],
+        program.programId
+      );
+      await setMintStatus(mint, mintStatusPda, { allowed: {} });
+    }
+    await createListedPool(pairMint, otherPairMint);
+
+    // Back to accepting any mint that isn't denied for the other tests
+    await setRequireAllowedMints(false);
+  });
+});
+
+/**
+ * Registry page and mint pair lookup accounts of the next pool created on the DEX.
+ */
+async function poolRegistryAccounts(
+  tokenAMint: PublicKey,
+  tokenBMint: PublicKey
+): Promise<{ registryPage: PublicKey; poolPair: PublicKey }> {
+  const [dexStatePda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("dex_state"), provider.wallet.publicKey.toBuffer()],
+    program.programId
+  );
+  const dexState = await program.account.dexState.fetch(dexStatePda);
+  const pageIndex = dexState.poolsCount.divn(POOLS_PER_REGISTRY_PAGE);
+  const [registryPage] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("pool_registry"),
+      dexStatePda.toBuffer(),
+      pageIndex.toArrayLike(Buffer, "le", 8),
+    ],
+    program.programId
+  );
+
+  const [mint0, mint1] =
+    Buffer.compare(tokenAMint.toBuffer(), tokenBMint.toBuffer()) <= 0
+      ? [tokenAMint, tokenBMint]
+      : [tokenBMint, tokenAMint];
+  const [poolPair] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("pool_pair"),
+      dexStatePda.toBuffer(),
+      mint0.toBuffer(),
+      mint1.toBuffer(),
+    ],
+    program.programId
+  );
+
+  return { registryPage, poolPair };
+}
 
 /**
  * Airdrops SOL to a given public key if the balance is below a specified threshold.